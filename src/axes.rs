@@ -0,0 +1,140 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+use smooth_bevy_cameras::{controllers::orbit::OrbitCameraController, LookTransform};
+
+use crate::{block_model::BlockLayer, layers::LayerEvent};
+
+/// Which model axis is drawn vertically.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AxisConvention {
+    /// Model Z (elevation) is up, model Y is north.
+    ZUp,
+    /// Model coordinates are used as is, with Y up.
+    YUp,
+}
+
+impl AxisConvention {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AxisConvention::ZUp => "Z up",
+            AxisConvention::YUp => "Y up",
+        }
+    }
+}
+
+/// Scene-wide mapping from model coordinates to world coordinates.
+///
+/// The mapping is linear, so the same functions convert directions.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SceneAxes {
+    pub convention: AxisConvention,
+    /// Scale applied to the vertical model axis.
+    pub exaggeration: f32,
+}
+
+impl Default for SceneAxes {
+    fn default() -> Self {
+        Self {
+            convention: AxisConvention::ZUp,
+            exaggeration: 1.0,
+        }
+    }
+}
+
+impl SceneAxes {
+    /// The model to world mapping as a transform, for entities built in model coordinates.
+    pub fn transform(&self) -> Transform {
+        match self.convention {
+            AxisConvention::ZUp => Transform {
+                rotation: Quat::from_rotation_x(-FRAC_PI_2),
+                scale: Vec3::new(1.0, 1.0, self.exaggeration),
+                ..default()
+            },
+            AxisConvention::YUp => Transform::from_scale(Vec3::new(1.0, self.exaggeration, 1.0)),
+        }
+    }
+
+    pub fn to_world(&self, p: Vec3) -> Vec3 {
+        match self.convention {
+            AxisConvention::ZUp => Vec3::new(p.x, p.z * self.exaggeration, -p.y),
+            AxisConvention::YUp => Vec3::new(p.x, p.y * self.exaggeration, p.z),
+        }
+    }
+
+    pub fn to_model(&self, p: Vec3) -> Vec3 {
+        match self.convention {
+            AxisConvention::ZUp => Vec3::new(p.x, -p.z, p.y / self.exaggeration),
+            AxisConvention::YUp => Vec3::new(p.x, p.y / self.exaggeration, p.z),
+        }
+    }
+
+    /// Axis aligned box in world coordinates covering a model space box.
+    pub fn box_to_world(&self, minimum: Vec3, maximum: Vec3) -> (Vec3, Vec3) {
+        let (a, b) = (self.to_world(minimum), self.to_world(maximum));
+        (a.min(b), a.max(b))
+    }
+
+    /// Axis aligned box in model coordinates covering a world space box.
+    pub fn box_to_model(&self, minimum: Vec3, maximum: Vec3) -> (Vec3, Vec3) {
+        let (a, b) = (self.to_model(minimum), self.to_model(maximum));
+        (a.min(b), a.max(b))
+    }
+
+    /// A world ray in model coordinates. The direction is not renormalised, so distances
+    /// along the ray are the same in both spaces.
+    pub fn ray_to_model(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.to_model(ray.origin),
+            direction: self.to_model(ray.direction),
+        }
+    }
+
+    /// Gizmo transform for a model space box.
+    pub fn box_transform(&self, minimum: Vec3, maximum: Vec3) -> Transform {
+        self.transform().mul_transform(
+            Transform::from_translation((minimum + maximum) / 2.0).with_scale(maximum - minimum),
+        )
+    }
+}
+
+/// Marks entities built in model coordinates, whose transform follows `SceneAxes`.
+#[derive(Component, Default)]
+pub struct ModelSpace;
+
+/// Re-applies the axis mapping when it changes: rebuilds block layers, updates model
+/// space entities and moves the camera so it keeps looking at the same part of the model.
+pub fn apply_scene_axes(
+    axes: Res<SceneAxes>,
+    mut model_space: Query<(&mut Transform, Ref<ModelSpace>), Without<OrbitCameraController>>,
+    mut cameras: Query<&mut LookTransform, With<OrbitCameraController>>,
+    layers: Query<&BlockLayer>,
+    mut layer_events: EventWriter<LayerEvent>,
+    mut previous: Local<Option<SceneAxes>>,
+) {
+    let old = previous.replace(*axes).unwrap_or(*axes);
+    let changed = old != *axes;
+
+    for (mut transform, marker) in model_space.iter_mut() {
+        if changed || marker.is_added() {
+            *transform = axes.transform();
+        }
+    }
+    if !changed {
+        return;
+    }
+
+    for layer in layers.iter().cloned().collect::<HashSet<_>>() {
+        layer_events.send(LayerEvent::Spawn(layer));
+    }
+
+    for mut look in cameras.iter_mut() {
+        let up = axes.to_world(old.to_model(look.up));
+        *look = LookTransform::new(
+            axes.to_world(old.to_model(look.eye)),
+            axes.to_world(old.to_model(look.target)),
+            up.try_normalize().unwrap_or(Vec3::Y),
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+
+use bevy::{app::AppExit, prelude::*};
+use polars::prelude::{CsvReader, SerReader};
+use serde::Deserialize;
+
+use crate::{
+    block_model::{BlockLayer, BlockModel, BlockModelDB},
+    export::{ExportRequest, ImageExport},
+    layers::LayerEvent,
+    ui::ViewAll,
+    ColorBarSelectionEvent,
+};
+
+/// Frames to let spawned layers settle before framing and capturing them.
+const SETTLE_FRAMES: u32 = 3;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct BatchModel {
+    pub path: PathBuf,
+    pub name: String,
+    pub x: String,
+    pub y: String,
+    pub z: String,
+    pub x_size: String,
+    pub y_size: String,
+    pub z_size: String,
+    /// Columns drawn as layers.
+    #[serde(default)]
+    pub columns: Vec<String>,
+}
+
+fn default_width() -> u32 {
+    1920
+}
+
+fn default_height() -> u32 {
+    1080
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Configuration of a headless run, read from the JSON file given to `--batch`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct BatchConfig {
+    pub models: Vec<BatchModel>,
+    pub output: PathBuf,
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(default = "default_true")]
+    pub color_bars: bool,
+}
+
+impl BatchConfig {
+    /// Reads the config named by `--batch <config.json>`, if present on the command line.
+    pub fn from_args() -> Option<Result<Self, String>> {
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg != "--batch" {
+                continue;
+            }
+            let Some(path) = args.next() else {
+                return Some(Err("--batch expects a config file".into()));
+            };
+            return Some(
+                std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Cannot read {}: {}", path, e))
+                    .and_then(|s| {
+                        serde_json::from_str(&s).map_err(|e| format!("Invalid {}: {}", path, e))
+                    }),
+            );
+        }
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BatchStage {
+    Load,
+    Settle(u32),
+    Frame(u32),
+    Export,
+    Wait { completed: usize, failed: usize },
+}
+
+#[derive(Resource)]
+pub struct Batch {
+    config: BatchConfig,
+    stage: BatchStage,
+}
+
+impl Batch {
+    pub fn new(config: BatchConfig) -> Self {
+        Self {
+            config,
+            stage: BatchStage::Load,
+        }
+    }
+
+    /// Size of the image written, which overlays are laid out for as there is no window.
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.config.width as f32, self.config.height as f32)
+    }
+}
+
+/// Drives a headless run: loads the configured models, draws their layers, frames them,
+/// writes the image and exits.
+pub fn run_batch(
+    mut batch: ResMut<Batch>,
+    mut block_models: ResMut<BlockModelDB>,
+    mut layer_events: EventWriter<LayerEvent>,
+    mut colorbar_events: EventWriter<ColorBarSelectionEvent>,
+    mut view_all: EventWriter<ViewAll>,
+    mut export: ResMut<ImageExport>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let batch = &mut *batch;
+    batch.stage = match batch.stage {
+        BatchStage::Load => {
+            for model in batch.config.models.iter() {
+                let df = match CsvReader::from_path(&model.path)
+                    .and_then(|reader| reader.has_header(true).finish())
+                {
+                    Ok(df) => df,
+                    Err(e) => {
+                        error!("Cannot read {}: {}", model.path.display(), e);
+                        exit.send(AppExit);
+                        return;
+                    }
+                };
+                let bm = BlockModel::new(
+                    model.name.clone(),
+                    df,
+                    model.x.clone(),
+                    model.y.clone(),
+                    model.z.clone(),
+                    model.x_size.clone(),
+                    model.y_size.clone(),
+                    model.z_size.clone(),
+                );
+                block_models.block_models.insert(model.name.clone(), bm);
+
+                for column in model.columns.iter() {
+                    layer_events.send(LayerEvent::Spawn(BlockLayer {
+                        grid: model.name.clone(),
+                        column: column.clone(),
+                    }));
+                    if batch.config.color_bars {
+                        colorbar_events.send(ColorBarSelectionEvent {
+                            grid: model.name.clone(),
+                            column: column.clone(),
+                        });
+                    }
+                }
+            }
+            BatchStage::Settle(0)
+        }
+        BatchStage::Settle(n) if n < SETTLE_FRAMES => BatchStage::Settle(n + 1),
+        BatchStage::Settle(_) => {
+            view_all.send(ViewAll);
+            BatchStage::Frame(0)
+        }
+        BatchStage::Frame(n) if n < SETTLE_FRAMES => BatchStage::Frame(n + 1),
+        BatchStage::Frame(_) => BatchStage::Export,
+        BatchStage::Export => {
+            let (completed, failed) = (export.completed, export.failed);
+            export.request(ExportRequest {
+                path: batch.config.output.clone(),
+                width: batch.config.width,
+                height: batch.config.height,
+                color_bars: batch.config.color_bars,
+                panels: false,
+            });
+            BatchStage::Wait { completed, failed }
+        }
+        BatchStage::Wait { completed, failed } => {
+            //the export logs why it failed
+            if export.completed > completed || export.failed > failed {
+                exit.send(AppExit);
+            }
+            BatchStage::Wait { completed, failed }
+        }
+    };
+}
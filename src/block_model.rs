@@ -1,24 +1,69 @@
-use bevy::{render::primitives::Aabb, utils::HashMap};
+use bevy::utils::HashMap;
 use colorgrad::Gradient;
 use itertools::izip;
 use ordered_float::OrderedFloat;
 use polars::datatypes::DataType;
-use polars::prelude::DataFrame;
+use polars::prelude::{DataFrame, Float32Chunked, PolarsResult, Series};
 
 use bevy::prelude::*;
 use bevy::render::color::Color;
-use bevy_aabb_instancing::{Cuboid, Cuboids};
+
+use crate::{
+    axes::SceneAxes,
+    layers::{value_color, LayerStyle},
+    patching::{build_patches, Patch, PatchBlock},
+    spatial_index::BlockGrid,
+};
 
 #[derive(Resource, Default, Clone)]
 pub struct BlockModelResource {
     pub block_model: Option<BlockModel>,
 }
 
+/// Identifies which block model and column a rendered patch of cuboids belongs to.
+#[derive(Component, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BlockLayer {
+    pub grid: String,
+    pub column: String,
+}
+
+/// DataFrame row of each cuboid instance in a patch, in instance order.
+#[derive(Component, Clone, Debug, Default)]
+pub struct BlockRows(pub Vec<usize>);
+
 #[derive(Resource, Default)]
 pub struct BlockModelDB {
     pub block_models: HashMap<String, BlockModel>,
 }
 
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    #[default]
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    pub fn index(&self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Axis::X => "X",
+            Axis::Y => "Y",
+            Axis::Z => "Z",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct BlockModel {
     pub name: String,
@@ -61,6 +106,69 @@ impl BlockModel {
         }
     }
 
+    /// Adds `series` as a column, replacing any existing column of the same name.
+    pub fn set_column(&mut self, series: Series) -> PolarsResult<()> {
+        let name = series.name().to_string();
+        self.df.with_column(series)?;
+        if !self.columns.contains(&name) {
+            self.columns.push(name);
+        }
+        Ok(())
+    }
+
+    /// Column cast to `f32`, or `None` if it is missing or not numeric.
+    pub fn column_f32(&self, column: &str) -> Option<Float32Chunked> {
+        self.df
+            .column(column)
+            .ok()?
+            .cast(&DataType::Float32)
+            .ok()?
+            .f32()
+            .ok()
+            .cloned()
+    }
+
+    /// Coordinate and size column names along `axis`.
+    pub fn axis_columns(&self, axis: Axis) -> (&str, &str) {
+        match axis {
+            Axis::X => (self.x.as_str(), self.x_size.as_str()),
+            Axis::Y => (self.y.as_str(), self.y_size.as_str()),
+            Axis::Z => (self.z.as_str(), self.z_size.as_str()),
+        }
+    }
+
+    /// Block centres along `axis`, in row order.
+    pub fn centres(&self, axis: Axis) -> Option<Vec<Option<f64>>> {
+        let (coord, size) = self.axis_columns(axis);
+        let coord = self.column_f32(coord)?;
+        let size = self.column_f32(size)?;
+        Some(
+            coord
+                .into_iter()
+                .zip(size.into_iter())
+                .map(|(c, s)| Some(c? as f64 + s? as f64 / 2.0))
+                .collect(),
+        )
+    }
+
+    /// Minimum and maximum corner of all blocks.
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        let mut min = Vec3::splat(f32::MAX);
+        let mut max = Vec3::splat(f32::MIN);
+        for axis in Axis::ALL {
+            let (coord, size) = self.axis_columns(axis);
+            let coord = self.column_f32(coord)?;
+            let size = self.column_f32(size)?;
+            for (c, s) in coord.into_iter().zip(size.into_iter()) {
+                if let (Some(c), Some(s)) = (c, s) {
+                    min[axis.index()] = min[axis.index()].min(c);
+                    max[axis.index()] = max[axis.index()].max(c + s);
+                }
+            }
+        }
+        (min.x <= max.x).then_some((min, max))
+    }
+
     pub fn mesh_material(&self, column: String, cmap: Gradient) -> Vec<(Mesh, Color)> {
         fn map_range(from_range: (f64, f64), to_range: (f64, f64), s: f64) -> f64 {
             to_range.0
@@ -197,13 +305,12 @@ impl BlockModel {
         &self,
         column: String,
         cmap: Gradient,
+        range: (f64, f64),
+        style: &LayerStyle,
+        occlusion: Option<&BlockGrid>,
+        axes: &SceneAxes,
         patch_size: usize,
-    ) -> Vec<(Cuboids, Aabb)> {
-        fn map_range(from_range: (f64, f64), to_range: (f64, f64), s: f64) -> f64 {
-            to_range.0
-                + (s - from_range.0) * (to_range.1 - to_range.0) / (from_range.1 - from_range.0)
-        }
-
+    ) -> Vec<Patch> {
         let binding = self
             .df
             .column(self.x.as_str())
@@ -260,30 +367,16 @@ impl BlockModel {
             .expect("column of incorrect datatype");
         let column_values = binding.f32().unwrap();
 
-        let min_value = column_values
-            .into_iter()
-            .filter_map(|v| {
-                if v.is_none() {
-                    return None;
-                }
-                Some(OrderedFloat(v.unwrap() as f64))
-            })
-            .min()
-            .unwrap();
-        let max_value = column_values
-            .into_iter()
-            .filter_map(|v| {
-                if v.is_none() {
-                    return None;
-                }
-                Some(OrderedFloat(v.unwrap() as f64))
-            })
-            .max()
-            .unwrap();
+        //blocks hidden behind opaque neighbours on all six faces are never uploaded
+        let opaque = occlusion.map(|_| {
+            column_values
+                .into_iter()
+                .map(|v| v.map_or(false, |v| style.alpha(v as f64) >= 1.0))
+                .collect::<Vec<_>>()
+        });
 
-        let mut all_cuboids = Vec::new();
-        let mut instances = Vec::with_capacity(patch_size);
-        for (x, y, z, x_size, y_size, z_size, value) in izip!(
+        let mut blocks = Vec::new();
+        for (row, (x, y, z, x_size, y_size, z_size, value)) in izip!(
             x_values,
             y_values,
             z_values,
@@ -291,7 +384,9 @@ impl BlockModel {
             y_size,
             z_size,
             column_values
-        ) {
+        )
+        .enumerate()
+        {
             if x.is_none()
                 || y.is_none()
                 || z.is_none()
@@ -311,27 +406,32 @@ impl BlockModel {
             let z_size = z_size.unwrap() as f32;
             let value = value.unwrap() as f32;
 
-            let mapped_value = map_range((min_value.0, max_value.0), (0.0, 1.0), value as f64);
-
-            let color = cmap.at(mapped_value);
-
-            let encoded_color =
-                Color::rgb(color.r as f32, color.g as f32, color.b as f32).as_rgba_u32();
+            let alpha = style.alpha(value as f64);
+            if alpha <= 0.0 {
+                continue;
+            }
 
             let minimum = Vec3::new(x, y, z);
             let maximum = Vec3::new(x + x_size, y + y_size, z + z_size);
 
-            let cuboid = Cuboid::new(minimum, maximum, encoded_color);
-
-            instances.push(cuboid);
-
-            if instances.len() % patch_size == 0 {
-                let cuboids = Cuboids::new(instances.clone());
-                let aabb = cuboids.aabb();
-                all_cuboids.push((cuboids, aabb));
+            if let (Some(grid), Some(opaque)) = (occlusion, &opaque) {
+                if grid.is_enclosed(minimum, maximum - minimum, |row| opaque[row]) {
+                    continue;
+                }
             }
+
+            let (minimum, maximum) = axes.box_to_world(minimum, maximum);
+            blocks.push(PatchBlock {
+                minimum,
+                maximum,
+                value: value as f64,
+                alpha,
+                row,
+            });
         }
 
-        all_cuboids
+        build_patches(blocks, patch_size, |value, alpha| {
+            value_color(&cmap, range, value, alpha).as_rgba_u32()
+        })
     }
 }
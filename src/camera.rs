@@ -0,0 +1,339 @@
+use bevy::{
+    prelude::*,
+    render::{camera::ScalingMode, primitives::Aabb},
+};
+use bevy_aabb_instancing::Cuboids;
+use serde::{Deserialize, Serialize};
+use smooth_bevy_cameras::{controllers::orbit::OrbitCameraController, LookTransform};
+
+use crate::axes::SceneAxes;
+
+/// Duration of the animated move to a standard view or bookmark.
+const TRANSITION_SECONDS: f32 = 0.6;
+/// Depth range either side of the eye drawn in orthographic mode.
+const ORTHOGRAPHIC_DEPTH: f32 = 1.0e5;
+
+/// Standard views. Z is elevation and +Y is north.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StandardView {
+    Plan,
+    North,
+    South,
+    East,
+    West,
+    Isometric,
+}
+
+impl StandardView {
+    pub const ALL: [StandardView; 6] = [
+        StandardView::Plan,
+        StandardView::North,
+        StandardView::South,
+        StandardView::East,
+        StandardView::West,
+        StandardView::Isometric,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StandardView::Plan => "Plan",
+            StandardView::North => "Looking North",
+            StandardView::South => "Looking South",
+            StandardView::East => "Looking East",
+            StandardView::West => "Looking West",
+            StandardView::Isometric => "Isometric",
+        }
+    }
+
+    /// Unit direction from the target to the eye, and the camera up vector, in model
+    /// coordinates.
+    pub fn orientation(&self) -> (Vec3, Vec3) {
+        match self {
+            StandardView::Plan => (Vec3::Z, Vec3::Y),
+            StandardView::North => (Vec3::NEG_Y, Vec3::Z),
+            StandardView::South => (Vec3::Y, Vec3::Z),
+            StandardView::East => (Vec3::NEG_X, Vec3::Z),
+            StandardView::West => (Vec3::X, Vec3::Z),
+            //from the south-west, looking down at 35°
+            StandardView::Isometric => (Vec3::new(-1.0, -1.0, 1.0).normalize(), Vec3::Z),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum BookmarkProjection {
+    Perspective { fov: f32 },
+    Orthographic { scale: f32 },
+}
+
+/// A named camera position. Points and the up direction are in model coordinates, so
+/// bookmarks stay put when the axis convention or vertical exaggeration changes.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    pub projection: BookmarkProjection,
+}
+
+impl CameraBookmark {
+    pub fn capture(
+        name: String,
+        look: &LookTransform,
+        projection: &Projection,
+        axes: &SceneAxes,
+    ) -> Self {
+        Self {
+            name,
+            eye: axes.to_model(look.eye).into(),
+            target: axes.to_model(look.target).into(),
+            up: axes.to_model(look.up).normalize_or_zero().into(),
+            projection: match projection {
+                Projection::Perspective(p) => BookmarkProjection::Perspective { fov: p.fov },
+                Projection::Orthographic(o) => BookmarkProjection::Orthographic { scale: o.scale },
+            },
+        }
+    }
+
+    pub fn look(&self, axes: &SceneAxes) -> LookTransform {
+        LookTransform::new(
+            axes.to_world(self.eye.into()),
+            axes.to_world(self.target.into()),
+            axes.to_world(self.up.into()).normalize_or_zero(),
+        )
+    }
+
+    pub fn apply_projection(&self, settings: &mut ProjectionSettings, axes: &SceneAxes) {
+        match self.projection {
+            BookmarkProjection::Perspective { fov } => {
+                settings.orthographic = false;
+                settings.fov = fov;
+            }
+            BookmarkProjection::Orthographic { scale } => {
+                //the field of view that maps the bookmarked distance to its scale
+                let look = self.look(axes);
+                let distance = look.eye.distance(look.target);
+                settings.orthographic = true;
+                settings.fov = 2.0 * (scale / distance.max(f32::EPSILON)).atan();
+            }
+        }
+    }
+}
+
+/// Named camera positions, saved with the project.
+#[derive(Resource, Default)]
+pub struct CameraBookmarks {
+    pub bookmarks: Vec<CameraBookmark>,
+}
+
+#[derive(Event, Clone, Debug)]
+pub enum CameraView {
+    Standard(StandardView),
+    Bookmark(CameraBookmark),
+}
+
+struct Transition {
+    from: LookTransform,
+    to: LookTransform,
+    elapsed: f32,
+}
+
+/// The in-progress animated camera move, if any.
+#[derive(Resource, Default)]
+pub struct CameraTransition {
+    transition: Option<Transition>,
+}
+
+impl CameraTransition {
+    pub fn start(&mut self, from: LookTransform, to: LookTransform) {
+        self.transition = Some(Transition {
+            from,
+            to,
+            elapsed: 0.0,
+        });
+    }
+}
+
+/// Projection of the main camera.
+///
+/// In orthographic mode the scale tracks the eye–target distance through `fov`, so orbit
+/// zoom keeps working and toggling projection keeps the current framing.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ProjectionSettings {
+    pub orthographic: bool,
+    /// Vertical field of view, in radians.
+    pub fov: f32,
+}
+
+impl Default for ProjectionSettings {
+    fn default() -> Self {
+        Self {
+            orthographic: false,
+            fov: PerspectiveProjection::default().fov,
+        }
+    }
+}
+
+impl ProjectionSettings {
+    /// Half height of the orthographic view for a camera `distance` from its target.
+    pub fn orthographic_scale(&self, distance: f32) -> f32 {
+        distance * (self.fov / 2.0).tan()
+    }
+}
+
+pub fn sync_projection(
+    settings: Res<ProjectionSettings>,
+    mut cameras: Query<(&LookTransform, &mut Projection), With<OrbitCameraController>>,
+) {
+    let Ok((look, mut projection)) = cameras.get_single_mut() else {
+        return;
+    };
+    let scale = settings.orthographic_scale(look.eye.distance(look.target));
+
+    //only write on change, the projection matrix is rebuilt whenever it is touched
+    let updated = match (&*projection, settings.orthographic) {
+        (Projection::Orthographic(o), true) if o.scale == scale => None,
+        (Projection::Perspective(p), false) if p.fov == settings.fov => None,
+        (_, true) => Some(Projection::Orthographic(OrthographicProjection {
+            scale,
+            near: -ORTHOGRAPHIC_DEPTH,
+            far: ORTHOGRAPHIC_DEPTH,
+            scaling_mode: ScalingMode::FixedVertical(2.0),
+            ..default()
+        })),
+        (_, false) => Some(Projection::Perspective(PerspectiveProjection {
+            fov: settings.fov,
+            ..default()
+        })),
+    };
+    if let Some(updated) = updated {
+        *projection = updated;
+    }
+}
+
+/// Bounds of the visible block model patches.
+pub fn visible_bounds<'a>(
+    boxes: impl Iterator<Item = (&'a Aabb, &'a Visibility)>,
+) -> Option<(Vec3, Vec3)> {
+    boxes
+        .filter(|(_, visibility)| **visibility != Visibility::Hidden)
+        .fold(None, |bounds, (aabb, _)| {
+            let (min, max): (Vec3, Vec3) = (aabb.min().into(), aabb.max().into());
+            Some(bounds.map_or((min, max), |(bmin, bmax): (Vec3, Vec3)| {
+                (bmin.min(min), bmax.max(max))
+            }))
+        })
+}
+
+/// Width over height of the view.
+fn aspect_ratio(projection: &Projection) -> f32 {
+    match projection {
+        Projection::Perspective(p) => p.aspect_ratio,
+        Projection::Orthographic(o) if o.area.height() > 0.0 => o.area.width() / o.area.height(),
+        Projection::Orthographic(_) => 1.0,
+    }
+}
+
+/// Distance from the centre of a sphere of `radius` at which it fills the view. In
+/// orthographic mode this is the distance whose scale (see `ProjectionSettings`) fits it.
+pub fn framing_distance(radius: f32, projection: &Projection, fov: f32) -> f32 {
+    let vertical = fov / 2.0;
+    let horizontal = (vertical.tan() * aspect_ratio(projection)).atan();
+    match projection {
+        Projection::Perspective(_) => radius / vertical.min(horizontal).sin(),
+        Projection::Orthographic(_) => radius / vertical.min(horizontal).tan(),
+    }
+}
+
+/// Look transform framing `bounds` from `direction` (target to eye).
+pub fn frame_bounds(
+    bounds: (Vec3, Vec3),
+    direction: Vec3,
+    up: Vec3,
+    projection: &Projection,
+    fov: f32,
+) -> LookTransform {
+    let centre = (bounds.0 + bounds.1) / 2.0;
+    let radius = ((bounds.1 - bounds.0).length() / 2.0).max(1.0);
+    LookTransform::new(
+        centre + direction * framing_distance(radius, projection, fov),
+        centre,
+        up,
+    )
+}
+
+pub fn set_view(
+    mut view_events: EventReader<CameraView>,
+    cameras: Query<(&LookTransform, &Projection), With<OrbitCameraController>>,
+    bounding_boxes: Query<(&Aabb, &Visibility), With<Cuboids>>,
+    axes: Res<SceneAxes>,
+    mut settings: ResMut<ProjectionSettings>,
+    mut transition: ResMut<CameraTransition>,
+) {
+    let Ok((look, projection)) = cameras.get_single() else {
+        return;
+    };
+    for event in view_events.iter() {
+        let to = match event {
+            CameraView::Standard(view) => {
+                let Some(bounds) = visible_bounds(bounding_boxes.iter()) else {
+                    continue;
+                };
+                let (direction, up) = view.orientation();
+                let direction = axes.to_world(direction).normalize();
+                let up = axes.to_world(up).normalize();
+                frame_bounds(bounds, direction, up, projection, settings.fov)
+            }
+            CameraView::Bookmark(bookmark) => {
+                bookmark.apply_projection(&mut settings, &axes);
+                bookmark.look(&axes)
+            }
+        };
+        transition.start(*look, to);
+    }
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Moves the camera along the active transition, orbiting about the target rather than
+/// cutting through it.
+pub fn animate_camera(
+    time: Res<Time>,
+    mut transition: ResMut<CameraTransition>,
+    mut cameras: Query<&mut LookTransform, With<OrbitCameraController>>,
+) {
+    let Some(t) = transition.transition.as_mut() else {
+        return;
+    };
+    let Ok(mut look) = cameras.get_single_mut() else {
+        return;
+    };
+
+    t.elapsed += time.delta_seconds();
+    let s = smoothstep((t.elapsed / TRANSITION_SECONDS).min(1.0));
+
+    let from_offset = t.from.eye - t.from.target;
+    let to_offset = t.to.eye - t.to.target;
+    let from_direction = from_offset.try_normalize().unwrap_or(Vec3::Z);
+    let to_direction = to_offset.try_normalize().unwrap_or(Vec3::Z);
+    let rotation = Quat::from_rotation_arc(from_direction, to_direction);
+    let direction = Quat::IDENTITY.slerp(rotation, s) * from_direction;
+    let distance = from_offset.length() + (to_offset.length() - from_offset.length()) * s;
+    let target = t.from.target.lerp(t.to.target, s);
+    let up = t
+        .from
+        .up
+        .lerp(t.to.up, s)
+        .try_normalize()
+        .unwrap_or(t.to.up);
+
+    *look = LookTransform::new(target + direction * distance, target, up);
+
+    if s >= 1.0 {
+        *look = t.to;
+        transition.transition = None;
+    }
+}
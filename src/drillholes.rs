@@ -0,0 +1,561 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    math::DVec3,
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    utils::HashMap,
+};
+use polars::prelude::{CsvReader, DataFrame, DataType, SerReader};
+
+use crate::{
+    axes::SceneAxes,
+    layers::value_color,
+    picking::{ClickRay, Selection},
+    stats::ColumnStats,
+};
+
+/// Sides of the polygon swept along a hole to draw it as a tube.
+const TUBE_SIDES: usize = 8;
+/// Colour of intervals without a value for the displayed attribute.
+const MISSING_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
+
+/// Source files and column names of a drillhole database. The hole id column has the
+/// same name in all three tables.
+#[derive(Clone, Debug)]
+pub struct DrillholeFiles {
+    pub collar: PathBuf,
+    pub survey: Option<PathBuf>,
+    pub intervals: PathBuf,
+    pub hole_id: String,
+    pub x: String,
+    pub y: String,
+    pub z: String,
+    pub depth: String,
+    pub azimuth: String,
+    pub dip: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl Default for DrillholeFiles {
+    fn default() -> Self {
+        Self {
+            collar: PathBuf::new(),
+            survey: None,
+            intervals: PathBuf::new(),
+            hole_id: "HOLEID".into(),
+            x: "EAST".into(),
+            y: "NORTH".into(),
+            z: "RL".into(),
+            depth: "DEPTH".into(),
+            azimuth: "AZIMUTH".into(),
+            dip: "DIP".into(),
+            from: "FROM".into(),
+            to: "TO".into(),
+        }
+    }
+}
+
+/// Unit direction of a hole with `azimuth` clockwise from north and `dip` negative
+/// downwards, both in degrees.
+fn direction(azimuth: f64, dip: f64) -> DVec3 {
+    let (azimuth, dip) = (azimuth.to_radians(), dip.to_radians());
+    DVec3::new(
+        dip.cos() * azimuth.sin(),
+        dip.cos() * azimuth.cos(),
+        dip.sin(),
+    )
+}
+
+/// Displacement along a minimum curvature arc of `length` from tangent `t1` to `t2`.
+fn min_curvature_step(t1: DVec3, t2: DVec3, length: f64) -> DVec3 {
+    let dogleg = t1.dot(t2).clamp(-1.0, 1.0).acos();
+    let ratio = if dogleg < 1e-9 {
+        1.0
+    } else {
+        2.0 / dogleg * (dogleg / 2.0).tan()
+    };
+    (t1 + t2) * (length / 2.0 * ratio)
+}
+
+/// Tangent a fraction `f` of the way around the arc from `t1` to `t2`.
+fn slerp(t1: DVec3, t2: DVec3, f: f64) -> DVec3 {
+    let dogleg = t1.dot(t2).clamp(-1.0, 1.0).acos();
+    if dogleg < 1e-9 {
+        return t1.lerp(t2, f).normalize();
+    }
+    (t1 * ((1.0 - f) * dogleg).sin() + t2 * (f * dogleg).sin()) / dogleg.sin()
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Station {
+    pub depth: f64,
+    pub position: DVec3,
+    pub tangent: DVec3,
+}
+
+#[derive(Clone, Debug)]
+pub struct Drillhole {
+    pub id: String,
+    /// Desurveyed stations, the first at the collar.
+    pub stations: Vec<Station>,
+}
+
+impl Drillhole {
+    /// Desurveys a hole by minimum curvature from `(depth, azimuth, dip)` surveys. Holes
+    /// without surveys are vertical; the first survey also applies at the collar.
+    pub fn desurvey(id: String, collar: DVec3, mut surveys: Vec<(f64, f64, f64)>) -> Self {
+        surveys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let first = surveys.first().copied().unwrap_or((0.0, 0.0, -90.0));
+        if first.0 > 0.0 || surveys.is_empty() {
+            surveys.insert(0, (0.0, first.1, first.2));
+        }
+
+        let mut stations: Vec<Station> = Vec::with_capacity(surveys.len());
+        for (depth, azimuth, dip) in surveys {
+            let tangent = direction(azimuth, dip);
+            let position = match stations.last() {
+                Some(prev) => {
+                    prev.position + min_curvature_step(prev.tangent, tangent, depth - prev.depth)
+                }
+                None => collar,
+            };
+            stations.push(Station {
+                depth,
+                position,
+                tangent,
+            });
+        }
+        Self { id, stations }
+    }
+
+    /// Position at measured `depth`, following the arc between stations and continuing
+    /// straight past the last one.
+    pub fn position(&self, depth: f64) -> DVec3 {
+        let i = self.stations.partition_point(|s| s.depth <= depth);
+        let a = self.stations[i.saturating_sub(1)];
+        match self.stations.get(i) {
+            Some(b) if i > 0 => {
+                let f = (depth - a.depth) / (b.depth - a.depth);
+                let tangent = slerp(a.tangent, b.tangent, f);
+                a.position + min_curvature_step(a.tangent, tangent, depth - a.depth)
+            }
+            _ => a.position + a.tangent * (depth - a.depth),
+        }
+    }
+
+    /// Points along the hole from `from` to `to`, including the stations in between.
+    pub fn path(&self, from: f64, to: f64) -> Vec<DVec3> {
+        let mut points = vec![self.position(from)];
+        points.extend(
+            self.stations
+                .iter()
+                .filter(|s| s.depth > from && s.depth < to)
+                .map(|s| s.position),
+        );
+        points.push(self.position(to));
+        points
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceStyle {
+    Lines,
+    Tubes,
+}
+
+pub struct DrillholeSet {
+    pub name: String,
+    pub holes: Vec<Drillhole>,
+    /// The interval table, one row per interval.
+    pub intervals: DataFrame,
+    /// Hole index and from/to depths of each interval row, `None` for rows whose hole
+    /// has no collar.
+    pub spans: Vec<Option<(usize, f64, f64)>>,
+    /// Interval column the traces are coloured by.
+    pub attribute: String,
+    pub style: TraceStyle,
+    /// Tube radius, also the pick tolerance around lines.
+    pub radius: f32,
+    pub visible: bool,
+    pub entity: Entity,
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+fn read_csv(path: &Path) -> Result<DataFrame, String> {
+    CsvReader::from_path(path)
+        .and_then(|reader| reader.has_header(true).finish())
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))
+}
+
+fn ids(df: &DataFrame, column: &str) -> Result<Vec<Option<String>>, String> {
+    let series = df
+        .column(column)
+        .and_then(|s| s.cast(&DataType::Utf8))
+        .map_err(|e| format!("Hole id column {}: {}", column, e))?;
+    let ids = series.utf8().map_err(|e| e.to_string())?;
+    Ok(ids.into_iter().map(|id| id.map(str::to_string)).collect())
+}
+
+fn numbers(df: &DataFrame, column: &str) -> Result<Vec<Option<f64>>, String> {
+    let series = df
+        .column(column)
+        .and_then(|s| s.cast(&DataType::Float64))
+        .map_err(|e| format!("Column {}: {}", column, e))?;
+    let values = series.f64().map_err(|e| e.to_string())?;
+    Ok(values.into_iter().collect())
+}
+
+impl DrillholeSet {
+    /// Reads and desurveys the collar, survey and interval tables.
+    pub fn load(name: String, files: &DrillholeFiles) -> Result<DrillholeSet, String> {
+        let collars = read_csv(&files.collar)?;
+        let intervals = read_csv(&files.intervals)?;
+
+        let mut surveys: HashMap<String, Vec<(f64, f64, f64)>> = HashMap::default();
+        if let Some(path) = &files.survey {
+            let df = read_csv(path)?;
+            for (id, depth, azimuth, dip) in itertools::izip!(
+                ids(&df, &files.hole_id)?,
+                numbers(&df, &files.depth)?,
+                numbers(&df, &files.azimuth)?,
+                numbers(&df, &files.dip)?
+            ) {
+                if let (Some(id), Some(depth), Some(azimuth), Some(dip)) = (id, depth, azimuth, dip)
+                {
+                    surveys.entry(id).or_default().push((depth, azimuth, dip));
+                }
+            }
+        }
+
+        let mut holes = Vec::new();
+        let mut hole_index = HashMap::default();
+        for (id, x, y, z) in itertools::izip!(
+            ids(&collars, &files.hole_id)?,
+            numbers(&collars, &files.x)?,
+            numbers(&collars, &files.y)?,
+            numbers(&collars, &files.z)?
+        ) {
+            let (Some(id), Some(x), Some(y), Some(z)) = (id, x, y, z) else {
+                continue;
+            };
+            let hole_surveys = surveys.remove(&id).unwrap_or_default();
+            hole_index.insert(id.clone(), holes.len());
+            holes.push(Drillhole::desurvey(id, DVec3::new(x, y, z), hole_surveys));
+        }
+        if holes.is_empty() {
+            return Err("No collars with coordinates".into());
+        }
+
+        let spans = itertools::izip!(
+            ids(&intervals, &files.hole_id)?,
+            numbers(&intervals, &files.from)?,
+            numbers(&intervals, &files.to)?
+        )
+        .map(|(id, from, to)| {
+            let hole = *hole_index.get(&id?)?;
+            let (from, to) = (from?, to?);
+            (to > from).then_some((hole, from, to))
+        })
+        .collect();
+
+        Ok(DrillholeSet {
+            name,
+            holes,
+            intervals,
+            spans,
+            attribute: String::new(),
+            style: TraceStyle::Tubes,
+            radius: 1.0,
+            visible: true,
+            entity: Entity::PLACEHOLDER,
+            mesh: Handle::default(),
+            material: Handle::default(),
+        })
+    }
+
+    /// Numeric interval columns, which can colour the traces.
+    pub fn numeric_columns(&self) -> Vec<String> {
+        self.intervals
+            .get_columns()
+            .iter()
+            .filter(|s| s.dtype().is_numeric())
+            .map(|s| s.name().to_string())
+            .collect()
+    }
+
+    /// Per interval row colour of the displayed attribute.
+    fn colors(&self) -> Vec<Color> {
+        let values = numbers(&self.intervals, &self.attribute)
+            .unwrap_or_else(|_| vec![None; self.spans.len()]);
+        let present = values.iter().flatten().copied().collect::<Vec<_>>();
+        let nulls = values.len() - present.len();
+        let stats = ColumnStats::from_values(present, nulls);
+        let cmap = colorgrad::turbo();
+        values
+            .iter()
+            .map(|v| {
+                v.map_or(MISSING_COLOR, |v| {
+                    value_color(&cmap, (stats.min, stats.max), v, 1.0)
+                })
+            })
+            .collect()
+    }
+
+    /// Trace geometry in model coordinates, coloured per vertex.
+    pub fn to_mesh(&self) -> Mesh {
+        let colors = self.colors();
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+        let mut vertex_colors: Vec<[f32; 4]> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+
+        for (span, color) in self.spans.iter().zip(colors.iter()) {
+            let Some((hole, from, to)) = span else {
+                continue;
+            };
+            let path = self.holes[*hole].path(*from, *to);
+            let color = color.as_rgba_f32();
+
+            for segment in path.windows(2) {
+                let (a, b) = (segment[0].as_vec3(), segment[1].as_vec3());
+                match self.style {
+                    TraceStyle::Lines => {
+                        positions.extend([a.to_array(), b.to_array()]);
+                        vertex_colors.extend([color, color]);
+                    }
+                    TraceStyle::Tubes => {
+                        let Some(axis) = (b - a).try_normalize() else {
+                            continue;
+                        };
+                        let u = axis.any_orthonormal_vector();
+                        let v = axis.cross(u);
+                        let first = positions.len() as u32;
+                        for side in 0..TUBE_SIDES {
+                            let angle = side as f32 / TUBE_SIDES as f32 * std::f32::consts::TAU;
+                            let normal = u * angle.cos() + v * angle.sin();
+                            for end in [a, b] {
+                                positions.push((end + normal * self.radius).to_array());
+                                normals.push(normal.to_array());
+                                vertex_colors.push(color);
+                            }
+                        }
+                        for side in 0..TUBE_SIDES as u32 {
+                            let next = (side + 1) % TUBE_SIDES as u32;
+                            let (a0, b0) = (first + side * 2, first + side * 2 + 1);
+                            let (a1, b1) = (first + next * 2, first + next * 2 + 1);
+                            indices.extend([a0, a1, b1, a0, b1, b0]);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut mesh = match self.style {
+            TraceStyle::Lines => Mesh::new(PrimitiveTopology::LineList),
+            TraceStyle::Tubes => {
+                let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+                mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+                mesh.set_indices(Some(Indices::U32(indices)));
+                mesh
+            }
+        };
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, vertex_colors);
+        mesh
+    }
+
+    pub fn material(&self) -> StandardMaterial {
+        StandardMaterial {
+            base_color: Color::WHITE,
+            unlit: self.style == TraceStyle::Lines,
+            perceptual_roughness: 0.8,
+            ..default()
+        }
+    }
+
+    /// Nearest interval whose trace passes within `radius` of `ray` (in model
+    /// coordinates), with the distance along the ray.
+    pub fn pick(&self, ray: &Ray) -> Option<(f32, usize)> {
+        let origin = ray.origin.as_dvec3();
+        let direction = ray.direction.as_dvec3();
+        let tolerance = self.radius as f64;
+        let mut closest: Option<(f64, usize)> = None;
+
+        for (row, span) in self.spans.iter().enumerate() {
+            let Some((hole, from, to)) = span else {
+                continue;
+            };
+            for segment in self.holes[*hole].path(*from, *to).windows(2) {
+                if let Some((t, distance)) =
+                    ray_segment_distance(origin, direction, segment[0], segment[1])
+                {
+                    if distance <= tolerance && closest.map_or(true, |(best, _)| t < best) {
+                        closest = Some((t, row));
+                    }
+                }
+            }
+        }
+        closest.map(|(t, row)| (t as f32, row))
+    }
+}
+
+/// Parameter along the ray of its closest approach to segment `a`–`b`, and the distance
+/// between them there.
+fn ray_segment_distance(origin: DVec3, direction: DVec3, a: DVec3, b: DVec3) -> Option<(f64, f64)> {
+    let segment = b - a;
+    let w = origin - a;
+    let (dd, ds, ss) = (
+        direction.dot(direction),
+        direction.dot(segment),
+        segment.dot(segment),
+    );
+    let (dw, sw) = (direction.dot(w), segment.dot(w));
+    let denom = dd * ss - ds * ds;
+
+    //segment parameter, clamped to the segment, then the ray parameter for it
+    let s = if denom.abs() < 1e-12 || ss < 1e-12 {
+        0.0
+    } else {
+        ((dd * sw - ds * dw) / denom).clamp(0.0, 1.0)
+    };
+    let t = (direction.dot(a + segment * s - origin) / dd).max(0.0);
+    let distance = (origin + direction * t).distance(a + segment * s);
+    (t > 0.0).then_some((t, distance))
+}
+
+/// Loaded drillhole databases.
+#[derive(Resource, Default)]
+pub struct Drillholes {
+    pub sets: Vec<DrillholeSet>,
+}
+
+/// The last picked interval, as `(set, interval row)`.
+#[derive(Resource, Default)]
+pub struct DrillholeSelection {
+    pub pick: Option<(usize, usize)>,
+}
+
+pub fn pick_intervals(
+    mut click_rays: EventReader<ClickRay>,
+    drillholes: Res<Drillholes>,
+    axes: Res<SceneAxes>,
+    mut selection: ResMut<DrillholeSelection>,
+    mut blocks: ResMut<Selection>,
+) {
+    for click in click_rays.iter() {
+        //the model mapping is linear, so ray parameters match those of the world ray
+        let ray = axes.ray_to_model(&click.ray);
+        let hit = drillholes
+            .sets
+            .iter()
+            .enumerate()
+            .filter(|(_, set)| set.visible)
+            .filter_map(|(i, set)| set.pick(&ray).map(|(t, row)| (t, i, row)))
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+
+        //whichever of the block and the interval is nearer takes the click
+        let hit = match (hit, &click.block) {
+            (Some((t, ..)), Some((block_t, _))) if *block_t <= t => None,
+            (Some((_, i, row)), block) => {
+                match block {
+                    Some((_, pick)) if click.append => blocks.click(pick.clone(), true),
+                    Some(_) => blocks.picks.clear(),
+                    None => {}
+                }
+                Some((i, row))
+            }
+            (None, _) => None,
+        };
+        //like blocks, a shift-click on empty space keeps the current pick
+        if hit.is_some() || !click.append {
+            selection.pick = hit;
+        }
+    }
+}
+
+pub fn highlight_interval(
+    drillholes: Res<Drillholes>,
+    selection: Res<DrillholeSelection>,
+    axes: Res<SceneAxes>,
+    mut gizmos: Gizmos,
+) {
+    let Some((set, row)) = selection.pick else {
+        return;
+    };
+    let Some(set) = drillholes.sets.get(set) else {
+        return;
+    };
+    let Some(Some((hole, from, to))) = set.spans.get(row) else {
+        return;
+    };
+    let path = set.holes[*hole].path(*from, *to);
+    gizmos.linestrip(
+        path.iter().map(|p| axes.to_world(p.as_vec3())),
+        Color::WHITE,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: DVec3, b: DVec3) {
+        assert!(a.distance(b) < 1e-6, "{} is not {}", a, b);
+    }
+
+    #[test]
+    fn hole_without_surveys_is_vertical() {
+        let collar = DVec3::new(10.0, 20.0, 100.0);
+        let hole = Drillhole::desurvey("DH1".into(), collar, Vec::new());
+        assert_near(hole.position(0.0), collar);
+        assert_near(hole.position(50.0), DVec3::new(10.0, 20.0, 50.0));
+        assert_near(hole.position(150.0), DVec3::new(10.0, 20.0, -50.0));
+    }
+
+    #[test]
+    fn constant_dip_and_azimuth_is_straight() {
+        let surveys = vec![
+            (0.0, 90.0, -60.0),
+            (50.0, 90.0, -60.0),
+            (100.0, 90.0, -60.0),
+        ];
+        let hole = Drillhole::desurvey("DH2".into(), DVec3::ZERO, surveys);
+        let along = DVec3::new(60f64.to_radians().cos(), 0.0, -60f64.to_radians().sin());
+        for station in hole.stations.iter() {
+            assert_near(station.position, along * station.depth);
+        }
+        assert_near(hole.position(75.0), along * 75.0);
+    }
+
+    #[test]
+    fn quarter_turn_follows_the_arc() {
+        //from straight down to horizontal east over a quarter circle of radius 10
+        let length = std::f64::consts::FRAC_PI_2 * 10.0;
+        let surveys = vec![(0.0, 90.0, -90.0), (length, 90.0, 0.0)];
+        let hole = Drillhole::desurvey("DH3".into(), DVec3::ZERO, surveys);
+        assert_near(hole.position(length), DVec3::new(10.0, 0.0, -10.0));
+        let half = 10.0 * (1.0 - std::f64::consts::FRAC_1_SQRT_2);
+        assert_near(
+            hole.position(length / 2.0),
+            DVec3::new(half, 0.0, -10.0 * std::f64::consts::FRAC_1_SQRT_2),
+        );
+    }
+
+    #[test]
+    fn vanishing_dogleg_does_not_divide_by_zero() {
+        let t = direction(45.0, -60.0);
+        assert_near(min_curvature_step(t, t, 10.0), t * 10.0);
+        assert_near(slerp(t, t, 0.5), t);
+
+        let surveys = vec![(0.0, 45.0, -60.0), (100.0, 45.0 + 1e-12, -60.0)];
+        let hole = Drillhole::desurvey("DH4".into(), DVec3::ZERO, surveys);
+        for depth in [0.0, 25.0, 100.0] {
+            let position = hole.position(depth);
+            assert!(position.is_finite());
+            assert_near(position, t * depth);
+        }
+    }
+}
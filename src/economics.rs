@@ -0,0 +1,265 @@
+use polars::prelude::{NamedFrom, Series};
+
+use crate::{block_model::BlockModel, optimizer::OptimizeParams};
+
+/// Where a mined block is sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Destination {
+    Process,
+    Waste,
+}
+
+impl Destination {
+    pub const ALL: [Destination; 2] = [Destination::Process, Destination::Waste];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Destination::Process => "process",
+            Destination::Waste => "waste",
+        }
+    }
+}
+
+/// Value of a block at each destination and the best of them.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockValue {
+    /// Value if processed, `None` below the cutoff grade.
+    pub process: Option<f64>,
+    pub waste: f64,
+    pub destination: Destination,
+    pub value: f64,
+}
+
+impl BlockValue {
+    /// Values a block of `tonnes` at `grade` (metal per tonne). Mining is paid at either
+    /// destination; processed blocks also earn the metal price less the processing cost.
+    pub fn new(tonnes: f64, grade: f64, params: &OptimizeParams) -> Self {
+        Self::with_cutoff(tonnes, grade, params.cutoff as f64, params)
+    }
+
+    /// As `new`, with `cutoff` in place of the cutoff grade in `params`.
+    pub fn with_cutoff(tonnes: f64, grade: f64, cutoff: f64, params: &OptimizeParams) -> Self {
+        let waste = -tonnes * params.mining_cost as f64;
+        let process = (grade >= cutoff).then(|| {
+            waste + tonnes * (grade * params.metal_price as f64 - params.processing_cost as f64)
+        });
+        let (destination, value) = match process {
+            Some(process) if process > waste => (Destination::Process, process),
+            _ => (Destination::Waste, waste),
+        };
+        Self {
+            process,
+            waste,
+            destination,
+            value,
+        }
+    }
+}
+
+/// Totals of the blocks sent to one destination.
+#[derive(Clone, Debug, Default)]
+pub struct DestinationTotals {
+    pub blocks: usize,
+    pub tonnes: f64,
+    pub metal: f64,
+    pub revenue: f64,
+    pub mining_cost: f64,
+    pub processing_cost: f64,
+    pub value: f64,
+}
+
+impl DestinationTotals {
+    pub fn grade(&self) -> f64 {
+        if self.tonnes > 0.0 {
+            self.metal / self.tonnes
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Tonnes and grades of a block model with their values, in row order.
+pub struct EconomicModel {
+    pub tonnes: Vec<f64>,
+    pub grades: Vec<f64>,
+    pub values: Vec<BlockValue>,
+}
+
+impl EconomicModel {
+    /// Values every block from the grade and tonnage columns named in `params`; missing
+    /// tonnes or grades count as zero.
+    pub fn new(bm: &BlockModel, params: &OptimizeParams) -> Result<Self, String> {
+        Self::with_cutoffs(bm, params, |_| params.cutoff as f64)
+    }
+
+    /// As `new`, with the cutoff grade of each row given by `cutoff`.
+    pub fn with_cutoffs(
+        bm: &BlockModel,
+        params: &OptimizeParams,
+        cutoff: impl Fn(usize) -> f64,
+    ) -> Result<Self, String> {
+        let tonnes = bm
+            .column_f32(&params.tonnage_col)
+            .ok_or_else(|| format!("Tonnage column {} is not numeric", params.tonnage_col))?;
+        let grades = bm
+            .column_f32(&params.grade_col)
+            .ok_or_else(|| format!("Grade column {} is not numeric", params.grade_col))?;
+        let tonnes = tonnes
+            .into_iter()
+            .map(|t| t.unwrap_or(0.0) as f64)
+            .collect::<Vec<_>>();
+        let grades = grades
+            .into_iter()
+            .map(|g| g.unwrap_or(0.0) as f64)
+            .collect::<Vec<_>>();
+        let values = tonnes
+            .iter()
+            .zip(grades.iter())
+            .enumerate()
+            .map(|(row, (t, g))| BlockValue::with_cutoff(*t, *g, cutoff(row), params))
+            .collect();
+        Ok(Self {
+            tonnes,
+            grades,
+            values,
+        })
+    }
+
+    /// `value` and `destination` columns.
+    pub fn columns(&self, value_name: &str, destination_name: &str) -> (Series, Series) {
+        (
+            Series::new(
+                value_name,
+                self.values.iter().map(|v| v.value).collect::<Vec<_>>(),
+            ),
+            Series::new(
+                destination_name,
+                self.values
+                    .iter()
+                    .map(|v| v.destination.name())
+                    .collect::<Vec<_>>(),
+            ),
+        )
+    }
+
+    /// Totals per destination (in `Destination::ALL` order) over the rows for which
+    /// `include` returns true.
+    pub fn totals(
+        &self,
+        params: &OptimizeParams,
+        include: impl Fn(usize) -> bool,
+    ) -> [DestinationTotals; 2] {
+        let mut totals: [DestinationTotals; 2] = Default::default();
+        for (row, ((t, g), v)) in self
+            .tonnes
+            .iter()
+            .zip(self.grades.iter())
+            .zip(self.values.iter())
+            .enumerate()
+        {
+            if !include(row) {
+                continue;
+            }
+            let total = &mut totals[v.destination as usize];
+            total.blocks += 1;
+            total.tonnes += t;
+            total.mining_cost += t * params.mining_cost as f64;
+            total.metal += t * g;
+            total.value += v.value;
+            if v.destination == Destination::Process {
+                total.revenue += t * g * params.metal_price as f64;
+                total.processing_cost += t * params.processing_cost as f64;
+            }
+        }
+        totals
+    }
+}
+
+/// Grade at which processing a block pays for mining and processing it.
+pub fn breakeven_grade(params: &OptimizeParams) -> Option<f64> {
+    (params.metal_price > 0.0)
+        .then(|| (params.mining_cost + params.processing_cost) as f64 / params.metal_price as f64)
+}
+
+/// Grade above which a block that is mined anyway is worth processing.
+pub fn marginal_grade(params: &OptimizeParams) -> Option<f64> {
+    (params.metal_price > 0.0).then(|| params.processing_cost as f64 / params.metal_price as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use polars::prelude::DataFrame;
+
+    use super::*;
+
+    fn params() -> OptimizeParams {
+        OptimizeParams {
+            tonnage_col: "tonnes".into(),
+            grade_col: "grade".into(),
+            metal_price: 10.0,
+            mining_cost: 2.0,
+            processing_cost: 5.0,
+            cutoff: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn block_at_cutoff_is_processed() {
+        let block = BlockValue::new(10.0, 1.0, &params());
+        assert_eq!(block.destination, Destination::Process);
+        assert_eq!(block.process, Some(30.0));
+        assert_eq!(block.waste, -20.0);
+        assert_eq!(block.value, 30.0);
+    }
+
+    #[test]
+    fn block_below_cutoff_is_waste() {
+        let block = BlockValue::new(10.0, 0.99, &params());
+        assert_eq!(block.destination, Destination::Waste);
+        assert_eq!(block.process, None);
+        assert_eq!(block.value, -20.0);
+    }
+
+    #[test]
+    fn block_above_cutoff_that_does_not_pay_is_waste() {
+        //processing 10 t at grade 0.4 loses 10 on top of mining
+        let params = OptimizeParams {
+            cutoff: 0.0,
+            ..params()
+        };
+        let block = BlockValue::new(10.0, 0.4, &params);
+        assert_eq!(block.destination, Destination::Waste);
+        assert_eq!(block.process, Some(-30.0));
+        assert_eq!(block.value, -20.0);
+    }
+
+    #[test]
+    fn null_grade_counts_as_zero() {
+        let df = DataFrame::new(vec![
+            Series::new("tonnes", &[10.0f32, 10.0]),
+            Series::new("grade", &[Some(2.0f32), None]),
+        ])
+        .unwrap();
+        let bm = BlockModel::new(
+            "bm".into(),
+            df,
+            "x".into(),
+            "y".into(),
+            "z".into(),
+            "dx".into(),
+            "dy".into(),
+            "dz".into(),
+        );
+        let params = params();
+        let economics = EconomicModel::new(&bm, &params).unwrap();
+        assert_eq!(economics.grades, vec![2.0, 0.0]);
+        assert_eq!(economics.values[1].destination, Destination::Waste);
+        assert_eq!(economics.values[1].value, -20.0);
+
+        let [process, waste] = economics.totals(&params, |_| true);
+        assert_eq!((process.blocks, waste.blocks), (1, 1));
+        assert_eq!(process.value, 130.0);
+        assert_eq!(waste.tonnes, 10.0);
+    }
+}
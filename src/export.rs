@@ -0,0 +1,469 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    prelude::*,
+    render::{
+        camera::{RenderTarget, ScalingMode},
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_asset::RenderAssets,
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d,
+            ImageCopyBuffer, ImageDataLayout, MapMode, TextureDescriptor, TextureDimension,
+            TextureFormat, TextureUsages,
+        },
+        renderer::{render_system, RenderDevice, RenderQueue},
+        texture::{GpuImage, TextureFormatPixelInfo},
+        view::{screenshot::ScreenshotManager, RenderLayers},
+        Render, RenderApp, RenderSet,
+    },
+    window::PrimaryWindow,
+};
+use smooth_bevy_cameras::controllers::orbit::OrbitCameraController;
+
+/// Frames to wait after spawning the export cameras before capturing, so the scene has
+/// been rendered into the target.
+const WARMUP_FRAMES: u32 = 3;
+
+/// Frames to wait for a captured image before giving up on it.
+const CAPTURE_TIMEOUT_FRAMES: u32 = 300;
+
+/// Row alignment of texture to buffer copies (wgpu's `COPY_BYTES_PER_ROW_ALIGNMENT`).
+const COPY_ROW_ALIGNMENT: u32 = 256;
+
+/// Where a capture is delivered, once the GPU has copied it back.
+type CaptureSlot = Arc<Mutex<Option<Result<Image, String>>>>;
+
+#[derive(Clone, Debug)]
+pub struct ExportRequest {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub color_bars: bool,
+    /// Capture the main window as shown, egui panels included. The window's own
+    /// resolution is used in this case.
+    pub panels: bool,
+}
+
+/// What an export is rendered into.
+enum Capture {
+    /// The main window, egui panels included.
+    Window(Entity),
+    /// An offscreen image of the requested size.
+    Image(Handle<Image>),
+}
+
+enum ExportStage {
+    Idle,
+    Warmup {
+        request: ExportRequest,
+        capture: Capture,
+        spawned: Vec<Entity>,
+        frames: u32,
+    },
+    Capturing {
+        request: ExportRequest,
+        spawned: Vec<Entity>,
+        slot: CaptureSlot,
+        frames: u32,
+    },
+}
+
+#[derive(Resource)]
+pub struct ImageExport {
+    queue: Vec<ExportRequest>,
+    stage: ExportStage,
+    /// Number of images written since startup.
+    pub completed: usize,
+    /// Number of exports that failed since startup.
+    pub failed: usize,
+    pub last_error: Option<String>,
+}
+
+impl Default for ImageExport {
+    fn default() -> Self {
+        Self {
+            queue: Vec::new(),
+            stage: ExportStage::Idle,
+            completed: 0,
+            failed: 0,
+            last_error: None,
+        }
+    }
+}
+
+impl ImageExport {
+    pub fn request(&mut self, request: ExportRequest) {
+        self.queue.push(request);
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.queue.is_empty() || !matches!(self.stage, ExportStage::Idle)
+    }
+}
+
+/// An offscreen image to copy back to the CPU after the next frame is rendered.
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct ExportReadback {
+    pending: Option<(Handle<Image>, CaptureSlot)>,
+}
+
+/// Copies offscreen export images back from the GPU.
+pub struct ExportReadbackPlugin;
+
+impl Plugin for ExportReadbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExportReadback>()
+            .add_plugins(ExtractResourcePlugin::<ExportReadback>::default());
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.add_systems(
+                Render,
+                read_back_image
+                    .after(render_system)
+                    .in_set(RenderSet::Render),
+            );
+        }
+    }
+}
+
+/// A copy of an export image on its way back from the GPU.
+struct Mapping {
+    buffer: Buffer,
+    mapped: Arc<Mutex<Option<Result<(), String>>>>,
+    size: Extent3d,
+    row_bytes: usize,
+    padded_row_bytes: usize,
+    format: TextureFormat,
+    slot: CaptureSlot,
+}
+
+impl Mapping {
+    /// Copies `image` into a buffer and starts mapping it for reading.
+    fn start(
+        image: &GpuImage,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        slot: CaptureSlot,
+    ) -> Self {
+        let size = Extent3d {
+            width: image.size.x as u32,
+            height: image.size.y as u32,
+            depth_or_array_layers: 1,
+        };
+        let row_bytes = size.width * image.texture_format.pixel_size() as u32;
+        let padded_row_bytes =
+            (row_bytes + COPY_ROW_ALIGNMENT - 1) / COPY_ROW_ALIGNMENT * COPY_ROW_ALIGNMENT;
+
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("image_export_readback"),
+            size: (padded_row_bytes * size.height) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("image_export_readback"),
+        });
+        encoder.copy_texture_to_buffer(
+            image.texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        queue.submit([encoder.finish()]);
+
+        //the callback runs when wgpu next maintains the device, on a later submit
+        let mapped = Arc::new(Mutex::new(None));
+        let done = mapped.clone();
+        device.map_buffer(&buffer.slice(..), MapMode::Read, move |result| {
+            if let Ok(mut done) = done.lock() {
+                *done = Some(result.map_err(|e| e.to_string()));
+            }
+        });
+
+        Self {
+            buffer,
+            mapped,
+            size,
+            row_bytes: row_bytes as usize,
+            padded_row_bytes: padded_row_bytes as usize,
+            format: image.texture_format,
+            slot,
+        }
+    }
+
+    /// Hands the image to its slot once mapped, returning whether it is finished with.
+    fn finish(&self) -> bool {
+        let Some(mapped) = self.mapped.lock().ok().and_then(|mut m| m.take()) else {
+            return false;
+        };
+        let result = mapped
+            .map_err(|e| format!("Cannot read the export image back: {}", e))
+            .map(|()| {
+                //rows are padded to the copy alignment
+                let data = self
+                    .buffer
+                    .slice(..)
+                    .get_mapped_range()
+                    .chunks(self.padded_row_bytes)
+                    .flat_map(|row| &row[..self.row_bytes])
+                    .copied()
+                    .collect::<Vec<_>>();
+                self.buffer.unmap();
+                Image::new(self.size, TextureDimension::D2, data, self.format)
+            });
+        if let Ok(mut slot) = self.slot.lock() {
+            *slot = Some(result);
+        }
+        true
+    }
+}
+
+/// Starts copying the pending export image back once the frame has been rendered, and
+/// delivers the copies that have arrived.
+fn read_back_image(
+    mut readback: ResMut<ExportReadback>,
+    images: Res<RenderAssets<Image>>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    mut mappings: Local<Vec<Mapping>>,
+) {
+    mappings.retain(|mapping| !mapping.finish());
+
+    //taken so it is copied once; the main world clears its own copy when it arrives
+    let Some((handle, slot)) = readback.pending.take() else {
+        return;
+    };
+    match images.get(&handle) {
+        Some(image) => mappings.push(Mapping::start(image, &device, &queue, slot)),
+        None => {
+            if let Ok(mut slot) = slot.lock() {
+                *slot = Some(Err("The export image was not prepared".into()));
+            }
+        }
+    }
+}
+
+/// An image the export cameras can render into and the GPU can copy from.
+fn export_image(width: u32, height: u32) -> Image {
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("image_export"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    image
+}
+
+/// Renders export requests into an offscreen image of the requested size, with its own
+/// copies of the main 3D camera and (optionally) the colour bar camera, then reads the
+/// frame back and writes it to PNG. Requests for the panels capture the main window.
+pub fn run_image_export(
+    mut commands: Commands,
+    mut export: ResMut<ImageExport>,
+    mut readback: ResMut<ExportReadback>,
+    mut images: ResMut<Assets<Image>>,
+    mut screenshots: ResMut<ScreenshotManager>,
+    primary_window: Query<(Entity, &Window), With<PrimaryWindow>>,
+    main_camera: Query<(&Transform, &Projection), With<OrbitCameraController>>,
+    mut overlay_cameras: Query<&mut Camera, (With<Camera2d>, Without<OrbitCameraController>)>,
+) {
+    let export = &mut *export;
+    match &mut export.stage {
+        ExportStage::Idle => {
+            let Some(request) = export.queue.first() else {
+                return;
+            };
+            let window = primary_window.get_single().ok();
+
+            if request.panels {
+                let request = export.queue.remove(0);
+                let Some((primary, _)) = window else {
+                    export.failed += 1;
+                    export.last_error = Some("There is no window to capture the panels of".into());
+                    return;
+                };
+                for mut camera in overlay_cameras.iter_mut() {
+                    camera.is_active = request.color_bars;
+                }
+                export.stage = ExportStage::Warmup {
+                    request,
+                    capture: Capture::Window(primary),
+                    spawned: Vec::new(),
+                    frames: WARMUP_FRAMES - 1,
+                };
+                return;
+            }
+
+            //left queued until there is a view to export
+            let Ok((transform, projection)) = main_camera.get_single() else {
+                return;
+            };
+            let request = export.queue.remove(0);
+            let target = images.add(export_image(request.width, request.height));
+            let mut spawned = vec![commands
+                .spawn((
+                    Camera3dBundle {
+                        camera: Camera {
+                            target: RenderTarget::Image(target.clone()),
+                            ..default()
+                        },
+                        transform: *transform,
+                        projection: projection.clone(),
+                        ..default()
+                    },
+                    RenderLayers::layer(0),
+                ))
+                .id()];
+
+            if request.color_bars {
+                //keep the colour bar layout of the main window, scaled up to the image; batch
+                //runs have no window and lay the bars out for the image itself
+                let (min_width, min_height) = window.map_or(
+                    (request.width as f32, request.height as f32),
+                    |(_, window)| (window.width(), window.height()),
+                );
+                spawned.push(
+                    commands
+                        .spawn((
+                            Camera2dBundle {
+                                camera_2d: Camera2d {
+                                    clear_color: ClearColorConfig::None,
+                                },
+                                camera: Camera {
+                                    order: 2,
+                                    target: RenderTarget::Image(target.clone()),
+                                    ..default()
+                                },
+                                projection: OrthographicProjection {
+                                    scaling_mode: ScalingMode::AutoMin {
+                                        min_width,
+                                        min_height,
+                                    },
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            RenderLayers::layer(1),
+                        ))
+                        .id(),
+                );
+            }
+
+            export.stage = ExportStage::Warmup {
+                request,
+                capture: Capture::Image(target),
+                spawned,
+                frames: 0,
+            };
+        }
+        ExportStage::Warmup {
+            request,
+            capture,
+            spawned,
+            frames,
+        } => {
+            *frames += 1;
+            if *frames < WARMUP_FRAMES {
+                return;
+            }
+
+            let slot = CaptureSlot::default();
+            match capture {
+                Capture::Window(window) => {
+                    let delivered = slot.clone();
+                    let result = screenshots.take_screenshot(*window, move |image| {
+                        if let Ok(mut slot) = delivered.lock() {
+                            *slot = Some(Ok(image));
+                        }
+                    });
+                    if let Err(e) = result {
+                        if let Ok(mut slot) = slot.lock() {
+                            *slot = Some(Err(e.to_string()));
+                        }
+                    }
+                }
+                Capture::Image(image) => {
+                    readback.pending = Some((image.clone(), slot.clone()));
+                }
+            }
+            export.stage = ExportStage::Capturing {
+                request: request.clone(),
+                spawned: std::mem::take(spawned),
+                slot,
+                frames: 0,
+            };
+        }
+        ExportStage::Capturing {
+            request,
+            spawned,
+            slot,
+            frames,
+        } => {
+            *frames += 1;
+            let captured = slot.lock().ok().and_then(|mut slot| slot.take());
+            let result = match captured {
+                Some(Ok(image)) => image
+                    .try_into_dynamic()
+                    .map_err(|e| format!("Cannot convert exported image: {}", e))
+                    .and_then(|image| {
+                        image.to_rgba8().save(&request.path).map_err(|e| {
+                            format!("Cannot save image to {}: {}", request.path.display(), e)
+                        })
+                    }),
+                Some(Err(e)) => Err(e),
+                None if *frames > CAPTURE_TIMEOUT_FRAMES => Err(format!(
+                    "No image was captured for {} within {} frames",
+                    request.path.display(),
+                    CAPTURE_TIMEOUT_FRAMES
+                )),
+                None => return,
+            };
+
+            match result {
+                Ok(()) => {
+                    info!("Image saved to {}", request.path.display());
+                    export.completed += 1;
+                    export.last_error = None;
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    export.failed += 1;
+                    export.last_error = Some(e);
+                }
+            }
+            readback.pending = None;
+            for entity in spawned.drain(..) {
+                commands.entity(entity).despawn_recursive();
+            }
+            for mut camera in overlay_cameras.iter_mut() {
+                camera.is_active = true;
+            }
+            export.stage = ExportStage::Idle;
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::Path;
+
+use itertools::izip;
+
+use crate::block_model::BlockModel;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TonnageSource {
+    /// Tonnes read directly from a column.
+    Column(String),
+    /// Block volume multiplied by a density column.
+    DensityColumn(String),
+    /// Block volume multiplied by a constant density.
+    Density(f64),
+}
+
+impl Default for TonnageSource {
+    fn default() -> Self {
+        TonnageSource::Density(2.7)
+    }
+}
+
+impl BlockModel {
+    /// Tonnes of each block (`None` where inputs are missing), in row order.
+    pub fn tonnages(&self, source: &TonnageSource) -> Option<Vec<Option<f64>>> {
+        let volumes = || -> Option<Vec<Option<f64>>> {
+            let x_size = self.column_f32(&self.x_size)?;
+            let y_size = self.column_f32(&self.y_size)?;
+            let z_size = self.column_f32(&self.z_size)?;
+            Some(
+                izip!(&x_size, &y_size, &z_size)
+                    .map(|(x, y, z)| Some(x? as f64 * y? as f64 * z? as f64))
+                    .collect(),
+            )
+        };
+
+        match source {
+            TonnageSource::Column(column) => Some(
+                self.column_f32(column)?
+                    .into_iter()
+                    .map(|t| t.map(|t| t as f64))
+                    .collect(),
+            ),
+            TonnageSource::DensityColumn(column) => {
+                let density = self.column_f32(column)?;
+                Some(
+                    izip!(volumes()?, &density)
+                        .map(|(v, d)| Some(v? * d? as f64))
+                        .collect(),
+                )
+            }
+            TonnageSource::Density(density) => Some(
+                volumes()?
+                    .into_iter()
+                    .map(|v| v.map(|v| v * density))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GradeTonnagePoint {
+    pub cutoff: f64,
+    pub tonnes: f64,
+    pub grade: f64,
+    pub metal: f64,
+}
+
+/// Tonnes and average grade above each cutoff.
+///
+/// Only `rows` are considered when given, e.g. the current pick selection.
+pub fn grade_tonnage_curve(
+    bm: &BlockModel,
+    tonnage: &TonnageSource,
+    grade: &str,
+    rows: Option<&[usize]>,
+    cutoffs: &[f64],
+) -> Option<Vec<GradeTonnagePoint>> {
+    let tonnes = bm.tonnages(tonnage)?;
+    let grades = bm.column_f32(grade)?;
+
+    let pair = |row: usize| -> Option<(f64, f64)> {
+        Some((grades.get(row)? as f64, (*tonnes.get(row)?)?))
+    };
+    let mut blocks = match rows {
+        Some(rows) => rows.iter().filter_map(|row| pair(*row)).collect::<Vec<_>>(),
+        None => (0..tonnes.len()).filter_map(pair).collect::<Vec<_>>(),
+    };
+
+    //sort by descending grade so every cutoff is a prefix
+    blocks.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut cumulative = Vec::with_capacity(blocks.len() + 1);
+    cumulative.push((0.0, 0.0));
+    for (g, t) in blocks.iter() {
+        let (ct, cm) = cumulative.last().copied().unwrap();
+        cumulative.push((ct + t, cm + g * t));
+    }
+
+    Some(
+        cutoffs
+            .iter()
+            .map(|cutoff| {
+                let n = blocks.partition_point(|(g, _)| g >= cutoff);
+                let (tonnes, metal) = cumulative[n];
+                GradeTonnagePoint {
+                    cutoff: *cutoff,
+                    tonnes,
+                    grade: if tonnes > 0.0 { metal / tonnes } else { 0.0 },
+                    metal,
+                }
+            })
+            .collect(),
+    )
+}
+
+/// `steps + 1` evenly spaced cutoffs from `min` to `max`.
+pub fn cutoff_range(min: f64, max: f64, steps: usize) -> Vec<f64> {
+    let steps = steps.max(1);
+    (0..=steps)
+        .map(|i| min + (max - min) * i as f64 / steps as f64)
+        .collect()
+}
+
+pub fn export_csv(
+    path: &Path,
+    curves: &[(String, Vec<GradeTonnagePoint>)],
+) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["curve", "cutoff", "tonnes", "grade", "metal"])?;
+    for (name, points) in curves.iter() {
+        for p in points.iter() {
+            writer.write_record([
+                name.clone(),
+                p.cutoff.to_string(),
+                p.tonnes.to_string(),
+                p.grade.to_string(),
+                p.metal.to_string(),
+            ])?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    utils::HashMap,
+};
+use ndarray::Array3;
+
+use crate::spatial_index::BlockGrid;
+
+/// Triangle mesh in model coordinates.
+#[derive(Clone, Debug, Default)]
+pub struct IsoMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+/// Extracts the `threshold` isosurface of a block model column with naive surface nets
+/// (dual contouring without the QEF step) over the regular block grid.
+///
+/// Samples sit at block centres; missing blocks count as below the threshold so shells
+/// close against the edge of the model. `value` maps a DataFrame row to its value.
+pub fn extract(grid: &BlockGrid, value: impl Fn(usize) -> Option<f32>, threshold: f32) -> IsoMesh {
+    //pad by one sample on each side so shells touching the model edge are closed
+    let dims = (grid.dims.i + 2, grid.dims.j + 2, grid.dims.k + 2);
+    let mut field = Array3::from_elem(dims, f32::NAN);
+    for (ind, row) in grid.iter() {
+        if let Some(v) = value(*row) {
+            field[[ind.i + 1, ind.j + 1, ind.k + 1]] = v;
+        }
+    }
+
+    let inside = |v: f32| !v.is_nan() && v >= threshold;
+    let sample_position = |i: usize, j: usize, k: usize| {
+        grid.origin + grid.cell * (Vec3::new(i as f32, j as f32, k as f32) - Vec3::splat(0.5))
+    };
+    //crossing point along the edge between samples a and b, as a fraction from a
+    let crossing = |a: f32, b: f32| {
+        if a.is_nan() || b.is_nan() {
+            0.5
+        } else {
+            ((threshold - a) / (b - a)).clamp(0.0, 1.0)
+        }
+    };
+
+    const CORNERS: [(usize, usize, usize); 8] = [
+        (0, 0, 0),
+        (1, 0, 0),
+        (0, 1, 0),
+        (1, 1, 0),
+        (0, 0, 1),
+        (1, 0, 1),
+        (0, 1, 1),
+        (1, 1, 1),
+    ];
+    const EDGES: [(usize, usize); 12] = [
+        (0, 1),
+        (2, 3),
+        (4, 5),
+        (6, 7),
+        (0, 2),
+        (1, 3),
+        (4, 6),
+        (5, 7),
+        (0, 4),
+        (1, 5),
+        (2, 6),
+        (3, 7),
+    ];
+
+    let mut mesh = IsoMesh::default();
+    let mut vertex_of_cube: HashMap<(usize, usize, usize), u32> = HashMap::default();
+
+    //one vertex per cube with a sign change, at the mean of its edge crossings
+    for i in 0..dims.0 - 1 {
+        for j in 0..dims.1 - 1 {
+            for k in 0..dims.2 - 1 {
+                let values = CORNERS.map(|(di, dj, dk)| field[[i + di, j + dj, k + dk]]);
+                let mask = values.iter().filter(|v| inside(**v)).count();
+                if mask == 0 || mask == 8 {
+                    continue;
+                }
+
+                let mut sum = Vec3::ZERO;
+                let mut n = 0;
+                for (a, b) in EDGES {
+                    if inside(values[a]) == inside(values[b]) {
+                        continue;
+                    }
+                    let (ai, aj, ak) = CORNERS[a];
+                    let (bi, bj, bk) = CORNERS[b];
+                    let pa = sample_position(i + ai, j + aj, k + ak);
+                    let pb = sample_position(i + bi, j + bj, k + bk);
+                    sum += pa.lerp(pb, crossing(values[a], values[b]));
+                    n += 1;
+                }
+
+                vertex_of_cube.insert((i, j, k), mesh.positions.len() as u32);
+                mesh.positions.push((sum / n as f32).into());
+            }
+        }
+    }
+
+    //one quad per sample edge with a sign change, joining the four cubes around it
+    for i in 0..dims.0 {
+        for j in 0..dims.1 {
+            for k in 0..dims.2 {
+                let here = inside(field[[i, j, k]]);
+                for axis in 0..3 {
+                    let (ni, nj, nk) = match axis {
+                        0 => (i + 1, j, k),
+                        1 => (i, j + 1, k),
+                        _ => (i, j, k + 1),
+                    };
+                    if ni >= dims.0 || nj >= dims.1 || nk >= dims.2 {
+                        continue;
+                    }
+                    let there = inside(field[[ni, nj, nk]]);
+                    if here == there {
+                        continue;
+                    }
+
+                    let cubes = match (axis, i.checked_sub(1), j.checked_sub(1), k.checked_sub(1)) {
+                        (0, _, Some(pj), Some(pk)) => {
+                            [(i, pj, pk), (i, j, pk), (i, j, k), (i, pj, k)]
+                        }
+                        (1, Some(pi), _, Some(pk)) => {
+                            [(pi, j, pk), (pi, j, k), (i, j, k), (i, j, pk)]
+                        }
+                        (2, Some(pi), Some(pj), _) => {
+                            [(pi, pj, k), (i, pj, k), (i, j, k), (pi, j, k)]
+                        }
+                        _ => continue,
+                    };
+                    let Some(quad) = cubes
+                        .iter()
+                        .map(|c| vertex_of_cube.get(c).copied())
+                        .collect::<Option<Vec<_>>>()
+                    else {
+                        continue;
+                    };
+
+                    //wind so normals point from inside to outside
+                    if here {
+                        mesh.indices
+                            .extend([quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+                    } else {
+                        mesh.indices
+                            .extend([quad[0], quad[2], quad[1], quad[0], quad[3], quad[2]]);
+                    }
+                }
+            }
+        }
+    }
+
+    mesh.compute_normals();
+    mesh
+}
+
+impl IsoMesh {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Area-weighted smooth vertex normals.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vec3::ZERO; self.positions.len()];
+        for tri in self.indices.chunks_exact(3) {
+            let [a, b, c] =
+                [tri[0], tri[1], tri[2]].map(|i| Vec3::from(self.positions[i as usize]));
+            let n = (b - a).cross(c - a);
+            for i in tri {
+                normals[*i as usize] += n;
+            }
+        }
+        self.normals = normals
+            .into_iter()
+            .map(|n| n.normalize_or_zero().into())
+            .collect();
+    }
+
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions.clone());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals.clone());
+        mesh.set_indices(Some(Indices::U32(self.indices.clone())));
+        mesh
+    }
+
+    pub fn write_obj(&self, path: &Path) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        for p in self.positions.iter() {
+            writeln!(w, "v {} {} {}", p[0], p[1], p[2])?;
+        }
+        for n in self.normals.iter() {
+            writeln!(w, "vn {} {} {}", n[0], n[1], n[2])?;
+        }
+        for tri in self.indices.chunks_exact(3) {
+            let (a, b, c) = (tri[0] + 1, tri[1] + 1, tri[2] + 1);
+            writeln!(w, "f {a}//{a} {b}//{b} {c}//{c}")?;
+        }
+        w.flush()
+    }
+
+    /// Binary STL.
+    pub fn write_stl(&self, path: &Path) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        w.write_all(&[0u8; 80])?;
+        w.write_all(&(self.triangle_count() as u32).to_le_bytes())?;
+        for tri in self.indices.chunks_exact(3) {
+            let [a, b, c] =
+                [tri[0], tri[1], tri[2]].map(|i| Vec3::from(self.positions[i as usize]));
+            let n = (b - a).cross(c - a).normalize_or_zero();
+            for v in [n, a, b, c] {
+                for x in v.to_array() {
+                    w.write_all(&x.to_le_bytes())?;
+                }
+            }
+            w.write_all(&0u16.to_le_bytes())?;
+        }
+        w.flush()
+    }
+}
+
+pub struct IsoShell {
+    pub name: String,
+    pub mesh: IsoMesh,
+    /// Unmultiplied RGBA.
+    pub color: [f32; 4],
+    pub visible: bool,
+    pub entity: Entity,
+    pub material: Handle<StandardMaterial>,
+}
+
+/// Extracted grade shells, each drawn as its own mesh layer.
+#[derive(Resource, Default)]
+pub struct IsoShells {
+    pub shells: Vec<IsoShell>,
+}
+
+pub fn shell_material(color: [f32; 4]) -> StandardMaterial {
+    StandardMaterial {
+        base_color: Color::rgba(color[0], color[1], color[2], color[3]),
+        alpha_mode: if color[3] < 1.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        },
+        double_sided: true,
+        cull_mode: None,
+        perceptual_roughness: 0.8,
+        ..default()
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use polars::prelude::Series;
+
+use crate::{
+    block_model::{BlockLayer, BlockModelDB},
+    layers::LayerEvent,
+    pit::PitSummary,
+    shells::PitShell,
+    stats::StatsCache,
+    ui::CheckedColumns,
+};
+
+/// Name of the ultimate pit job; only one runs at a time.
+pub const PIT_JOB: &str = "Ultimate pit";
+/// Name of the nested pit shells job.
+pub const SHELLS_JOB: &str = "Pit shells";
+/// Name of the inside solid flagging job.
+pub const SOLID_JOB: &str = "Inside solid";
+/// Name of the pit scheduling job.
+pub const SCHEDULE_JOB: &str = "Schedule";
+
+/// Latest progress reported by a running job.
+#[derive(Clone, Debug, Default)]
+pub struct Progress {
+    pub stage: String,
+    pub iteration: usize,
+    /// Current objective, e.g. the bound on the pit value or an NPV.
+    pub objective: Option<f64>,
+    /// Which of several runs making up the job is in progress, and how many there are.
+    pub part: Option<(usize, usize)>,
+}
+
+/// Shared between a job's thread and the UI: progress and partial results one way,
+/// cancellation the other.
+#[derive(Clone, Default)]
+pub struct JobControl {
+    cancel: Arc<AtomicBool>,
+    progress: Arc<Mutex<Progress>>,
+    partial: Arc<Mutex<Option<JobOutput>>>,
+}
+
+impl JobControl {
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// `Err` once the job has been cancelled, for `?` in job loops.
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err("Cancelled".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn report(&self, stage: &str, iteration: usize, objective: Option<f64>) {
+        if let Ok(mut progress) = self.progress.lock() {
+            progress.stage.clear();
+            progress.stage.push_str(stage);
+            progress.iteration = iteration;
+            progress.objective = objective;
+        }
+    }
+
+    pub fn set_part(&self, part: usize, parts: usize) {
+        if let Ok(mut progress) = self.progress.lock() {
+            progress.part = Some((part, parts));
+        }
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.progress.lock().map(|p| p.clone()).unwrap_or_default()
+    }
+
+    /// Hands over a result so far, replacing any not yet applied.
+    pub fn publish(&self, output: JobOutput) {
+        if let Ok(mut partial) = self.partial.lock() {
+            *partial = Some(output);
+        }
+    }
+
+    fn take_partial(&self) -> Option<JobOutput> {
+        self.partial.lock().ok().and_then(|mut p| p.take())
+    }
+}
+
+/// What a finished job hands back to the main thread.
+pub enum JobOutput {
+    Pit {
+        grid: String,
+        flags: Series,
+        summary: PitSummary,
+    },
+    Shells {
+        grid: String,
+        shells: Series,
+        pits: Vec<PitShell>,
+    },
+    Solid {
+        grid: String,
+        solid: String,
+        flags: Series,
+        percents: Series,
+    },
+    Schedule {
+        grid: String,
+        periods: Series,
+        npv: f64,
+    },
+}
+
+pub struct Job {
+    pub name: String,
+    pub started: Instant,
+    pub control: JobControl,
+    thread: JoinHandle<Result<JobOutput, String>>,
+}
+
+/// A job that has finished, was cancelled or failed.
+pub struct FinishedJob {
+    pub name: String,
+    pub elapsed: Duration,
+    pub outcome: Result<String, String>,
+}
+
+/// Long running work (pit optimisation, scheduling) run on their own threads so the
+/// main loop keeps rendering.
+#[derive(Resource, Default)]
+pub struct Jobs {
+    pub running: Vec<Job>,
+    pub finished: Vec<FinishedJob>,
+    /// Result of the last pit optimisation, shown in the optimisation dialog.
+    pub last_pit: Option<Result<PitSummary, String>>,
+    /// Pit by pit results of the last shells run, shown in the pit shells panel.
+    pub last_shells: Option<Result<Vec<PitShell>, String>>,
+}
+
+impl Jobs {
+    pub fn spawn(
+        &mut self,
+        name: impl Into<String>,
+        work: impl FnOnce(&JobControl) -> Result<JobOutput, String> + Send + 'static,
+    ) {
+        let control = JobControl::default();
+        let thread_control = control.clone();
+        self.running.push(Job {
+            name: name.into(),
+            started: Instant::now(),
+            control,
+            thread: std::thread::spawn(move || work(&thread_control)),
+        });
+    }
+
+    pub fn is_running(&self, name: &str) -> bool {
+        self.running.iter().any(|job| job.name == name)
+    }
+}
+
+/// Writes a schedule's period column and draws it. Returns the column name.
+fn write_periods(
+    block_models: &mut BlockModelDB,
+    stats_cache: &mut StatsCache,
+    checked: &mut CheckedColumns,
+    layer_events: &mut EventWriter<LayerEvent>,
+    grid: &str,
+    periods: Series,
+) -> Result<String, String> {
+    let bm = block_models
+        .block_models
+        .get_mut(grid)
+        .ok_or_else(|| format!("Block model {} was removed", grid))?;
+    let column = periods.name().to_string();
+    bm.set_column(periods).map_err(|e| e.to_string())?;
+    stats_cache.invalidate(grid);
+    checked.check(bm, &column);
+    layer_events.send(LayerEvent::Spawn(BlockLayer {
+        grid: grid.to_string(),
+        column: column.clone(),
+    }));
+    Ok(column)
+}
+
+/// Applies the results so far of running jobs, so a schedule can be watched converging.
+pub fn refresh_jobs(
+    jobs: Res<Jobs>,
+    mut block_models: ResMut<BlockModelDB>,
+    mut stats_cache: ResMut<StatsCache>,
+    mut checked: ResMut<CheckedColumns>,
+    mut layer_events: EventWriter<LayerEvent>,
+) {
+    for job in jobs.running.iter() {
+        if let Some(JobOutput::Schedule { grid, periods, .. }) = job.control.take_partial() {
+            //a model removed meanwhile is reported when the job finishes
+            let _ = write_periods(
+                &mut block_models,
+                &mut stats_cache,
+                &mut checked,
+                &mut layer_events,
+                &grid,
+                periods,
+            );
+        }
+    }
+}
+
+/// Collects finished jobs and applies their output.
+pub fn finish_jobs(
+    mut jobs: ResMut<Jobs>,
+    mut block_models: ResMut<BlockModelDB>,
+    mut stats_cache: ResMut<StatsCache>,
+    mut checked: ResMut<CheckedColumns>,
+    mut layer_events: EventWriter<LayerEvent>,
+) {
+    if !jobs.running.iter().any(|job| job.thread.is_finished()) {
+        return;
+    }
+    let (finished, running): (Vec<_>, Vec<_>) = std::mem::take(&mut jobs.running)
+        .into_iter()
+        .partition(|job| job.thread.is_finished());
+    jobs.running = running;
+
+    for job in finished {
+        let elapsed = job.started.elapsed();
+        let output = job
+            .thread
+            .join()
+            .unwrap_or_else(|_| Err("The job panicked".into()));
+        let outcome = output.and_then(|output| match output {
+            JobOutput::Pit {
+                grid,
+                flags,
+                summary,
+            } => {
+                let bm = block_models
+                    .block_models
+                    .get_mut(&grid)
+                    .ok_or_else(|| format!("Block model {} was removed", grid))?;
+                let column = flags.name().to_string();
+                bm.set_column(flags).map_err(|e| e.to_string())?;
+                stats_cache.invalidate(&grid);
+                let message = format!(
+                    "{} blocks in pit, value {:.0}, written to {}",
+                    summary.blocks, summary.value, column
+                );
+                jobs.last_pit = Some(Ok(summary));
+                Ok(message)
+            }
+            JobOutput::Shells { grid, shells, pits } => {
+                let bm = block_models
+                    .block_models
+                    .get_mut(&grid)
+                    .ok_or_else(|| format!("Block model {} was removed", grid))?;
+                let column = shells.name().to_string();
+                bm.set_column(shells).map_err(|e| e.to_string())?;
+                stats_cache.invalidate(&grid);
+                let message = format!("{} nested pits written to {}", pits.len(), column);
+                jobs.last_shells = Some(Ok(pits));
+                Ok(message)
+            }
+            JobOutput::Solid {
+                grid,
+                solid,
+                flags,
+                percents,
+            } => {
+                let bm = block_models
+                    .block_models
+                    .get_mut(&grid)
+                    .ok_or_else(|| format!("Block model {} was removed", grid))?;
+                let inside = flags.bool().ok().and_then(|f| f.sum()).unwrap_or(0);
+                bm.set_column(flags).map_err(|e| e.to_string())?;
+                bm.set_column(percents).map_err(|e| e.to_string())?;
+                stats_cache.invalidate(&grid);
+                Ok(format!(
+                    "{} of {} blocks inside {}",
+                    inside,
+                    bm.df.height(),
+                    solid
+                ))
+            }
+            JobOutput::Schedule { grid, periods, npv } => {
+                let years = periods.max::<u32>().unwrap_or(0);
+                let column = write_periods(
+                    &mut block_models,
+                    &mut stats_cache,
+                    &mut checked,
+                    &mut layer_events,
+                    &grid,
+                    periods,
+                )?;
+                Ok(format!(
+                    "{} periods, NPV {:.0}, written to {}",
+                    years, npv, column
+                ))
+            }
+        });
+        if let Err(e) = &outcome {
+            match job.name.as_str() {
+                PIT_JOB => jobs.last_pit = Some(Err(e.clone())),
+                SHELLS_JOB => jobs.last_shells = Some(Err(e.clone())),
+                _ => {}
+            }
+        }
+        jobs.finished.push(FinishedJob {
+            name: job.name,
+            elapsed,
+            outcome,
+        });
+    }
+}
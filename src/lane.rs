@@ -0,0 +1,345 @@
+use crate::optimizer::OptimizeParams;
+
+/// Years after which a schedule is cut off, in case capacities are tiny for the reserve.
+const MAX_YEARS: usize = 200;
+/// NPV change (relative) below which the iteration is considered converged.
+const TOLERANCE: f64 = 1e-6;
+
+/// Which capacity, or balance of two capacities, sets the cutoff of a year.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Mine,
+    Mill,
+    Market,
+    MineMill,
+    MineMarket,
+    MillMarket,
+}
+
+impl Limit {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Limit::Mine => "mine",
+            Limit::Mill => "mill",
+            Limit::Market => "market",
+            Limit::MineMill => "mine/mill balance",
+            Limit::MineMarket => "mine/market balance",
+            Limit::MillMarket => "mill/market balance",
+        }
+    }
+}
+
+/// One year of a cutoff grade schedule.
+#[derive(Clone, Debug)]
+pub struct LaneYear {
+    pub cutoff: f64,
+    pub limit: Limit,
+    pub mined: f64,
+    pub processed: f64,
+    /// Mean grade of the processed tonnes.
+    pub grade: f64,
+    pub metal: f64,
+    pub cash_flow: f64,
+    /// NPV of the remaining schedule at the start of the year.
+    pub npv: f64,
+}
+
+/// Grade–tonnage distribution of a reserve, sorted by descending grade.
+struct Distribution {
+    grades: Vec<f64>,
+    /// Tonnes and metal of all blocks at or above each grade.
+    tonnes_above: Vec<f64>,
+    metal_above: Vec<f64>,
+}
+
+impl Distribution {
+    fn new(tonnes: &[f64], grades: &[f64]) -> Self {
+        let mut blocks = tonnes
+            .iter()
+            .zip(grades.iter())
+            .filter(|(t, _)| **t > 0.0)
+            .map(|(t, g)| (*t, *g))
+            .collect::<Vec<_>>();
+        blocks.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let (mut tonnes, mut metal) = (0.0, 0.0);
+        let mut distribution = Self {
+            grades: Vec::with_capacity(blocks.len()),
+            tonnes_above: Vec::with_capacity(blocks.len()),
+            metal_above: Vec::with_capacity(blocks.len()),
+        };
+        for (t, g) in blocks {
+            tonnes += t;
+            metal += t * g;
+            distribution.grades.push(g);
+            distribution.tonnes_above.push(tonnes);
+            distribution.metal_above.push(metal);
+        }
+        distribution
+    }
+
+    fn total(&self) -> f64 {
+        self.tonnes_above.last().copied().unwrap_or(0.0)
+    }
+
+    /// Tonnes and metal at or above `cutoff`.
+    fn above(&self, cutoff: f64) -> (f64, f64) {
+        let n = self.grades.partition_point(|g| *g >= cutoff);
+        if n == 0 {
+            (0.0, 0.0)
+        } else {
+            (self.tonnes_above[n - 1], self.metal_above[n - 1])
+        }
+    }
+
+    /// Cutoff at which the ore makes up `fraction` of the tonnes.
+    fn cutoff_for_fraction(&self, fraction: f64) -> f64 {
+        let target = fraction * self.total();
+        let n = self.tonnes_above.partition_point(|t| *t < target);
+        self.grades
+            .get(n)
+            .or(self.grades.last())
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Cutoff at which the metal of the ore is `fraction` of the tonnes.
+    fn cutoff_for_metal(&self, fraction: f64) -> f64 {
+        let target = fraction * self.total();
+        let n = self.metal_above.partition_point(|m| *m < target);
+        self.grades
+            .get(n)
+            .or(self.grades.last())
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Cutoff at which the mean grade of the ore falls to `grade`.
+    fn cutoff_for_grade(&self, grade: f64) -> f64 {
+        let n = self
+            .tonnes_above
+            .iter()
+            .zip(self.metal_above.iter())
+            .position(|(t, m)| *m <= grade * *t)
+            .unwrap_or(self.grades.len());
+        self.grades
+            .get(n)
+            .or(self.grades.last())
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+/// Lane's cutoff grades for a reserve of `tonnes` at `grades` under the mining,
+/// processing and market capacities of `params`, without fixed costs.
+///
+/// Each year the cutoff is whichever of the limiting cutoffs of each capacity and the
+/// balancing cutoffs of each pair of capacities is worth most per tonne mined, once the
+/// time the binding capacity takes is charged the opportunity cost `d·V` of the remaining
+/// NPV `V`. The market is left out when `market_cap` is 0. The reserve is depleted proportionally across grades. The schedule is
+/// recomputed with the NPVs of the previous pass until they converge or `num_iters`
+/// passes are made. `min_life` (years) caps the mining rate so the reserve lasts at least
+/// that long.
+pub fn lane_schedule(
+    tonnes: &[f64],
+    grades: &[f64],
+    params: &OptimizeParams,
+) -> Result<Vec<LaneYear>, String> {
+    let distribution = Distribution::new(tonnes, grades);
+    let total = distribution.total();
+    if total <= 0.0 {
+        return Err("The reserve has no tonnes".into());
+    }
+    if params.metal_price <= 0.0 || params.proc_cap <= 0.0 || params.mining_rate <= 0.0 {
+        return Err("Metal price, mining rate and processing capacity must be positive".into());
+    }
+
+    let price = params.metal_price as f64;
+    let (mining_cost, processing_cost) = (params.mining_cost as f64, params.processing_cost as f64);
+    let discount = params.discount_rate as f64;
+    let mill = params.proc_cap as f64;
+    let mut mine = params.mining_rate as f64;
+    if params.min_life > 0.0 {
+        mine = mine.min(total / params.min_life as f64);
+    }
+    let market = (params.market_cap > 0.0).then_some(params.market_cap as f64);
+
+    let mine_cutoff = processing_cost / price;
+    let mine_mill = distribution.cutoff_for_fraction((mill / mine).min(1.0));
+    let mine_market = market.map(|market| distribution.cutoff_for_metal(market / mine));
+    let mill_market = market.map(|market| distribution.cutoff_for_grade(market / mill));
+
+    //value per tonne mined at `cutoff`, charging `cost` a year of the binding capacity
+    let value = |cutoff: f64, cost: f64| {
+        let (ore, metal) = distribution.above(cutoff);
+        let (ore, metal) = (ore / total, metal / total);
+        let time = (1.0 / mine)
+            .max(ore / mill)
+            .max(market.map_or(0.0, |market| metal / market));
+        metal * price - ore * processing_cost - mining_cost - cost * time
+    };
+
+    //NPV at the start of each year from the previous pass
+    let mut npvs: Vec<f64> = Vec::new();
+    let mut schedule: Vec<LaneYear> = Vec::new();
+    for _ in 0..params.num_iters.max(1) {
+        schedule.clear();
+        let mut remaining = 1.0;
+        while remaining > 1e-9 && schedule.len() < MAX_YEARS {
+            let npv = npvs.get(schedule.len()).copied().unwrap_or(0.0);
+            let cost = discount * npv;
+            let mill_cutoff = ((processing_cost + cost / mill) / price).max(mine_cutoff);
+            let market_cutoff = market
+                .filter(|market| price > cost / market)
+                .map(|market| processing_cost / (price - cost / market));
+
+            let candidates = [
+                (Some(mine_cutoff), Limit::Mine),
+                (Some(mill_cutoff), Limit::Mill),
+                (market_cutoff, Limit::Market),
+                (Some(mine_mill), Limit::MineMill),
+                (mine_market, Limit::MineMarket),
+                (mill_market, Limit::MillMarket),
+            ];
+            let mut best = (mine_cutoff, Limit::Mine, value(mine_cutoff, cost));
+            for (cutoff, limit) in candidates.into_iter().skip(1) {
+                if let Some(cutoff) = cutoff {
+                    let v = value(cutoff, cost);
+                    if v > best.2 {
+                        best = (cutoff, limit, v);
+                    }
+                }
+            }
+            let (cutoff, limit, _) = best;
+
+            let (ore, metal) = distribution.above(cutoff);
+            let ore_fraction = ore / total;
+            let available = remaining * total;
+            let metal_fraction = metal / total;
+            let mut mined = mine.min(available);
+            if ore_fraction > 0.0 {
+                mined = mined.min(mill / ore_fraction);
+            }
+            if let Some(market) = market.filter(|_| metal_fraction > 0.0) {
+                mined = mined.min(market / metal_fraction);
+            }
+            let processed = mined * ore_fraction;
+            let metal = if ore > 0.0 {
+                processed * metal / ore
+            } else {
+                0.0
+            };
+
+            schedule.push(LaneYear {
+                cutoff,
+                limit,
+                mined,
+                processed,
+                grade: if processed > 0.0 {
+                    metal / processed
+                } else {
+                    0.0
+                },
+                metal,
+                cash_flow: metal * price - processed * processing_cost - mined * mining_cost,
+                npv: 0.0,
+            });
+            remaining -= mined / total;
+        }
+
+        //discount the cash flows back from the last year
+        let mut npv = 0.0;
+        for year in schedule.iter_mut().rev() {
+            npv = (year.cash_flow + npv) / (1.0 + discount);
+            year.npv = npv;
+        }
+
+        let previous = npvs.first().copied().unwrap_or(0.0);
+        npvs = schedule.iter().map(|y| y.npv).collect();
+        if (npv - previous).abs() <= TOLERANCE * npv.abs().max(1.0) {
+            break;
+        }
+    }
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //1 t blocks at grades 0.01 to 1.00
+    fn reserve() -> (Vec<f64>, Vec<f64>) {
+        let grades = (1..=100).map(|g| g as f64 / 100.0).collect::<Vec<_>>();
+        (vec![1.0; grades.len()], grades)
+    }
+
+    fn params(mining_rate: f32, proc_cap: f32, market_cap: f32) -> OptimizeParams {
+        OptimizeParams {
+            mining_rate,
+            proc_cap,
+            market_cap,
+            metal_price: 100.0,
+            processing_cost: 24.5,
+            discount_rate: 0.1,
+            num_iters: 100,
+            ..Default::default()
+        }
+    }
+
+    fn first_year(params: &OptimizeParams) -> LaneYear {
+        let (tonnes, grades) = reserve();
+        lane_schedule(&tonnes, &grades, params).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn small_mine_limits() {
+        //the mill and market never fill, so the cutoff only has to pay for processing
+        let year = first_year(&params(10.0, 1000.0, 0.0));
+        assert_eq!(year.limit, Limit::Mine);
+        assert_eq!(year.cutoff, 0.245);
+        assert_eq!(year.mined, 10.0);
+    }
+
+    #[test]
+    fn small_mill_limits() {
+        let year = first_year(&params(1000.0, 10.0, 0.0));
+        assert_eq!(year.limit, Limit::Mill);
+        //the mill cutoff carries the opportunity cost of a year of milling
+        let expected = (24.5 + 0.1 * year.npv / 10.0) / 100.0;
+        assert!((year.cutoff - expected).abs() < 1e-4);
+        assert!((year.processed - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn small_market_limits() {
+        let year = first_year(&params(1000.0, 1000.0, 2.0));
+        assert_eq!(year.limit, Limit::Market);
+        let expected = 24.5 / (100.0 - 0.1 * year.npv / 2.0);
+        assert!((year.cutoff - expected).abs() < 1e-4);
+        assert!((year.metal - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mine_and_mill_balance() {
+        //half of each year's tonnes fill the mill at grades 0.51 and up
+        let year = first_year(&params(10.0, 5.0, 0.0));
+        assert_eq!(year.limit, Limit::MineMill);
+        assert_eq!(year.cutoff, 0.51);
+        assert!((year.mined - 10.0).abs() < 1e-9);
+        assert!((year.processed - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn schedule_mines_the_whole_reserve() {
+        let (tonnes, grades) = reserve();
+        for params in [
+            params(10.0, 1000.0, 0.0),
+            params(1000.0, 10.0, 0.0),
+            params(1000.0, 1000.0, 2.0),
+        ] {
+            let schedule = lane_schedule(&tonnes, &grades, &params).unwrap();
+            let mined = schedule.iter().map(|y| y.mined).sum::<f64>();
+            assert!((mined - 100.0).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::{prelude::*, render::view::RenderLayers, utils::HashMap};
+use bevy_aabb_instancing::{CuboidMaterial, CuboidMaterialMap, Cuboids, COLOR_MODE_RGB};
+use colorgrad::Gradient;
+use smooth_bevy_cameras::LookTransform;
+
+use crate::{
+    axes::SceneAxes,
+    block_model::{BlockLayer, BlockModelDB, BlockRows},
+    patching::CoarseLod,
+    spatial_index::SpatialIndex,
+    stats::StatsCache,
+};
+
+/// Camera travel (relative to its distance from the target) before transparent
+/// layers are re-sorted.
+const RESORT_THRESHOLD: f32 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LayerStyle {
+    /// Global opacity of the layer in `0..=1`.
+    pub opacity: f32,
+    /// Optional opacity transfer function: blocks at or below `.0` are fully
+    /// transparent, ramping linearly to `opacity` at `.1` and above.
+    pub ramp: Option<(f64, f64)>,
+    /// Skip blocks whose six neighbours are all drawn opaque.
+    pub cull_hidden: bool,
+    /// Blocks below `.0` are drawn at `.1` times their opacity, `0` hiding them; used
+    /// to ghost the periods already mined during schedule playback.
+    pub ghost: Option<(f64, f32)>,
+}
+
+impl Default for LayerStyle {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            ramp: None,
+            cull_hidden: true,
+            ghost: None,
+        }
+    }
+}
+
+impl LayerStyle {
+    pub fn alpha(&self, value: f64) -> f32 {
+        let ramp = match self.ramp {
+            Some((low, high)) if high > low => ((value - low) / (high - low)).clamp(0.0, 1.0),
+            Some((low, _)) => {
+                if value > low {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            None => 1.0,
+        };
+        let ghost = match self.ghost {
+            Some((below, ghost)) if value < below => ghost,
+            _ => 1.0,
+        };
+        self.opacity * ramp as f32 * ghost
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.opacity < 1.0
+            || self.ramp.is_some()
+            || self
+                .ghost
+                .map_or(false, |(_, ghost)| ghost > 0.0 && ghost < 1.0)
+    }
+}
+
+/// Colour of `value` on `cmap`, stretched over `range`.
+pub fn value_color(cmap: &Gradient, range: (f64, f64), value: f64, alpha: f32) -> Color {
+    let t = if range.1 > range.0 {
+        (value - range.0) / (range.1 - range.0)
+    } else {
+        0.5
+    };
+    let color = cmap.at(t);
+    Color::rgba(color.r as f32, color.g as f32, color.b as f32, alpha)
+}
+
+#[derive(Resource, Default)]
+pub struct LayerStyles {
+    pub styles: HashMap<BlockLayer, LayerStyle>,
+}
+
+#[derive(Event, Clone, Debug)]
+pub enum LayerEvent {
+    /// (Re)build the patches of a layer, replacing any already spawned.
+    Spawn(BlockLayer),
+    Despawn(BlockLayer),
+}
+
+pub fn update_layers(
+    mut commands: Commands,
+    mut layer_events: EventReader<LayerEvent>,
+    patches: Query<(Entity, &BlockLayer)>,
+    block_models: Res<BlockModelDB>,
+    styles: Res<LayerStyles>,
+    axes: Res<SceneAxes>,
+    mut stats_cache: ResMut<StatsCache>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    mut material_map: ResMut<CuboidMaterialMap>,
+) {
+    for event in layer_events.iter() {
+        let layer = match event {
+            LayerEvent::Spawn(layer) | LayerEvent::Despawn(layer) => layer,
+        };
+        patches
+            .iter()
+            .filter(|(_, l)| *l == layer)
+            .for_each(|(ent, _)| commands.entity(ent).despawn_recursive());
+
+        let LayerEvent::Spawn(layer) = event else {
+            continue;
+        };
+        let Some(bm) = block_models.block_models.get(&layer.grid) else {
+            continue;
+        };
+        let Some(stats) = stats_cache.get(bm, &layer.column) else {
+            continue;
+        };
+        let range = (stats.min, stats.max);
+        let style = styles.styles.get(layer).copied().unwrap_or_default();
+        let occlusion = if style.cull_hidden {
+            spatial_index.grid(bm)
+        } else {
+            None
+        };
+
+        let patches = bm.aabb_instances(
+            layer.column.clone(),
+            colorgrad::turbo(),
+            range,
+            &style,
+            occlusion,
+            &axes,
+            22500,
+        );
+
+        let material_id = material_map.push(CuboidMaterial {
+            color_mode: COLOR_MODE_RGB,
+            ..default()
+        });
+        for patch in patches.into_iter() {
+            let mut entity = commands.spawn(SpatialBundle::default());
+            entity.insert((
+                patch.cuboids,
+                patch.aabb,
+                patch.rows,
+                patch.lod,
+                layer.clone(),
+                material_id,
+                RenderLayers::layer(0),
+            ));
+            if patch.coarse {
+                entity.insert(CoarseLod);
+            }
+        }
+    }
+}
+
+/// Keeps the instances of semi-transparent layers sorted back to front so that
+/// blending composites in the right order within each patch.
+pub fn sort_transparent_layers(
+    cameras: Query<&LookTransform>,
+    styles: Res<LayerStyles>,
+    mut patches: Query<(&mut Cuboids, &mut BlockRows, &BlockLayer)>,
+    added: Query<(), Added<BlockLayer>>,
+    mut last_eye: Local<Option<Vec3>>,
+) {
+    let Some(look) = cameras.iter().next() else {
+        return;
+    };
+    let eye = look.eye;
+    let moved = last_eye.map_or(true, |last| {
+        last.distance(eye) > RESORT_THRESHOLD * look.target.distance(eye)
+    });
+    if !moved && added.is_empty() {
+        return;
+    }
+    *last_eye = Some(eye);
+
+    for (mut cuboids, mut rows, layer) in patches.iter_mut() {
+        if !styles
+            .styles
+            .get(layer)
+            .map_or(false, |style| style.is_transparent())
+        {
+            continue;
+        }
+
+        let mut order = (0..cuboids.instances.len()).collect::<Vec<_>>();
+        let distance = |i: usize| {
+            let c = &cuboids.instances[i];
+            ((c.minimum + c.maximum) / 2.0).distance_squared(eye)
+        };
+        order.sort_by(|a, b| distance(*b).total_cmp(&distance(*a)));
+
+        let instances = order.iter().map(|i| cuboids.instances[*i]).collect();
+        let sorted_rows = order.iter().map(|i| rows.0[*i]).collect();
+        cuboids.instances = instances;
+        rows.0 = sorted_rows;
+    }
+}
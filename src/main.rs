@@ -1,6 +1,8 @@
+use std::time::Duration;
+
 use bevy::{
+    app::ScheduleRunnerPlugin,
     core_pipeline::clear_color::ClearColorConfig,
-    math::Vec3A,
     prelude::*,
     render::{
         primitives::Aabb,
@@ -9,7 +11,8 @@ use bevy::{
     },
     sprite::Anchor,
     utils::HashMap,
-    window::PrimaryWindow,
+    window::{ExitCondition, PrimaryWindow},
+    winit::WinitPlugin,
 };
 use bevy_aabb_instancing::{Cuboid, Cuboids, VertexPullingRenderPlugin};
 use bevy_egui::{
@@ -19,10 +22,36 @@ use bevy_egui::{
 use image::EncodableLayout;
 use image::{ImageBuffer, Rgba};
 
+mod axes;
+mod batch;
 mod block;
 mod block_model;
+mod camera;
+mod drillholes;
+mod economics;
+mod export;
+mod grade_tonnage;
+mod isosurface;
+mod jobs;
+mod lane;
+mod layers;
+mod mesh_io;
 mod optimizer;
+mod patching;
+mod picking;
+mod pit;
+mod playback;
+mod project;
+mod reference;
+mod schedule;
+mod shells;
+mod solid;
+mod spatial_index;
+mod stats;
+mod surface;
+mod swath;
 mod ui;
+mod wireframe;
 
 use block_model::{BlockModelDB, BlockModelResource};
 use colorgrad::Gradient;
@@ -31,39 +60,184 @@ use smooth_bevy_cameras::{
     controllers::orbit::{OrbitCameraBundle, OrbitCameraController, OrbitCameraPlugin},
     LookTransform, LookTransformPlugin,
 };
+use stats::StatsCache;
 use ui::{init_optimizer, OccupiedScreenSpace, ViewAll};
 
 fn main() {
-    App::new()
-        .add_state::<AppState>()
+    let batch = match batch::BatchConfig::from_args() {
+        Some(Ok(config)) => Some(config),
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+
+    let window_plugin = WindowPlugin {
+        primary_window: Some(Window {
+            title: "bm_viewer".into(),
+            ..default()
+        }),
+        ..default()
+    };
+    //batch runs are headless: no window and no event loop, the image is rendered offscreen
+    let plugins = if batch.is_some() {
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..window_plugin
+            })
+            .disable::<WinitPlugin>()
+            .add(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+                1.0 / 60.0,
+            )))
+    } else {
+        DefaultPlugins.set(window_plugin)
+    };
+    //egui draws into the primary window, so the panels only run when there is one
+    let has_window = any_with_component::<PrimaryWindow>();
+
+    let mut app = App::new();
+    app.add_state::<AppState>()
         .add_event::<ViewAll>()
         .add_event::<ColorBarSelectionEvent>()
+        .add_event::<layers::LayerEvent>()
+        .add_event::<camera::CameraView>()
+        .add_event::<picking::ClickRay>()
         .insert_resource(Msaa::Sample4)
         .insert_resource(ui::FileResource::default())
         .insert_resource(ui::FileInputResource::default())
         .insert_resource(BlockModelResource::default())
         .insert_resource(BlockModelDB::default())
         .insert_resource(OptimizeParams::default())
+        .insert_resource(picking::Selection::default())
+        .insert_resource(spatial_index::SpatialIndex::default())
         .init_resource::<OccupiedScreenSpace>()
-        .add_plugins(DefaultPlugins)
+        .init_resource::<ui::OpenPanels>()
+        .init_resource::<StatsCache>()
+        .init_resource::<layers::LayerStyles>()
+        .init_resource::<ui::stats::StatsPanel>()
+        .init_resource::<ui::grade_tonnage::GradeTonnagePanel>()
+        .init_resource::<ui::swath::SwathPanel>()
+        .init_resource::<isosurface::IsoShells>()
+        .init_resource::<ui::isosurface::IsoSurfacePanel>()
+        .init_resource::<export::ImageExport>()
+        .init_resource::<ui::export::ExportPanel>()
+        .init_resource::<camera::CameraBookmarks>()
+        .init_resource::<project::ProjectFile>()
+        .init_resource::<camera::CameraTransition>()
+        .init_resource::<camera::ProjectionSettings>()
+        .init_resource::<axes::SceneAxes>()
+        .init_resource::<reference::ReferenceFrame>()
+        .init_resource::<surface::Surfaces>()
+        .init_resource::<ui::surface::SurfacePanel>()
+        .init_resource::<ui::camera::CameraPanel>()
+        .init_resource::<drillholes::Drillholes>()
+        .init_resource::<drillholes::DrillholeSelection>()
+        .init_resource::<ui::drillholes::DrillholePanel>()
+        .init_resource::<wireframe::Wireframes>()
+        .init_resource::<ui::solid::SolidPanel>()
+        .init_resource::<ui::economics::EconomicsPanel>()
+        .init_resource::<ui::lane::LanePanel>()
+        .init_resource::<jobs::Jobs>()
+        .init_resource::<ui::CheckedColumns>()
+        .init_resource::<ui::shells::ShellsPanel>()
+        .init_resource::<playback::Playback>()
+        .add_plugins(plugins)
         .add_plugins((
             VertexPullingRenderPlugin { outlines: true },
+            export::ExportReadbackPlugin,
             LookTransformPlugin,
             OrbitCameraPlugin::default(),
             EguiPlugin,
         ))
         .add_systems(Startup, setup)
-        .add_systems(Startup, configure_visuals_system)
+        .add_systems(Startup, configure_visuals_system.run_if(has_window.clone()))
         .add_systems(Startup, (camera_2d))
         .add_systems(Update, show_color_bar)
-        .add_systems(Update, (ui::ui_system, ui::detect_file_drop))
-        .add_systems(Update, ui::file_drop.run_if(in_state(AppState::FileInput)))
-        .add_systems(Update, view_all)
         .add_systems(
             Update,
-            init_optimizer.run_if(in_state(AppState::OptimizeInit)),
+            (ui::ui_system, ui::detect_file_drop).run_if(has_window.clone()),
+        )
+        .add_systems(
+            Update,
+            ui::file_drop
+                .run_if(in_state(AppState::FileInput))
+                .run_if(has_window.clone()),
+        )
+        .add_systems(
+            Update,
+            (
+                view_all,
+                camera::set_view,
+                camera::animate_camera,
+                camera::sync_projection,
+                axes::apply_scene_axes,
+            )
+                .chain(),
         )
-        .run();
+        .add_systems(
+            Update,
+            (
+                layers::update_layers,
+                layers::sort_transparent_layers,
+                patching::update_lod,
+                export::run_image_export,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                picking::pick_blocks,
+                picking::highlight_selection,
+                ui::inspector::inspector_panel,
+                ui::hover::hover_tooltip,
+                ui::stats::stats_panel,
+                ui::grade_tonnage::grade_tonnage_panel,
+                ui::swath::swath_panel,
+                ui::swath::highlight_swath_slice,
+                ui::isosurface::isosurface_panel,
+                ui::export::export_panel,
+                ui::camera::camera_panel,
+                ui::surface::surface_panel,
+                reference::draw_reference_frame.after(ui::ui_system),
+            )
+                .run_if(has_window.clone()),
+        )
+        .add_systems(
+            Update,
+            (
+                drillholes::pick_intervals.after(picking::pick_blocks),
+                drillholes::highlight_interval,
+                ui::drillholes::drillhole_panel,
+                ui::drillholes::interval_inspector,
+                ui::solid::solid_panel,
+                ui::economics::economics_panel,
+                ui::lane::lane_panel,
+                jobs::refresh_jobs,
+                jobs::finish_jobs,
+                ui::jobs::jobs_panel,
+                ui::shells::shells_panel,
+                playback::run_playback,
+                playback::highlight_period,
+                ui::playback::playback_panel,
+            )
+                .run_if(has_window.clone()),
+        )
+        .add_systems(
+            Update,
+            init_optimizer
+                .run_if(in_state(AppState::OptimizeInit))
+                .run_if(has_window),
+        );
+
+    if let Some(config) = batch {
+        app.insert_resource(batch::Batch::new(config))
+            .add_systems(Update, batch::run_batch);
+    }
+
+    app.run();
 }
 
 #[derive(Default, Debug, Hash, PartialEq, Eq, Clone, States)]
@@ -81,11 +255,7 @@ fn configure_visuals_system(mut contexts: EguiContexts) {
     });
 }
 
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
+fn setup(mut commands: Commands, axes: Res<axes::SceneAxes>) {
     //camera
     commands.spawn(Camera3dBundle::default()).insert((
         OrbitCameraBundle::new(
@@ -97,27 +267,27 @@ fn setup(
                 enabled: true,
                 pixels_per_line: 53.0,
             },
-            Vec3::new(14424.87, 108851.7, 3597.5),
-            Vec3::new(14424.87, 108851.7, 3197.5),
-            Vec3::Y,
+            axes.to_world(Vec3::new(14424.87, 108851.7, 3597.5)),
+            axes.to_world(Vec3::new(14424.87, 108851.7, 3197.5)),
+            axes.to_world(Vec3::Y),
         ),
         RenderLayers::from_layers(&[0]),
     ));
 
-    // plane
-    commands.spawn(PbrBundle {
-        mesh: meshes.add(Mesh::from(shape::Plane {
-            size: 5.0,
-            subdivisions: 0,
-        })),
-        material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
-        ..default()
-    });
-
     commands.insert_resource(AmbientLight {
         color: Color::WHITE,
         brightness: 1.0,
     });
+
+    //directional light to shade mesh layers (cuboids are unlit)
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            illuminance: 10000.0,
+            ..default()
+        },
+        transform: Transform::default().looking_to(Vec3::new(-0.4, -1.0, -0.6), Vec3::Y),
+        ..default()
+    });
 }
 
 fn camera_2d(mut commands: Commands) {
@@ -144,12 +314,14 @@ pub struct ColorBarSelectionEvent {
 fn show_color_bar(
     mut commands: Commands,
     mut images: ResMut<Assets<Image>>,
-    windows: Query<&Window>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    batch: Option<Res<batch::Batch>>,
     occupied_screen_space: Res<OccupiedScreenSpace>,
     mut entities_to_delete: Local<Vec<Entity>>,
     mut color_bar_selection_event: EventReader<ColorBarSelectionEvent>,
     mut current_color_bar_selection: Local<HashMap<ColorBarSelectionEvent, Vec<Entity>>>,
     blockmodels: Res<BlockModelDB>,
+    mut stats_cache: ResMut<StatsCache>,
 ) {
     let cb_event = color_bar_selection_event.iter().next();
 
@@ -168,7 +340,15 @@ fn show_color_bar(
         return;
     }
 
-    let window = windows.single();
+    //batch runs have no window and lay the bars out for the image they write
+    let Some(canvas) = windows
+        .get_single()
+        .map(|window| Vec2::new(window.width(), window.height()))
+        .ok()
+        .or(batch.map(|batch| batch.size()))
+    else {
+        return;
+    };
     let gradient = colorgrad::turbo();
     let width = 500;
     let height = 50;
@@ -228,7 +408,7 @@ fn show_color_bar(
                         transform: Transform::from_translation(Vec3::new(
                             0.0 + occupied_screen_space.left as f32 / 2.0
                                 - occupied_screen_space.right as f32 / 2.0,
-                            -window.height() / 2.0
+                            -canvas.y / 2.0
                                 + 2f32 * height as f32
                                 + occupied_screen_space.bottom as f32 / 2.0
                                 - occupied_screen_space.top as f32 / 2.0,
@@ -242,29 +422,18 @@ fn show_color_bar(
                 .id(),
         );
 
-        let min: f32 = blockmodels
+        let Some(stats) = blockmodels
             .block_models
             .get(cb.grid.as_str())
-            .unwrap()
-            .df
-            .column(cb.column.as_str())
-            .unwrap()
-            .min()
-            .unwrap();
-
-        let max: f32 = blockmodels
-            .block_models
-            .get(cb.grid.as_str())
-            .unwrap()
-            .df
-            .column(cb.column.as_str())
-            .unwrap()
-            .max()
-            .unwrap();
+            .and_then(|bm| stats_cache.get(bm, cb.column.as_str()))
+        else {
+            continue;
+        };
+        let (min, max) = (stats.min, stats.max);
 
         for i in 0..=10 {
             let text = Text::from_section(
-                format!("{:.3}", remap(i as f32, 0.0..=10.0, min..=max)),
+                format!("{:.3}", remap(i as f64, 0.0..=10.0, min..=max)),
                 TextStyle {
                     ..Default::default()
                 },
@@ -280,7 +449,7 @@ fn show_color_bar(
                                     - occupied_screen_space.right as f32 / 2.0
                                     + i as f32 * width as f32 / 10.0
                                     - width as f32 / 2f32,
-                                -window.height() / 2.0
+                                -canvas.y / 2.0
                                     + height as f32
                                     + occupied_screen_space.bottom as f32 / 2.0
                                     - occupied_screen_space.top as f32 / 2.0,
@@ -309,7 +478,7 @@ fn show_color_bar(
                         transform: Transform::from_translation(Vec3::new(
                             0.0 + occupied_screen_space.left as f32 / 2.0
                                 - occupied_screen_space.right as f32 / 2.0,
-                            -window.height() / 2.0
+                            -canvas.y / 2.0
                                 + 3f32 * height as f32
                                 + occupied_screen_space.bottom as f32 / 2.0
                                 - occupied_screen_space.top as f32 / 2.0,
@@ -324,46 +493,34 @@ fn show_color_bar(
     }
 }
 
+/// Frames the visible data, keeping the current viewing direction.
 fn view_all(
-    mut cameras: Query<(&OrbitCameraController, &mut LookTransform, &mut Transform)>,
+    cameras: Query<(&LookTransform, &Projection), With<OrbitCameraController>>,
     mut view_all_event: EventReader<ViewAll>,
-    bounding_boxes: Query<&Aabb, With<Cuboids>>,
+    bounding_boxes: Query<(&Aabb, &Visibility), With<Cuboids>>,
+    wireframes: Res<wireframe::Wireframes>,
+    axes: Res<axes::SceneAxes>,
+    settings: Res<camera::ProjectionSettings>,
+    mut transition: ResMut<camera::CameraTransition>,
 ) {
     if view_all_event.iter().next().is_none() {
         return;
     }
-    //println!("HELLO");
-    // Can only control one camera at a time.
-    let (mut transform, mut scene_transform) =
-        if let Some((_, transform, scene_transform)) = cameras.iter_mut().find(|c| c.0.enabled) {
-            (transform, scene_transform)
-        } else {
-            return;
-        };
-
-    //compute bounding box
-    let mut min = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
-    let mut max = Vec3::new(f32::MIN, f32::MIN, f32::MIN);
-    let mut boxes_flag = false;
-    for bounding_box in bounding_boxes.iter() {
-        boxes_flag = true;
-        min = min.min((bounding_box.min()).into());
-        max = max.max((bounding_box.max()).into());
-    }
-
-    if !boxes_flag {
+    let Ok((look, projection)) = cameras.get_single() else {
         return;
-    }
-    let scene_box = Aabb::from_min_max(min, max);
-
-    let length = scene_box.half_extents.max_element();
-    let fov_angle = f32::to_radians(45.0); //proj.fov;
-    let dist = length / (fov_angle / 2.0).tan();
-
-    *transform = LookTransform::new(
-        (scene_box.center + Vec3A::new(0.0, 0.0, dist)).into(),
-        scene_box.center.into(),
-        Vec3::Y,
-    )
-    .into();
+    };
+    let bounds = [
+        camera::visible_bounds(bounding_boxes.iter()),
+        wireframes.visible_bounds(&axes),
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(|(amin, amax), (bmin, bmax)| (amin.min(bmin), amax.max(bmax)));
+    let Some(bounds) = bounds else {
+        return;
+    };
+
+    let direction = (look.eye - look.target).try_normalize().unwrap_or(Vec3::Z);
+    let to = camera::frame_bounds(bounds, direction, look.up, projection, settings.fov);
+    transition.start(*look, to);
 }
@@ -0,0 +1,232 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+use bevy::prelude::*;
+
+use crate::isosurface::IsoMesh;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn parse_f32(s: &str) -> io::Result<f32> {
+    s.trim()
+        .parse()
+        .map_err(|_| invalid(format!("Expected a number, found '{}'", s.trim())))
+}
+
+/// Reads a triangulation from OBJ, STL (ASCII or binary) or DXF (3DFACE and POLYFACE),
+/// chosen by file extension. Coordinates are taken as model coordinates.
+pub fn read_mesh(path: &Path) -> io::Result<IsoMesh> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let mut mesh = match extension.as_deref() {
+        Some("obj") => read_obj(path)?,
+        Some("stl") => read_stl(path)?,
+        Some("dxf") => read_dxf(path)?,
+        _ => return Err(invalid("Unsupported mesh format")),
+    };
+    if mesh.indices.is_empty() {
+        return Err(invalid("No triangles found"));
+    }
+    mesh.compute_normals();
+    Ok(mesh)
+}
+
+/// Vertices and faces of an OBJ file; polygons are fan triangulated.
+fn read_obj(path: &Path) -> io::Result<IsoMesh> {
+    let mut mesh = IsoMesh::default();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("v") => {
+                let mut p = [0.0; 3];
+                for c in p.iter_mut() {
+                    *c = parse_f32(parts.next().ok_or_else(|| invalid("Short vertex"))?)?;
+                }
+                mesh.positions.push(p);
+            }
+            Some("f") => {
+                let count = mesh.positions.len() as i64;
+                let face = parts
+                    .map(|v| {
+                        //"v", "v/vt", "v//vn" or "v/vt/vn", 1-based or negative from the end
+                        let i: i64 = v
+                            .split('/')
+                            .next()
+                            .unwrap_or_default()
+                            .parse()
+                            .map_err(|_| invalid(format!("Bad face index '{}'", v)))?;
+                        let i = if i < 0 { count + i } else { i - 1 };
+                        if i < 0 || i >= count {
+                            return Err(invalid(format!("Face index {} out of range", v)));
+                        }
+                        Ok(i as u32)
+                    })
+                    .collect::<io::Result<Vec<_>>>()?;
+                for k in 1..face.len().saturating_sub(1) {
+                    mesh.indices.extend([face[0], face[k], face[k + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+fn push_triangle(mesh: &mut IsoMesh, triangle: [Vec3; 3]) {
+    let first = mesh.positions.len() as u32;
+    mesh.positions.extend(triangle.map(|v| v.to_array()));
+    mesh.indices.extend([first, first + 1, first + 2]);
+}
+
+fn read_stl(path: &Path) -> io::Result<IsoMesh> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    //binary files may also start with "solid", so trust the size implied by the count
+    let binary_count = bytes
+        .get(80..84)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    if let Some(count) = binary_count.filter(|count| 84 + count * 50 == bytes.len()) {
+        let mut mesh = IsoMesh::default();
+        for record in bytes[84..].chunks_exact(50) {
+            let value = |i: usize| {
+                let b = &record[12 + i * 4..16 + i * 4];
+                f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+            };
+            push_triangle(
+                &mut mesh,
+                [0, 1, 2].map(|v| Vec3::new(value(v * 3), value(v * 3 + 1), value(v * 3 + 2))),
+            );
+        }
+        debug_assert_eq!(mesh.triangle_count(), count);
+        return Ok(mesh);
+    }
+
+    let text = String::from_utf8_lossy(&bytes);
+    let mut mesh = IsoMesh::default();
+    let mut facet = Vec::with_capacity(3);
+    for line in text.lines() {
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("vertex") => {
+                let mut p = [0.0; 3];
+                for c in p.iter_mut() {
+                    *c = parse_f32(parts.next().ok_or_else(|| invalid("Short vertex"))?)?;
+                }
+                facet.push(Vec3::from(p));
+            }
+            Some("endfacet") => {
+                if facet.len() == 3 {
+                    push_triangle(&mut mesh, [facet[0], facet[1], facet[2]]);
+                }
+                facet.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+/// Entity being read from a DXF file.
+enum DxfEntity {
+    Face([Vec3; 4]),
+    /// A POLYLINE vertex: a position, or with flag 128 alone a polyface face record
+    /// indexing earlier positions.
+    Vertex {
+        position: Vec3,
+        flags: i32,
+        indices: [i32; 4],
+    },
+}
+
+/// Triangles from the 3DFACE entities and polyface mesh POLYLINEs of an ASCII DXF file.
+fn read_dxf(path: &Path) -> io::Result<IsoMesh> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+    let mut mesh = IsoMesh::default();
+    //positions of the current polyface mesh, which its face records index 1-based
+    let mut polyface: Vec<Vec3> = Vec::new();
+
+    fn finish(entity: &mut Option<DxfEntity>, polyface: &mut Vec<Vec3>, mesh: &mut IsoMesh) {
+        match entity.take() {
+            //a repeated fourth corner marks a triangle
+            Some(DxfEntity::Face([a, b, c, d])) => {
+                push_triangle(mesh, [a, b, c]);
+                if d != c {
+                    push_triangle(mesh, [a, c, d]);
+                }
+            }
+            Some(DxfEntity::Vertex {
+                position, flags, ..
+            }) if flags & 64 != 0 => polyface.push(position),
+            Some(DxfEntity::Vertex { indices, .. }) => {
+                //negative indices only hide the edge; zero marks an unused corner
+                let corners = indices
+                    .iter()
+                    .filter(|i| **i != 0)
+                    .filter_map(|i| polyface.get(i.unsigned_abs() as usize - 1).copied())
+                    .collect::<Vec<_>>();
+                for k in 1..corners.len().saturating_sub(1) {
+                    push_triangle(mesh, [corners[0], corners[k], corners[k + 1]]);
+                }
+            }
+            None => {}
+        }
+    }
+
+    //(group code, value) pairs; an entity ends where the next one starts
+    let mut entity: Option<DxfEntity> = None;
+    while let (Some(code), Some(value)) = (lines.next(), lines.next()) {
+        let (code, value) = (code?, value?);
+        let code: i32 = code
+            .trim()
+            .parse()
+            .map_err(|_| invalid(format!("Bad group code '{}'", code.trim())))?;
+        let value = value.trim();
+
+        if code == 0 {
+            finish(&mut entity, &mut polyface, &mut mesh);
+            match value {
+                "3DFACE" => entity = Some(DxfEntity::Face([Vec3::ZERO; 4])),
+                "VERTEX" => {
+                    entity = Some(DxfEntity::Vertex {
+                        position: Vec3::ZERO,
+                        flags: 0,
+                        indices: [0; 4],
+                    })
+                }
+                "POLYLINE" | "SEQEND" => polyface.clear(),
+                _ => {}
+            }
+            continue;
+        }
+        match entity.as_mut() {
+            //10..13 are the X of each corner, 20..23 Y and 30..33 Z
+            Some(DxfEntity::Face(corners)) if matches!(code, 10..=13 | 20..=23 | 30..=33) => {
+                let corner = (code % 10) as usize;
+                let axis = (code / 10 - 1) as usize;
+                corners[corner][axis] = parse_f32(value)?;
+            }
+            Some(DxfEntity::Vertex {
+                position,
+                flags,
+                indices,
+            }) => match code {
+                10 | 20 | 30 => position[(code / 10 - 1) as usize] = parse_f32(value)?,
+                70 => *flags = parse_f32(value)? as i32,
+                71..=74 => indices[(code - 71) as usize] = parse_f32(value)? as i32,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    finish(&mut entity, &mut polyface, &mut mesh);
+    Ok(mesh)
+}
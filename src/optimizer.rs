@@ -1,10 +1,16 @@
 use bevy::prelude::Resource;
 use ndarray::Array3;
+use polars::prelude::{NamedFrom, Series};
 
-use mining_width_maintainer::mining_width_maintainer::MiningWidthMaintainer;
+use crate::{
+    block_model::BlockModel, economics::EconomicModel, jobs::JobControl, schedule::Scheduler,
+    spatial_index::BlockGrid,
+};
 
-#[derive(Default, Resource)]
+#[derive(Resource, Clone)]
 pub struct OptimizeParams {
+    /// Block model the pit is optimised over.
+    pub grid: String,
     pub grade_col: String,
     pub tonnage_col: String,
     pub discount_rate: f32,
@@ -12,15 +18,141 @@ pub struct OptimizeParams {
     pub min_life: f32,
     pub proc_cap: f32,
     pub mining_rate: f32,
+    /// Metal that can be sold a year, 0 for no limit.
+    pub market_cap: f32,
     pub metal_price: f32,
     pub mining_cost: f32,
     pub processing_cost: f32,
     pub num_iters: usize,
+    /// Sweeps between writing the schedule so far to the period column, 0 for only once
+    /// scheduling is done.
+    pub refresh_every: usize,
+    /// Overall slope angle in degrees from horizontal.
+    pub slope_angle: f32,
+    /// Narrowest opening the scheduler mines, in metres; 0 for a single cell.
+    pub mining_width: f32,
+    /// Column the in-pit flag is written to.
+    pub pit_col: String,
+    /// Column the period each block is mined in is written to.
+    pub period_col: String,
+}
+
+impl Default for OptimizeParams {
+    fn default() -> Self {
+        Self {
+            grid: String::new(),
+            grade_col: String::new(),
+            tonnage_col: String::new(),
+            discount_rate: 0.0,
+            cutoff: 0.0,
+            min_life: 0.0,
+            proc_cap: 0.0,
+            mining_rate: 0.0,
+            market_cap: 0.0,
+            metal_price: 0.0,
+            mining_cost: 0.0,
+            processing_cost: 0.0,
+            num_iters: 20,
+            refresh_every: 0,
+            slope_angle: 45.0,
+            mining_width: 0.0,
+            pit_col: "in_pit".into(),
+            period_col: "period".into(),
+        }
+    }
 }
 
 pub struct Optimizer {
     pub params: OptimizeParams,
     pub tonnage: Array3<f32>,
     pub grade: Array3<f32>,
-    pub sched: MiningWidthMaintainer,
+    pub sched: Scheduler,
+}
+
+impl Optimizer {
+    /// Rasterises `bm` and schedules the cells of the blocks flagged by the pit column.
+    pub fn new(
+        bm: &BlockModel,
+        grid: &BlockGrid,
+        params: &OptimizeParams,
+        control: &JobControl,
+    ) -> Result<Self, String> {
+        let in_pit = bm
+            .df
+            .column(&params.pit_col)
+            .and_then(|c| c.bool().cloned())
+            .map_err(|_| {
+                format!(
+                    "No pit flag column {}, optimise the pit first",
+                    params.pit_col
+                )
+            })?
+            .into_iter()
+            .map(|flag| flag == Some(true))
+            .collect::<Vec<_>>();
+        let (tonnage, grade) = Self::rasterise(bm, grid, params)?;
+        let mut include = Array3::from_elem(tonnage.dim(), false);
+        for (ind, row) in grid.iter() {
+            include[[ind.i, ind.j, ind.k]] = in_pit[*row];
+        }
+        let sched = Scheduler::new(&tonnage, &grade, &include, grid.cell, params, control)?;
+        Ok(Self {
+            params: params.clone(),
+            tonnage,
+            grade,
+            sched,
+        })
+    }
+
+    /// Improves the schedule for `num_iters` sweeps and returns its NPV, handing the
+    /// schedule to `refresh` every `refresh_every` sweeps.
+    pub fn run(
+        &mut self,
+        control: &JobControl,
+        mut refresh: impl FnMut(&Scheduler),
+    ) -> Result<f64, String> {
+        let every = self.params.refresh_every;
+        self.sched
+            .run(self.params.num_iters, control, |iteration, sched| {
+                if every > 0 && iteration % every == 0 {
+                    refresh(sched);
+                }
+            })
+    }
+
+    /// Tonnage and grade of every cell of `grid`, indexed `[i, j, k]`. Blocks larger than
+    /// a cell split their tonnage evenly over their cells; empty cells are zero.
+    pub fn rasterise(
+        bm: &BlockModel,
+        grid: &BlockGrid,
+        params: &OptimizeParams,
+    ) -> Result<(Array3<f32>, Array3<f32>), String> {
+        let economics = EconomicModel::new(bm, params)?;
+        let mut cells_per_row = vec![0usize; bm.df.height()];
+        for (_, row) in grid.iter() {
+            cells_per_row[*row] += 1;
+        }
+
+        let shape = (grid.dims.i, grid.dims.j, grid.dims.k);
+        let mut tonnage = Array3::zeros(shape);
+        let mut grade = Array3::zeros(shape);
+        for (ind, row) in grid.iter() {
+            tonnage[[ind.i, ind.j, ind.k]] =
+                (economics.tonnes[*row] / cells_per_row[*row] as f64) as f32;
+            grade[[ind.i, ind.j, ind.k]] = economics.grades[*row] as f32;
+        }
+        Ok((tonnage, grade))
+    }
+
+    /// Column of the period each block is mined in from per cell `periods`, taking the
+    /// earliest period over the block's cells. Null for blocks that are never mined.
+    pub fn period_column(grid: &BlockGrid, periods: &Array3<Option<u32>>, name: &str) -> Series {
+        let mut rows: Vec<Option<u32>> = vec![None; grid.rows];
+        for (ind, row) in grid.iter() {
+            if let Some(period) = periods[[ind.i, ind.j, ind.k]] {
+                rows[*row] = Some(rows[*row].map_or(period, |p| p.min(period)));
+            }
+        }
+        Series::new(name, rows)
+    }
 }
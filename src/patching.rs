@@ -0,0 +1,227 @@
+use bevy::{prelude::*, render::primitives::Aabb, utils::HashMap};
+use bevy_aabb_instancing::{Cuboid, Cuboids};
+use smooth_bevy_cameras::LookTransform;
+
+use crate::block_model::BlockRows;
+
+/// Maximum octree depth, guarding against many coincident blocks.
+const MAX_DEPTH: usize = 16;
+/// Blocks per edge merged into one coarse LOD block.
+const LOD_FACTOR: f32 = 4.0;
+/// Multiple of a patch's half diagonal beyond which its coarse LOD is shown.
+const LOD_DISTANCE_FACTOR: f32 = 6.0;
+
+/// A block that survived filtering, ready to be colour encoded.
+#[derive(Clone, Copy, Debug)]
+pub struct PatchBlock {
+    pub minimum: Vec3,
+    pub maximum: Vec3,
+    pub value: f64,
+    pub alpha: f32,
+    pub row: usize,
+}
+
+impl PatchBlock {
+    fn centre(&self) -> Vec3 {
+        (self.minimum + self.maximum) / 2.0
+    }
+}
+
+/// Camera distances (from the patch centre) at which a patch is shown.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct LodRange {
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl LodRange {
+    pub fn contains(&self, distance: f32) -> bool {
+        distance >= self.min_distance && distance < self.max_distance
+    }
+}
+
+/// Marks the merged stand-in of a patch. Its `BlockRows` name only one of the rows each
+/// merged cuboid covers, so it is drawn but never picked; the fine patch it stands in
+/// for holds the same blocks.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CoarseLod;
+
+pub struct Patch {
+    pub cuboids: Cuboids,
+    pub aabb: Aabb,
+    pub rows: BlockRows,
+    pub lod: LodRange,
+    pub coarse: bool,
+}
+
+/// Recursively splits `blocks` into octants until each holds at most `patch_size`.
+fn octree_leaves(
+    blocks: Vec<PatchBlock>,
+    patch_size: usize,
+    depth: usize,
+    leaves: &mut Vec<Vec<PatchBlock>>,
+) {
+    if blocks.len() <= patch_size || depth >= MAX_DEPTH {
+        if !blocks.is_empty() {
+            leaves.push(blocks);
+        }
+        return;
+    }
+
+    let (min, max) = blocks.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), b| (min.min(b.centre()), max.max(b.centre())),
+    );
+    let mid = (min + max) / 2.0;
+
+    let mut octants: [Vec<PatchBlock>; 8] = Default::default();
+    for b in blocks.into_iter() {
+        let c = b.centre();
+        let octant = (c.x > mid.x) as usize
+            | (((c.y > mid.y) as usize) << 1)
+            | (((c.z > mid.z) as usize) << 2);
+        octants[octant].push(b);
+    }
+    for octant in octants.into_iter() {
+        octree_leaves(octant, patch_size, depth + 1, leaves);
+    }
+}
+
+/// Merges the blocks of a patch on a grid `LOD_FACTOR` blocks wide, volume weighting
+/// their values.
+fn coarsen(blocks: &[PatchBlock]) -> Vec<PatchBlock> {
+    let origin = blocks
+        .iter()
+        .fold(Vec3::splat(f32::MAX), |min, b| min.min(b.minimum));
+    let cell = blocks.iter().fold(Vec3::splat(f32::MAX), |cell, b| {
+        cell.min(b.maximum - b.minimum)
+    }) * LOD_FACTOR;
+
+    let mut groups: HashMap<IVec3, (PatchBlock, f64)> = HashMap::default();
+    for b in blocks.iter() {
+        let key = ((b.minimum - origin) / cell).floor().as_ivec3();
+        let size = b.maximum - b.minimum;
+        let volume = size.x as f64 * size.y as f64 * size.z as f64;
+        let (merged, total) = groups.entry(key).or_insert((
+            PatchBlock {
+                value: 0.0,
+                alpha: 0.0,
+                ..*b
+            },
+            0.0,
+        ));
+        merged.minimum = merged.minimum.min(b.minimum);
+        merged.maximum = merged.maximum.max(b.maximum);
+        merged.value += b.value * volume;
+        merged.alpha = merged.alpha.max(b.alpha);
+        *total += volume;
+    }
+
+    groups
+        .into_values()
+        .map(|(mut merged, total)| {
+            merged.value /= total;
+            merged
+        })
+        .collect()
+}
+
+fn to_patch(blocks: &[PatchBlock], lod: LodRange, color: &impl Fn(f64, f32) -> u32) -> Patch {
+    let cuboids = Cuboids::new(
+        blocks
+            .iter()
+            .map(|b| Cuboid::new(b.minimum, b.maximum, color(b.value, b.alpha)))
+            .collect(),
+    );
+    let aabb = cuboids.aabb();
+    Patch {
+        cuboids,
+        aabb,
+        rows: BlockRows(blocks.iter().map(|b| b.row).collect()),
+        lod,
+        coarse: false,
+    }
+}
+
+/// Partitions blocks into spatially coherent patches of at most `patch_size` blocks,
+/// each with a coarse LOD counterpart shown when the camera is far away.
+///
+/// `color` encodes a block's value and alpha.
+pub fn build_patches(
+    blocks: Vec<PatchBlock>,
+    patch_size: usize,
+    color: impl Fn(f64, f32) -> u32,
+) -> Vec<Patch> {
+    let mut leaves = Vec::new();
+    octree_leaves(blocks, patch_size.max(1), 0, &mut leaves);
+
+    let mut patches = Vec::new();
+    for leaf in leaves.iter() {
+        let coarse = coarsen(leaf);
+        //only worth a separate LOD if it meaningfully reduces the instance count
+        if coarse.len() * 2 > leaf.len() {
+            patches.push(to_patch(
+                leaf,
+                LodRange {
+                    min_distance: 0.0,
+                    max_distance: f32::INFINITY,
+                },
+                &color,
+            ));
+            continue;
+        }
+
+        let fine = to_patch(
+            leaf,
+            LodRange {
+                min_distance: 0.0,
+                max_distance: f32::INFINITY,
+            },
+            &color,
+        );
+        let switch = LOD_DISTANCE_FACTOR * Vec3::from(fine.aabb.half_extents).length();
+        let coarse = Patch {
+            coarse: true,
+            ..to_patch(
+                &coarse,
+                LodRange {
+                    min_distance: switch,
+                    max_distance: f32::INFINITY,
+                },
+                &color,
+            )
+        };
+        patches.push(Patch {
+            lod: LodRange {
+                min_distance: 0.0,
+                max_distance: switch,
+            },
+            ..fine
+        });
+        patches.push(coarse);
+    }
+
+    patches
+}
+
+/// Shows the LOD of each patch appropriate for its distance to the camera.
+pub fn update_lod(
+    cameras: Query<&LookTransform>,
+    mut patches: Query<(&Aabb, &LodRange, &mut Visibility)>,
+) {
+    let Some(look) = cameras.iter().next() else {
+        return;
+    };
+
+    for (aabb, lod, mut visibility) in patches.iter_mut() {
+        let distance = look.eye.distance(aabb.center.into());
+        let wanted = if lod.contains(distance) {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use bevy::{prelude::*, render::primitives::Aabb, window::PrimaryWindow};
+use bevy_aabb_instancing::Cuboids;
+use bevy_egui::EguiContexts;
+use smooth_bevy_cameras::controllers::orbit::OrbitCameraController;
+
+use crate::{
+    axes::SceneAxes,
+    block_model::{BlockLayer, BlockRows},
+    patching::CoarseLod,
+};
+
+/// Cursor travel (in logical pixels) between press and release above which a click is
+/// treated as an orbit drag rather than a pick.
+const CLICK_TOLERANCE: f32 = 4.0;
+
+/// A selected block, with its extent in model coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PickedBlock {
+    pub layer: BlockLayer,
+    pub row: usize,
+    pub minimum: Vec3,
+    pub maximum: Vec3,
+}
+
+/// A click in the 3D view that was not an orbit drag, as a world space ray. Sent for
+/// pickable scene content other than blocks.
+#[derive(Event, Clone, Debug)]
+pub struct ClickRay {
+    pub ray: Ray,
+    pub append: bool,
+    /// The nearest block hit and its parameter along `ray`, already applied to the
+    /// `Selection`. Content hit nearer than it takes the click back with `Selection::click`.
+    pub block: Option<(f32, PickedBlock)>,
+}
+
+#[derive(Resource, Default)]
+pub struct Selection {
+    pub picks: Vec<PickedBlock>,
+}
+
+impl Selection {
+    /// Selects `pick`, or toggles it when appending. Toggling again undoes an append.
+    pub fn click(&mut self, pick: PickedBlock, append: bool) {
+        if !append {
+            self.picks.clear();
+            self.picks.push(pick);
+        } else if let Some(i) = self.picks.iter().position(|p| *p == pick) {
+            //shift-clicking a selected block deselects it
+            self.picks.remove(i);
+        } else {
+            self.picks.push(pick);
+        }
+    }
+}
+
+/// Slab test of a ray against an axis aligned box, returning the entry distance along the ray.
+pub fn ray_box_intersection(ray: &Ray, minimum: Vec3, maximum: Vec3) -> Option<f32> {
+    let inv_dir = ray.direction.recip();
+    let t0 = (minimum - ray.origin) * inv_dir;
+    let t1 = (maximum - ray.origin) * inv_dir;
+
+    let t_near = t0.min(t1).max_element();
+    let t_far = t0.max(t1).min_element();
+
+    if t_near > t_far || t_far < 0.0 {
+        return None;
+    }
+    Some(t_near.max(0.0))
+}
+
+/// Casts `ray` against every rendered patch, using the patch `Aabb` as a broad phase.
+pub fn cast_ray<'a>(
+    ray: &Ray,
+    patches: impl Iterator<Item = (&'a Cuboids, &'a Aabb, &'a BlockRows, &'a BlockLayer)>,
+) -> Option<(f32, PickedBlock)> {
+    let mut closest: Option<(f32, PickedBlock)> = None;
+
+    for (cuboids, aabb, rows, layer) in patches {
+        let patch_t = match ray_box_intersection(ray, aabb.min().into(), aabb.max().into()) {
+            Some(t) => t,
+            None => continue,
+        };
+        if let Some((best, _)) = &closest {
+            if patch_t > *best {
+                continue;
+            }
+        }
+
+        for (cuboid, row) in cuboids.instances.iter().zip(rows.0.iter()) {
+            if let Some(t) = ray_box_intersection(ray, cuboid.minimum, cuboid.maximum) {
+                if closest.as_ref().map_or(true, |(best, _)| t < *best) {
+                    closest = Some((
+                        t,
+                        PickedBlock {
+                            layer: layer.clone(),
+                            row: *row,
+                            minimum: cuboid.minimum,
+                            maximum: cuboid.maximum,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+
+    closest
+}
+
+pub fn pick_blocks(
+    mut contexts: EguiContexts,
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<OrbitCameraController>>,
+    patches: Query<(&Cuboids, &Aabb, &BlockRows, &BlockLayer), Without<CoarseLod>>,
+    axes: Res<SceneAxes>,
+    mut selection: ResMut<Selection>,
+    mut click_rays: EventWriter<ClickRay>,
+    mut press_position: Local<Option<Vec2>>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+
+    if mouse.just_pressed(MouseButton::Left) {
+        *press_position = if contexts.ctx_mut().wants_pointer_input() {
+            None
+        } else {
+            Some(cursor)
+        };
+        return;
+    }
+
+    if !mouse.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some(pressed_at) = press_position.take() else {
+        return;
+    };
+    if pressed_at.distance(cursor) > CLICK_TOLERANCE {
+        return;
+    }
+
+    let Some((camera, camera_transform)) = cameras.iter().next() else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+
+    let append = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    //fine patches hold every drawn block with its own row, so they are picked even when
+    //their coarse stand-in is the one shown
+    let hit = cast_ray(&ray, patches.iter());
+
+    let hit = hit.map(|(t, mut pick)| {
+        (pick.minimum, pick.maximum) = axes.box_to_model(pick.minimum, pick.maximum);
+        (t, pick)
+    });
+    match &hit {
+        Some((_, pick)) => selection.click(pick.clone(), append),
+        None => {
+            if !append {
+                selection.picks.clear();
+            }
+        }
+    }
+    click_rays.send(ClickRay {
+        ray,
+        append,
+        block: hit,
+    });
+}
+
+pub fn highlight_selection(selection: Res<Selection>, axes: Res<SceneAxes>, mut gizmos: Gizmos) {
+    for pick in selection.picks.iter() {
+        //slightly oversized so the outline is not hidden by the block's own faces
+        let margin = (pick.maximum - pick.minimum) * 0.01;
+        gizmos.cuboid(
+            axes.box_transform(pick.minimum - margin, pick.maximum + margin),
+            Color::WHITE,
+        );
+    }
+}
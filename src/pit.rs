@@ -0,0 +1,421 @@
+use std::collections::VecDeque;
+
+use bevy::{math::Vec3, utils::HashMap};
+use polars::prelude::{NamedFrom, Series};
+
+use crate::{
+    block::BlockIndex, block_model::BlockModel, economics::EconomicModel, jobs::JobControl,
+    optimizer::OptimizeParams, spatial_index::BlockGrid,
+};
+
+/// Residual capacities below this are treated as saturated.
+const EPSILON: f64 = 1e-9;
+const NONE: u32 = u32::MAX;
+
+/// Residual graph for the maximum closure problem, solved as a minimum cut with
+/// Dinic's algorithm. Edges are stored in pairs, `e ^ 1` being the reverse of `e`.
+struct FlowGraph {
+    head: Vec<u32>,
+    next: Vec<u32>,
+    to: Vec<u32>,
+    capacity: Vec<f64>,
+}
+
+impl FlowGraph {
+    fn new(nodes: usize) -> Self {
+        Self {
+            head: vec![NONE; nodes],
+            next: Vec::new(),
+            to: Vec::new(),
+            capacity: Vec::new(),
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: f64) {
+        for (a, b, c) in [(from, to, capacity), (to, from, 0.0)] {
+            self.next.push(self.head[a]);
+            self.head[a] = self.to.len() as u32;
+            self.to.push(b as u32);
+            self.capacity.push(c);
+        }
+    }
+
+    /// BFS distances from `source` over unsaturated edges.
+    fn levels(&self, source: usize) -> Vec<u32> {
+        let mut level = vec![NONE; self.head.len()];
+        let mut queue = VecDeque::from([source]);
+        level[source] = 0;
+        while let Some(v) = queue.pop_front() {
+            let mut e = self.head[v];
+            while e != NONE {
+                let w = self.to[e as usize] as usize;
+                if level[w] == NONE && self.capacity[e as usize] > EPSILON {
+                    level[w] = level[v] + 1;
+                    queue.push_back(w);
+                }
+                e = self.next[e as usize];
+            }
+        }
+        level
+    }
+
+    /// Saturates every source to sink path, leaving the maximum closure as the nodes
+    /// still reachable from the source. Each phase reports the positive value less the flow
+    /// so far, an upper bound on the closure value that falls to it, and stops if cancelled.
+    fn max_flow(&mut self, source: usize, sink: usize, control: &JobControl) -> Result<(), String> {
+        let mut bound = {
+            let mut e = self.head[source];
+            let mut total = 0.0;
+            while e != NONE {
+                total += self.capacity[e as usize];
+                e = self.next[e as usize];
+            }
+            total
+        };
+        let (mut phase, mut augmentations) = (0, 0usize);
+        loop {
+            phase += 1;
+            control.report("Max flow", phase, Some(bound));
+            let level = self.levels(source);
+            if level[sink] == NONE {
+                return Ok(());
+            }
+            let mut current = self.head.clone();
+            //edges of the path being extended from the source
+            let mut path: Vec<u32> = Vec::new();
+            loop {
+                let v = path
+                    .last()
+                    .map_or(source, |e| self.to[*e as usize] as usize);
+                if v == sink {
+                    let flow = path
+                        .iter()
+                        .map(|e| self.capacity[*e as usize])
+                        .fold(f64::INFINITY, f64::min);
+                    for e in path.iter() {
+                        self.capacity[*e as usize] -= flow;
+                        self.capacity[(*e ^ 1) as usize] += flow;
+                    }
+                    bound -= flow;
+                    augmentations += 1;
+                    if augmentations % 4096 == 0 {
+                        control.check()?;
+                    }
+                    //retreat to the tail of the first saturated edge
+                    let saturated = path
+                        .iter()
+                        .position(|e| self.capacity[*e as usize] <= EPSILON)
+                        .unwrap_or(0);
+                    path.truncate(saturated);
+                    continue;
+                }
+
+                let mut e = current[v];
+                while e != NONE {
+                    let w = self.to[e as usize] as usize;
+                    if self.capacity[e as usize] > EPSILON && level[w] == level[v] + 1 {
+                        break;
+                    }
+                    e = self.next[e as usize];
+                }
+                current[v] = e;
+                if e != NONE {
+                    path.push(e);
+                } else if let Some(back) = path.pop() {
+                    //dead end: skip the edge leading here from now on
+                    let u = self.to[(back ^ 1) as usize] as usize;
+                    current[u] = self.next[back as usize];
+                } else {
+                    break;
+                }
+            }
+            control.check()?;
+        }
+    }
+
+    fn reachable(&self, source: usize) -> Vec<bool> {
+        self.levels(source).into_iter().map(|l| l != NONE).collect()
+    }
+}
+
+/// Totals over the blocks inside an ultimate pit.
+#[derive(Clone, Debug, Default)]
+pub struct PitSummary {
+    pub blocks: usize,
+    pub ore_tonnes: f64,
+    pub waste_tonnes: f64,
+    pub metal: f64,
+    pub value: f64,
+}
+
+impl PitSummary {
+    pub fn strip_ratio(&self) -> f64 {
+        if self.ore_tonnes > 0.0 {
+            self.waste_tonnes / self.ore_tonnes
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Horizontal cell offsets one level up that a block depends on for `slope_angle`
+/// (degrees from horizontal): those whose centres are within the cone through the block
+/// centre. The block directly above is always included.
+pub(crate) fn precedence_pattern(cell: Vec3, slope_angle: f32) -> Vec<(i32, i32)> {
+    let radius = cell.z / slope_angle.clamp(1.0, 89.0).to_radians().tan();
+    let reach = (
+        (radius / cell.x).floor() as i32,
+        (radius / cell.y).floor() as i32,
+    );
+    let mut pattern = Vec::new();
+    for di in -reach.0..=reach.0 {
+        for dj in -reach.1..=reach.1 {
+            let (dx, dy) = (di as f32 * cell.x, dj as f32 * cell.y);
+            if (dx * dx + dy * dy).sqrt() <= radius + 1e-3 {
+                pattern.push((di, dj));
+            }
+        }
+    }
+    if pattern.is_empty() {
+        pattern.push((0, 0));
+    }
+    pattern
+}
+
+/// Occupied cells of a block grid, numbered as graph nodes, with the cells each depends on.
+pub(crate) struct Precedence {
+    pub cells: Vec<BlockIndex>,
+    /// Per cell, those that must be mined before it: for each offset of the slope pattern,
+    /// the first occupied cell above, so precedence carries on through empty (air) cells.
+    pub above: Vec<Vec<usize>>,
+}
+
+impl Precedence {
+    pub fn new(
+        cells: Vec<BlockIndex>,
+        dims: &BlockIndex,
+        pattern: &[(i32, i32)],
+        control: &JobControl,
+    ) -> Result<Self, String> {
+        let node_of = cells
+            .iter()
+            .enumerate()
+            .map(|(n, ind)| (*ind, n))
+            .collect::<HashMap<BlockIndex, usize>>();
+        let mut above = Vec::with_capacity(cells.len());
+        for (n, ind) in cells.iter().enumerate() {
+            if n % 65536 == 0 {
+                control.report("Building precedence graph", n, None);
+                control.check()?;
+            }
+            let mut nodes = Vec::with_capacity(pattern.len());
+            for (di, dj) in pattern.iter() {
+                let (i, j) = (ind.i as i64 + *di as i64, ind.j as i64 + *dj as i64);
+                if i < 0 || j < 0 || i >= dims.i as i64 || j >= dims.j as i64 {
+                    continue;
+                }
+                let first = (ind.k + 1..dims.k).find_map(|k| {
+                    node_of
+                        .get(&BlockIndex {
+                            i: i as usize,
+                            j: j as usize,
+                            k,
+                        })
+                        .copied()
+                });
+                nodes.extend(first);
+            }
+            above.push(nodes);
+        }
+        Ok(Self { cells, above })
+    }
+}
+
+/// The set of cells of maximum total `values` in which every cell has those it depends
+/// on too, as a flag per cell.
+fn max_closure(
+    values: &[f64],
+    precedence: &Precedence,
+    control: &JobControl,
+) -> Result<Vec<bool>, String> {
+    let (source, sink) = (values.len(), values.len() + 1);
+    let mut graph = FlowGraph::new(values.len() + 2);
+    for (n, value) in values.iter().enumerate() {
+        if *value > 0.0 {
+            graph.add_edge(source, n, *value);
+        } else if *value < 0.0 {
+            graph.add_edge(n, sink, -value);
+        }
+        for above in precedence.above[n].iter() {
+            graph.add_edge(n, *above, f64::INFINITY);
+        }
+    }
+
+    graph.max_flow(source, sink, control)?;
+    let mut reachable = graph.reachable(source);
+    reachable.truncate(values.len());
+    Ok(reachable)
+}
+
+/// Ultimate pit over the cells of `grid`: the set of blocks of maximum total value in
+/// which every block has the blocks in its slope pattern above it mined too.
+///
+/// Blocks larger than a grid cell spread their value over their cells and are in the pit
+/// if any of their cells are. Where a cell in the pattern is empty (air), the precedence
+/// carries on to the first occupied cell above it. Returns the in-pit flag column and the
+/// pit totals.
+pub fn ultimate_pit(
+    bm: &BlockModel,
+    grid: &BlockGrid,
+    params: &OptimizeParams,
+    name: &str,
+    control: &JobControl,
+) -> Result<(Series, PitSummary), String> {
+    let economics = EconomicModel::new(bm, params)?;
+
+    let (cells, rows): (Vec<_>, Vec<_>) = grid.iter().map(|(ind, row)| (*ind, *row)).unzip();
+    let mut cells_per_row = vec![0usize; bm.df.height()];
+    for row in rows.iter() {
+        cells_per_row[*row] += 1;
+    }
+    let values = rows
+        .iter()
+        .map(|row| economics.values[*row].value / cells_per_row[*row] as f64)
+        .collect::<Vec<_>>();
+
+    let pattern = precedence_pattern(grid.cell, params.slope_angle);
+    let precedence = Precedence::new(cells, &grid.dims, &pattern, control)?;
+    let closure = max_closure(&values, &precedence, control)?;
+
+    let mut in_pit = vec![false; bm.df.height()];
+    for (row, in_closure) in rows.iter().zip(closure) {
+        in_pit[*row] |= in_closure;
+    }
+    let [ore, waste] = economics.totals(params, |row| in_pit[row]);
+    let summary = PitSummary {
+        blocks: ore.blocks + waste.blocks,
+        ore_tonnes: ore.tonnes,
+        waste_tonnes: waste.tonnes,
+        metal: ore.metal,
+        value: ore.value + waste.value,
+    };
+
+    Ok((Series::new(name, in_pit), summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One section of `values[k][i]`, listed top bench first, at 45 degrees on unit cells.
+    fn section(values: &[&[f64]]) -> (Precedence, Vec<f64>) {
+        let benches = values.len();
+        let dims = BlockIndex {
+            i: values[0].len(),
+            j: 1,
+            k: benches,
+        };
+        let (mut cells, mut flat) = (Vec::new(), Vec::new());
+        for (level, bench) in values.iter().enumerate() {
+            for (i, value) in bench.iter().enumerate() {
+                cells.push(BlockIndex {
+                    i,
+                    j: 0,
+                    k: benches - 1 - level,
+                });
+                flat.push(*value);
+            }
+        }
+        let pattern = precedence_pattern(Vec3::ONE, 45.0);
+        let control = JobControl::default();
+        (
+            Precedence::new(cells, &dims, &pattern, &control).unwrap(),
+            flat,
+        )
+    }
+
+    fn solve(values: &[&[f64]]) -> (Precedence, Vec<f64>, Vec<bool>) {
+        let (precedence, flat) = section(values);
+        let closure = max_closure(&flat, &precedence, &JobControl::default()).unwrap();
+        for (n, mined) in closure.iter().enumerate() {
+            if *mined {
+                assert!(
+                    precedence.above[n].iter().all(|above| closure[*above]),
+                    "{:?} mined without the blocks above it",
+                    precedence.cells[n]
+                );
+            }
+        }
+        (precedence, flat, closure)
+    }
+
+    fn mined(precedence: &Precedence, closure: &[bool]) -> Vec<(usize, usize)> {
+        let mut mined = precedence
+            .cells
+            .iter()
+            .zip(closure)
+            .filter(|(_, mined)| **mined)
+            .map(|(ind, _)| (ind.i, ind.k))
+            .collect::<Vec<_>>();
+        mined.sort();
+        mined
+    }
+
+    #[test]
+    fn pattern_at_45_degrees_is_the_block_above_and_its_neighbours() {
+        let mut pattern = precedence_pattern(Vec3::ONE, 45.0);
+        pattern.sort();
+        assert_eq!(pattern, vec![(-1, 0), (0, -1), (0, 0), (0, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn three_bench_section() {
+        let (precedence, values, closure) = solve(&[
+            &[-1.0, -1.0, -1.0, -1.0, -1.0],
+            &[-2.0, -2.0, 4.0, -2.0, -2.0],
+            &[-3.0, -3.0, 8.0, -3.0, -3.0],
+        ]);
+        //the whole cone over the bottom ore block: 8 + 4 - 2 - 2 - 5 = 3
+        let expected = vec![
+            (0, 2),
+            (1, 1),
+            (1, 2),
+            (2, 0),
+            (2, 1),
+            (2, 2),
+            (3, 1),
+            (3, 2),
+            (4, 2),
+        ];
+        assert_eq!(mined(&precedence, &closure), expected);
+        let value: f64 = values
+            .iter()
+            .zip(&closure)
+            .filter(|(_, mined)| **mined)
+            .map(|(value, _)| value)
+            .sum();
+        assert_eq!(value, 3.0);
+    }
+
+    #[test]
+    fn ore_not_worth_its_overburden_is_left() {
+        let (precedence, _, closure) = solve(&[
+            &[3.0, -1.0, -1.0, -1.0, -1.0],
+            &[-2.0, -2.0, -2.0, -2.0, -2.0],
+            &[-3.0, -3.0, 5.0, -3.0, -3.0],
+        ]);
+        //the centre block would take 5 - 6 - 4 = -5 with it
+        assert_eq!(mined(&precedence, &closure), vec![(0, 2)]);
+    }
+
+    #[test]
+    fn precedence_skips_air() {
+        let dims = BlockIndex { i: 1, j: 1, k: 3 };
+        let cells = vec![
+            BlockIndex { i: 0, j: 0, k: 0 },
+            BlockIndex { i: 0, j: 0, k: 2 },
+        ];
+        let precedence = Precedence::new(cells, &dims, &[(0, 0)], &JobControl::default()).unwrap();
+        assert_eq!(precedence.above, vec![vec![1], vec![]]);
+    }
+}
@@ -0,0 +1,340 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use bevy::prelude::*;
+use itertools::izip;
+
+use crate::{
+    axes::SceneAxes,
+    block_model::{BlockLayer, BlockModel, BlockModelDB},
+    economics::{Destination, EconomicModel},
+    export::{ExportRequest, ImageExport},
+    layers::{LayerEvent, LayerStyles},
+    optimizer::OptimizeParams,
+    ui::OpenPanels,
+};
+
+/// Blocks of the current period outlined one by one; larger periods get a single box.
+const MAX_OUTLINES: usize = 2000;
+/// Frames between respawning the period layer and exporting it: one for `update_layers`
+/// to take the event and one for the new patches to be drawn.
+const SETTLE_FRAMES: usize = 2;
+
+/// What is mined in one period of a schedule.
+#[derive(Clone, Debug, Default)]
+pub struct PeriodSummary {
+    pub period: i64,
+    pub tonnes: f64,
+    pub ore_tonnes: f64,
+    /// Mean grade of the ore.
+    pub grade: f64,
+    pub value: f64,
+    /// NPV of this and all earlier periods.
+    pub cumulative_npv: f64,
+}
+
+/// Totals per period of `column`, rounded to whole periods in ascending order. Ore is
+/// what the economics in `params` send to the process; each period is taken as a year
+/// when discounting.
+pub fn period_summaries(
+    bm: &BlockModel,
+    params: &OptimizeParams,
+    column: &str,
+) -> Result<Vec<PeriodSummary>, String> {
+    let periods = bm
+        .column_f32(column)
+        .ok_or_else(|| format!("Period column {} is not numeric", column))?;
+    let economics = EconomicModel::new(bm, params)?;
+
+    //metal is kept in `grade` until the totals are complete
+    let mut by_period: BTreeMap<i64, PeriodSummary> = BTreeMap::new();
+    for (row, period) in periods.into_iter().enumerate() {
+        let Some(period) = period else {
+            continue;
+        };
+        let period = period.round() as i64;
+        let summary = by_period.entry(period).or_insert_with(|| PeriodSummary {
+            period,
+            ..default()
+        });
+        let (tonnes, block) = (economics.tonnes[row], &economics.values[row]);
+        summary.tonnes += tonnes;
+        summary.value += block.value;
+        if block.destination == Destination::Process {
+            summary.ore_tonnes += tonnes;
+            summary.grade += tonnes * economics.grades[row];
+        }
+    }
+    if by_period.is_empty() {
+        return Err(format!("No blocks have a period in {}", column));
+    }
+
+    let discount = 1.0 + params.discount_rate as f64;
+    let mut npv = 0.0;
+    Ok(by_period
+        .into_values()
+        .enumerate()
+        .map(|(year, mut summary)| {
+            if summary.ore_tonnes > 0.0 {
+                summary.grade /= summary.ore_tonnes;
+            }
+            npv += summary.value / discount.powi(year as i32 + 1);
+            summary.cumulative_npv = npv;
+            summary
+        })
+        .collect())
+}
+
+/// A numbered PNG sequence being written, one frame per period.
+struct Recording {
+    template: ExportRequest,
+    dir: PathBuf,
+    frame: usize,
+    /// The current period's frame has been handed to the image export.
+    requested: bool,
+    /// Frames left before the respawned layer can be exported.
+    settling: usize,
+}
+
+/// Steps a period column through time: earlier periods ghosted or hidden, the current
+/// one outlined.
+#[derive(Resource)]
+pub struct Playback {
+    pub grid: String,
+    pub column: String,
+    pub summaries: Vec<PeriodSummary>,
+    /// Index into `summaries` of the period shown.
+    pub current: usize,
+    pub playing: bool,
+    /// Periods advanced per second while playing.
+    pub speed: f32,
+    /// Opacity of already mined blocks relative to the layer's, `0` hiding them.
+    pub ghost: f32,
+    pub highlight: bool,
+    /// Set when the layer needs rebuilding for the current period.
+    pub dirty: bool,
+    pub message: Option<String>,
+    elapsed: f32,
+    recording: Option<Recording>,
+    /// Layer whose style playback has ghosted, to be restored when playback stops.
+    ghosted: Option<BlockLayer>,
+    /// Model space boxes of the current period's blocks.
+    outlines: Vec<(Vec3, Vec3)>,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            grid: String::new(),
+            column: String::new(),
+            summaries: Vec::new(),
+            current: 0,
+            playing: false,
+            speed: 1.0,
+            ghost: 0.0,
+            highlight: true,
+            dirty: false,
+            message: None,
+            elapsed: 0.0,
+            recording: None,
+            ghosted: None,
+            outlines: Vec::new(),
+        }
+    }
+}
+
+impl Playback {
+    pub fn layer(&self) -> BlockLayer {
+        BlockLayer {
+            grid: self.grid.clone(),
+            column: self.column.clone(),
+        }
+    }
+
+    /// Shows the period at `index`, clamped to the schedule.
+    pub fn show(&mut self, index: usize) {
+        self.current = index.min(self.summaries.len().saturating_sub(1));
+        self.elapsed = 0.0;
+        self.dirty = true;
+    }
+
+    /// Writes one image per period, from the first, to `dir` with the size and overlays
+    /// of `template`.
+    pub fn record(&mut self, dir: PathBuf, template: ExportRequest) {
+        self.playing = false;
+        self.message = None;
+        self.recording = Some(Recording {
+            template,
+            dir,
+            frame: 0,
+            requested: false,
+            settling: 0,
+        });
+        self.show(0);
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            self.message = Some(format!("Stopped after {} frames", recording.frame));
+        }
+    }
+}
+
+/// Boxes of the blocks in `period` of `column`, or their bounding box if there are many.
+fn period_outlines(bm: &BlockModel, column: &str, period: i64) -> Vec<(Vec3, Vec3)> {
+    let columns = [
+        bm.x.as_str(),
+        bm.y.as_str(),
+        bm.z.as_str(),
+        bm.x_size.as_str(),
+        bm.y_size.as_str(),
+        bm.z_size.as_str(),
+        column,
+    ]
+    .map(|c| bm.column_f32(c));
+    let [Some(x), Some(y), Some(z), Some(xs), Some(ys), Some(zs), Some(periods)] = columns else {
+        return Vec::new();
+    };
+    let mut boxes = Vec::new();
+    for (x, y, z, xs, ys, zs, p) in izip!(&x, &y, &z, &xs, &ys, &zs, &periods) {
+        if let (Some(x), Some(y), Some(z), Some(xs), Some(ys), Some(zs), Some(p)) =
+            (x, y, z, xs, ys, zs, p)
+        {
+            if p.round() as i64 == period {
+                let minimum = Vec3::new(x, y, z);
+                boxes.push((minimum, minimum + Vec3::new(xs, ys, zs)));
+            }
+        }
+    }
+    if boxes.len() > MAX_OUTLINES {
+        let bounds = boxes.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), (a, b)| (min.min(*a), max.max(*b)),
+        );
+        boxes = vec![bounds];
+    }
+    boxes
+}
+
+/// Advances playback, rebuilds the period layer when the period changes and feeds frames
+/// to the image export while recording. Once the playback window is closed or its
+/// schedule unloaded, the layer is drawn without ghosting again.
+pub fn run_playback(
+    time: Res<Time>,
+    mut playback: ResMut<Playback>,
+    block_models: Res<BlockModelDB>,
+    open_panels: Res<OpenPanels>,
+    spawned: Query<&BlockLayer>,
+    mut styles: ResMut<LayerStyles>,
+    mut export: ResMut<ImageExport>,
+    mut layer_events: EventWriter<LayerEvent>,
+) {
+    let playback = &mut *playback;
+    let periods = playback.summaries.len();
+    let active = (open_panels.playback && periods > 0).then(|| playback.layer());
+    if playback.ghosted != active {
+        if let Some(layer) = playback.ghosted.take() {
+            if let Some(style) = styles.styles.get_mut(&layer) {
+                style.ghost = None;
+            }
+            //only redraw the layer if it is still shown
+            if spawned.iter().any(|l| *l == layer) {
+                layer_events.send(LayerEvent::Spawn(layer));
+            }
+        }
+        playback.dirty = active.is_some();
+    }
+    if active.is_none() {
+        playback.playing = false;
+        playback.stop_recording();
+        playback.outlines.clear();
+        return;
+    }
+
+    if playback.playing && playback.recording.is_none() {
+        playback.elapsed += time.delta_seconds() * playback.speed;
+        if playback.elapsed >= 1.0 {
+            if playback.current + 1 < periods {
+                playback.show(playback.current + 1);
+            } else {
+                playback.playing = false;
+            }
+        }
+    }
+
+    //move on once the export has written the current frame
+    let mut finished = false;
+    if let Some(recording) = &mut playback.recording {
+        if recording.requested && !export.is_busy() {
+            recording.requested = false;
+            recording.frame += 1;
+            if playback.current + 1 < periods {
+                playback.current += 1;
+                playback.dirty = true;
+            } else {
+                finished = true;
+            }
+        }
+    }
+    if finished {
+        if let Some(recording) = playback.recording.take() {
+            playback.message = Some(format!(
+                "{} frames written to {}",
+                recording.frame,
+                recording.dir.display()
+            ));
+        }
+    }
+
+    if playback.dirty {
+        playback.dirty = false;
+        let period = playback.summaries[playback.current].period;
+        let layer = playback.layer();
+        let style = styles.styles.entry(layer.clone()).or_default();
+        style.ghost = Some((period as f64 - 0.5, playback.ghost));
+        playback.ghosted = Some(layer.clone());
+        playback.outlines = block_models
+            .block_models
+            .get(&playback.grid)
+            .map_or(Vec::new(), |bm| {
+                period_outlines(bm, &playback.column, period)
+            });
+        layer_events.send(LayerEvent::Spawn(layer));
+        if let Some(recording) = &mut playback.recording {
+            recording.settling = SETTLE_FRAMES;
+        }
+    }
+
+    if let Some(recording) = &mut playback.recording {
+        if recording.settling > 0 {
+            recording.settling -= 1;
+        } else if !recording.requested && !export.is_busy() {
+            let path = recording
+                .dir
+                .join(format!("frame_{:04}.png", recording.frame + 1));
+            export.request(ExportRequest {
+                path,
+                ..recording.template.clone()
+            });
+            recording.requested = true;
+        }
+    }
+}
+
+/// Outlines the blocks of the period being shown.
+pub fn highlight_period(playback: Res<Playback>, axes: Res<SceneAxes>, mut gizmos: Gizmos) {
+    if !playback.highlight || playback.summaries.is_empty() {
+        return;
+    }
+    for (minimum, maximum) in playback.outlines.iter() {
+        //slightly oversized so the outline is not hidden by the block's own faces
+        let margin = (*maximum - *minimum) * 0.01;
+        gizmos.cuboid(
+            axes.box_transform(*minimum - margin, *maximum + margin),
+            Color::WHITE,
+        );
+    }
+}
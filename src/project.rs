@@ -0,0 +1,41 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    axes::SceneAxes,
+    camera::{CameraBookmark, ProjectionSettings},
+};
+
+/// View state saved as a project file, as JSON. Block models and other data are
+/// loaded on their own.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Project {
+    pub axes: SceneAxes,
+    pub projection: ProjectionSettings,
+    #[serde(default)]
+    pub bookmarks: Vec<CameraBookmark>,
+}
+
+impl Project {
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+}
+
+/// The project file last opened or saved, and why the last attempt failed.
+#[derive(Resource, Default)]
+pub struct ProjectFile {
+    pub path: Option<PathBuf>,
+    pub error: Option<String>,
+}
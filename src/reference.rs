@@ -0,0 +1,228 @@
+use bevy::{prelude::*, render::primitives::Aabb};
+use bevy_aabb_instancing::Cuboids;
+use bevy_egui::{egui, EguiContexts};
+use smooth_bevy_cameras::{controllers::orbit::OrbitCameraController, LookTransform};
+
+use crate::{axes::SceneAxes, camera::visible_bounds, ui::OccupiedScreenSpace};
+
+/// Approximate number of grid intervals along each side of the bounding box.
+const GRID_TICKS: f32 = 6.0;
+/// Length of the triad axes, in logical pixels.
+const TRIAD_LENGTH: f32 = 40.0;
+/// Preferred length of the scale bar, in logical pixels.
+const SCALE_BAR_LENGTH: f32 = 120.0;
+/// Distance of the corner overlays from the edge of the 3D view.
+const MARGIN: f32 = 20.0;
+
+/// Which parts of the spatial reference frame are drawn.
+#[derive(Resource)]
+pub struct ReferenceFrame {
+    pub triad: bool,
+    pub grid: bool,
+    pub north_arrow: bool,
+    pub scale_bar: bool,
+}
+
+impl Default for ReferenceFrame {
+    fn default() -> Self {
+        Self {
+            triad: true,
+            grid: false,
+            north_arrow: true,
+            scale_bar: true,
+        }
+    }
+}
+
+/// Largest step of the form 1, 2 or 5 × 10ⁿ not exceeding `step`.
+pub fn nice_step(step: f32) -> f32 {
+    if step <= 0.0 || !step.is_finite() {
+        return 1.0;
+    }
+    let magnitude = 10f32.powf(step.log10().floor());
+    let mantissa = step / magnitude;
+    let nice = if mantissa >= 5.0 {
+        5.0
+    } else if mantissa >= 2.0 {
+        2.0
+    } else {
+        1.0
+    };
+    nice * magnitude
+}
+
+/// Multiples of `step` within `min..=max`.
+fn ticks(min: f32, max: f32, step: f32) -> impl Iterator<Item = f32> {
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+    (first..=last).map(move |i| i as f32 * step)
+}
+
+/// Draws the orientation triad, bounding box grid, north arrow and scale bar.
+pub fn draw_reference_frame(
+    mut contexts: EguiContexts,
+    mut gizmos: Gizmos,
+    frame: Res<ReferenceFrame>,
+    axes: Res<SceneAxes>,
+    occupied_screen_space: Res<OccupiedScreenSpace>,
+    cameras: Query<
+        (&Camera, &GlobalTransform, &Projection, &LookTransform),
+        With<OrbitCameraController>,
+    >,
+    bounding_boxes: Query<(&Aabb, &Visibility), With<Cuboids>>,
+) {
+    let Ok((camera, camera_transform, projection, look)) = cameras.get_single() else {
+        return;
+    };
+    let ctx = contexts.ctx_mut();
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Background,
+        egui::Id::new("reference_frame"),
+    ));
+    let screen = ctx.screen_rect();
+    let view = egui::Rect::from_min_max(
+        egui::pos2(
+            screen.left() + occupied_screen_space.left,
+            screen.top() + occupied_screen_space.top,
+        ),
+        egui::pos2(
+            screen.right() - occupied_screen_space.right,
+            screen.bottom() - occupied_screen_space.bottom,
+        ),
+    );
+    let text_color = egui::Color32::from_gray(230);
+    let font = egui::FontId::proportional(12.0);
+
+    //world direction to screen direction (y down)
+    let right = camera_transform.right();
+    let up = camera_transform.up();
+    let to_screen = |world: Vec3| egui::vec2(world.dot(right), -world.dot(up));
+
+    if frame.triad {
+        let origin = egui::pos2(
+            view.left() + MARGIN + TRIAD_LENGTH,
+            view.bottom() - MARGIN - TRIAD_LENGTH,
+        );
+        for (axis, label, color) in [
+            (Vec3::X, "E", egui::Color32::from_rgb(230, 60, 60)),
+            (Vec3::Y, "N", egui::Color32::from_rgb(60, 200, 60)),
+            (Vec3::Z, "Z", egui::Color32::from_rgb(70, 120, 240)),
+        ] {
+            let tip = origin + to_screen(axes.to_world(axis).normalize()) * TRIAD_LENGTH;
+            painter.line_segment([origin, tip], egui::Stroke::new(2.0, color));
+            painter.text(tip, egui::Align2::CENTER_CENTER, label, font.clone(), color);
+        }
+    }
+
+    if frame.north_arrow {
+        let centre = egui::pos2(view.right() - MARGIN - 20.0, view.top() + MARGIN + 30.0);
+        let north = to_screen(axes.to_world(Vec3::Y).normalize());
+        //looking along north the arrow has no meaningful direction
+        if north.length() > 0.1 {
+            let dir = north.normalized();
+            let side = egui::vec2(-dir.y, dir.x);
+            let tip = centre + dir * 20.0;
+            let tail = centre - dir * 20.0;
+            painter.add(egui::Shape::convex_polygon(
+                vec![
+                    tip,
+                    centre - dir * 8.0 + side * 8.0,
+                    centre - dir * 8.0 - side * 8.0,
+                ],
+                text_color,
+                egui::Stroke::NONE,
+            ));
+            painter.line_segment([centre, tail], egui::Stroke::new(2.0, text_color));
+            painter.text(
+                tip + dir * 10.0,
+                egui::Align2::CENTER_CENTER,
+                "N",
+                font.clone(),
+                text_color,
+            );
+        }
+    }
+
+    if frame.scale_bar && view.height() > 0.0 {
+        //world units per logical pixel at the target
+        let visible_height = match projection {
+            Projection::Perspective(p) => {
+                2.0 * look.eye.distance(look.target) * (p.fov / 2.0).tan()
+            }
+            Projection::Orthographic(o) => o.area.height(),
+        };
+        let units_per_pixel = visible_height / view.height();
+        let length = nice_step(SCALE_BAR_LENGTH * units_per_pixel);
+        let pixels = length / units_per_pixel;
+
+        let end = egui::pos2(view.right() - MARGIN, view.bottom() - MARGIN);
+        let start = end - egui::vec2(pixels, 0.0);
+        let stroke = egui::Stroke::new(2.0, text_color);
+        painter.line_segment([start, end], stroke);
+        painter.line_segment([start, start - egui::vec2(0.0, 6.0)], stroke);
+        painter.line_segment([end, end - egui::vec2(0.0, 6.0)], stroke);
+        painter.text(
+            start + egui::vec2(pixels / 2.0, -6.0),
+            egui::Align2::CENTER_BOTTOM,
+            format!("{} m", length),
+            font.clone(),
+            text_color,
+        );
+    }
+
+    if !frame.grid {
+        return;
+    }
+    let Some((world_min, world_max)) = visible_bounds(bounding_boxes.iter()) else {
+        return;
+    };
+    let (min, max) = axes.box_to_model(world_min, world_max);
+    let color = Color::rgba(0.8, 0.8, 0.8, 0.6);
+    gizmos.cuboid(axes.box_transform(min, max), color);
+
+    let size = max - min;
+    let label = |p: Vec3, text: String| {
+        if let Some(pos) = camera.world_to_viewport(camera_transform, axes.to_world(p)) {
+            painter.text(
+                egui::pos2(pos.x, pos.y),
+                egui::Align2::CENTER_TOP,
+                text,
+                font.clone(),
+                text_color,
+            );
+        }
+    };
+
+    //easting and northing grid on the base of the box, elevation ticks up one corner
+    let step = nice_step(size.x.max(size.y) / GRID_TICKS);
+    for x in ticks(min.x, max.x, step) {
+        gizmos.line(
+            axes.to_world(Vec3::new(x, min.y, min.z)),
+            axes.to_world(Vec3::new(x, max.y, min.z)),
+            color,
+        );
+        label(Vec3::new(x, min.y, min.z), format!("{:.0}E", x));
+    }
+    for y in ticks(min.y, max.y, step) {
+        gizmos.line(
+            axes.to_world(Vec3::new(min.x, y, min.z)),
+            axes.to_world(Vec3::new(max.x, y, min.z)),
+            color,
+        );
+        label(Vec3::new(min.x, y, min.z), format!("{:.0}N", y));
+    }
+
+    let step = nice_step(size.z / GRID_TICKS);
+    let tick = step.min(size.x.min(size.y) / 20.0);
+    for z in ticks(min.z, max.z, step) {
+        gizmos.line(
+            axes.to_world(Vec3::new(min.x, min.y, z)),
+            axes.to_world(Vec3::new(min.x - tick, min.y - tick, z)),
+            color,
+        );
+        label(
+            Vec3::new(min.x - tick, min.y - tick, z),
+            format!("{:.0} RL", z),
+        );
+    }
+}
@@ -0,0 +1,519 @@
+use std::collections::{BinaryHeap, HashMap};
+
+use bevy::math::Vec3;
+use ndarray::Array3;
+use ordered_float::OrderedFloat;
+
+use crate::{
+    block::BlockIndex,
+    economics::{BlockValue, Destination},
+    jobs::JobControl,
+    optimizer::OptimizeParams,
+    pit::{precedence_pattern, Precedence},
+};
+
+/// Schedule of the cells of a pit into periods (years) at the mining rate and processing
+/// capacity of `OptimizeParams`, under the same slope precedence as the pit optimiser.
+///
+/// Cells are mined in openings at least `mining_width` wide along both axes: each cell
+/// needs a window of that size on its bench, clear of rock left outside the pit, that is
+/// fully mined by its period.
+///
+/// Starts from a greedy schedule, each period taking the most valuable tiles of the
+/// mining width whose precedences are mined, and improves its NPV with sweeps that move
+/// cells to the neighbouring period, earlier for positive values and later for negative
+/// ones, where precedence, capacities and the mining width allow.
+pub struct Scheduler {
+    precedence: Precedence,
+    /// For each cell, those that have it in their precedence.
+    below: Vec<Vec<usize>>,
+    /// Mining width in cells along i and j.
+    width: (usize, usize),
+    /// For each cell, the windows of the mining width around it that can be mined out.
+    /// Cells without any are not held to the width.
+    windows: Vec<Vec<usize>>,
+    /// Cells of each window.
+    window_cells: Vec<Vec<usize>>,
+    tonnes: Vec<f64>,
+    /// Tonnes sent to the mill if the cell is mined.
+    processed: Vec<f64>,
+    values: Vec<f64>,
+    /// Period each cell is mined in, from 1.
+    periods: Vec<u32>,
+    /// Tonnes mined and processed in each period, period 1 first.
+    mined: Vec<f64>,
+    milled: Vec<f64>,
+    mining_rate: f64,
+    proc_cap: f64,
+    discount: f64,
+}
+
+impl Scheduler {
+    /// Schedules the cells with tonnage that `include` flags, valued at the prices, costs
+    /// and cutoff of `params`.
+    pub fn new(
+        tonnage: &Array3<f32>,
+        grade: &Array3<f32>,
+        include: &Array3<bool>,
+        cell: Vec3,
+        params: &OptimizeParams,
+        control: &JobControl,
+    ) -> Result<Self, String> {
+        if params.mining_rate <= 0.0 || params.proc_cap <= 0.0 {
+            return Err("The mining rate and processing capacity must be positive".into());
+        }
+        let mut cells = Vec::new();
+        let (mut tonnes, mut processed, mut values) = (Vec::new(), Vec::new(), Vec::new());
+        for ((i, j, k), t) in tonnage.indexed_iter() {
+            if !include[[i, j, k]] || *t <= 0.0 {
+                continue;
+            }
+            let t = *t as f64;
+            let block = BlockValue::new(t, grade[[i, j, k]] as f64, params);
+            cells.push(BlockIndex { i, j, k });
+            tonnes.push(t);
+            processed.push(if block.destination == Destination::Process {
+                t
+            } else {
+                0.0
+            });
+            values.push(block.value);
+        }
+        if cells.is_empty() {
+            return Err("No blocks to schedule".into());
+        }
+
+        let (i, j, k) = tonnage.dim();
+        let dims = BlockIndex { i, j, k };
+        let width = (
+            cells_across(params.mining_width, cell.x, dims.i),
+            cells_across(params.mining_width, cell.y, dims.j),
+        );
+        let (windows, window_cells) = if width == (1, 1) {
+            (vec![Vec::new(); cells.len()], Vec::new())
+        } else {
+            mining_windows(&cells, tonnage, include, width)
+        };
+
+        let pattern = precedence_pattern(cell, params.slope_angle);
+        let precedence = Precedence::new(cells, &dims, &pattern, control)?;
+        let mut below = vec![Vec::new(); values.len()];
+        for (n, above) in precedence.above.iter().enumerate() {
+            for a in above.iter() {
+                below[*a].push(n);
+            }
+        }
+
+        let mut scheduler = Self {
+            precedence,
+            below,
+            width,
+            windows,
+            window_cells,
+            periods: vec![0; values.len()],
+            tonnes,
+            processed,
+            values,
+            mined: Vec::new(),
+            milled: Vec::new(),
+            mining_rate: params.mining_rate as f64,
+            proc_cap: params.proc_cap as f64,
+            discount: 1.0 + params.discount_rate as f64,
+        };
+        scheduler.greedy(&dims, control)?;
+        Ok(scheduler)
+    }
+
+    fn fits(&self, cells: &[usize], period: usize) -> bool {
+        let tonnes = cells.iter().map(|n| self.tonnes[*n]).sum::<f64>();
+        let processed = cells.iter().map(|n| self.processed[*n]).sum::<f64>();
+        self.mined[period] + tonnes <= self.mining_rate
+            && self.milled[period] + processed <= self.proc_cap
+    }
+
+    fn place(&mut self, n: usize, period: usize) {
+        self.periods[n] = period as u32 + 1;
+        self.mined[period] += self.tonnes[n];
+        self.milled[period] += self.processed[n];
+    }
+
+    fn unplace(&mut self, n: usize) {
+        let period = self.periods[n] as usize - 1;
+        self.mined[period] -= self.tonnes[n];
+        self.milled[period] -= self.processed[n];
+    }
+
+    /// Fills one period after another with the most valuable tiles available, a tile
+    /// being the cells of an aligned window of the mining width on one bench, widened at
+    /// the far edges of the grid of `dims` to take the cells left over. A tile too large
+    /// for the capacities on its own gets a period to itself. Cells whose tile holds rock
+    /// outside the pit can end up in narrower openings, which sweeps then keep.
+    fn greedy(&mut self, dims: &BlockIndex, control: &JobControl) -> Result<(), String> {
+        let (wi, wj) = self.width;
+        let (last_i, last_j) = (dims.i / wi - 1, dims.j / wj - 1);
+        let mut tile_of = Vec::with_capacity(self.values.len());
+        let mut tiles: Vec<Vec<usize>> = Vec::new();
+        let mut tile_index = HashMap::new();
+        for (n, ind) in self.precedence.cells.iter().enumerate() {
+            let t = *tile_index
+                .entry(((ind.i / wi).min(last_i), (ind.j / wj).min(last_j), ind.k))
+                .or_insert_with(|| {
+                    tiles.push(Vec::new());
+                    tiles.len() - 1
+                });
+            tiles[t].push(n);
+            tile_of.push(t);
+        }
+        //precedence between tiles only points up a bench, so has no cycles
+        let mut below = vec![Vec::new(); tiles.len()];
+        let mut waiting = vec![0; tiles.len()];
+        for (t, cells) in tiles.iter().enumerate() {
+            let mut above = cells
+                .iter()
+                .flat_map(|n| self.precedence.above[*n].iter().map(|a| tile_of[*a]))
+                .collect::<Vec<_>>();
+            above.sort_unstable();
+            above.dedup();
+            waiting[t] = above.len();
+            for a in above {
+                below[a].push(t);
+            }
+        }
+        let values = tiles
+            .iter()
+            .map(|cells| OrderedFloat(cells.iter().map(|n| self.values[*n]).sum()))
+            .collect::<Vec<_>>();
+        let mut available = (0..tiles.len())
+            .filter(|t| waiting[*t] == 0)
+            .map(|t| (values[t], t))
+            .collect::<BinaryHeap<_>>();
+
+        let mut scheduled = 0;
+        while scheduled < tiles.len() {
+            control.check()?;
+            let period = self.mined.len();
+            self.mined.push(0.0);
+            self.milled.push(0.0);
+            let mut deferred = Vec::new();
+            while let Some((value, t)) = available.pop() {
+                if !self.fits(&tiles[t], period) && self.mined[period] > 0.0 {
+                    deferred.push((value, t));
+                    continue;
+                }
+                for n in tiles[t].iter() {
+                    self.place(*n, period);
+                }
+                scheduled += 1;
+                for b in below[t].iter() {
+                    waiting[*b] -= 1;
+                    if waiting[*b] == 0 {
+                        available.push((values[*b], *b));
+                    }
+                }
+            }
+            available.extend(deferred);
+        }
+        Ok(())
+    }
+
+    /// Whether some window of the mining width around `n` is mined out by its period.
+    fn has_width(&self, n: usize) -> bool {
+        let period = self.periods[n];
+        self.windows[n].is_empty()
+            || self.windows[n].iter().any(|w| {
+                self.window_cells[*w]
+                    .iter()
+                    .all(|c| self.periods[*c] <= period)
+            })
+    }
+
+    /// Cells sharing a window of the mining width with `n`.
+    fn neighbours(&self, n: usize) -> Vec<usize> {
+        let mut cells = self.windows[n]
+            .iter()
+            .flat_map(|w| self.window_cells[*w].iter().copied())
+            .filter(|c| *c != n)
+            .collect::<Vec<_>>();
+        cells.sort_unstable();
+        cells.dedup();
+        cells
+    }
+
+    fn discounted(&self, value: f64, period: usize) -> f64 {
+        value / self.discount.powi(period as i32)
+    }
+
+    /// Each period's cash flow discounted from the end of the period.
+    pub fn npv(&self) -> f64 {
+        self.values
+            .iter()
+            .zip(self.periods.iter())
+            .map(|(value, period)| self.discounted(*value, *period as usize))
+            .sum()
+    }
+
+    /// Moves every cell that gains from it to the neighbouring period. Returns how many
+    /// were moved.
+    fn sweep(&mut self) -> usize {
+        let mut moved = 0;
+        for n in 0..self.values.len() {
+            let (value, period) = (self.values[n], self.periods[n] as usize);
+            let target = if value > 0.0 && period > 1 {
+                period - 1
+            } else if value < 0.0 && period < self.mined.len() {
+                period + 1
+            } else {
+                continue;
+            };
+            if self.discounted(value, target) <= self.discounted(value, period) {
+                continue;
+            }
+            let precedes = if target < period {
+                self.precedence.above[n]
+                    .iter()
+                    .all(|a| self.periods[*a] as usize <= target)
+            } else {
+                self.below[n]
+                    .iter()
+                    .all(|b| self.periods[*b] as usize >= target)
+            };
+            if !precedes || !self.fits(&[n], target - 1) {
+                continue;
+            }
+            //mining a cell later can close the openings of its neighbours
+            let open = if target > period {
+                self.neighbours(n)
+                    .into_iter()
+                    .filter(|m| self.has_width(*m))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            self.unplace(n);
+            self.place(n, target - 1);
+            if !self.has_width(n) || !open.iter().all(|m| self.has_width(*m)) {
+                self.unplace(n);
+                self.place(n, period - 1);
+                continue;
+            }
+            moved += 1;
+        }
+        moved
+    }
+
+    /// Runs up to `iterations` improvement sweeps, stopping early once none moves a
+    /// cell, reporting the NPV after each and handing the schedule to `swept`. Returns
+    /// the NPV of the schedule.
+    pub fn run(
+        &mut self,
+        iterations: usize,
+        control: &JobControl,
+        mut swept: impl FnMut(usize, &Self),
+    ) -> Result<f64, String> {
+        control.report("Scheduling", 0, Some(self.npv()));
+        for iteration in 1..=iterations {
+            control.check()?;
+            let moved = self.sweep();
+            control.report("Scheduling", iteration, Some(self.npv()));
+            if moved == 0 {
+                break;
+            }
+            swept(iteration, self);
+        }
+        Ok(self.npv())
+    }
+
+    /// Period of each cell of a grid of `dims`, from 1; `None` for cells not scheduled.
+    pub fn periods(&self, dims: &BlockIndex) -> Array3<Option<u32>> {
+        let mut periods = Array3::from_elem((dims.i, dims.j, dims.k), None);
+        for (ind, period) in self.precedence.cells.iter().zip(self.periods.iter()) {
+            periods[[ind.i, ind.j, ind.k]] = Some(*period);
+        }
+        periods
+    }
+}
+
+/// Cells a `width` in metres spans along an axis of `cell` size and `dim` cells, at least
+/// one and at most the whole axis.
+fn cells_across(width: f32, cell: f32, dim: usize) -> usize {
+    ((width / cell).ceil() as usize).clamp(1, dim.max(1))
+}
+
+/// Windows of `width` cells on each bench that hold at least one of `cells` and no rock
+/// outside `include`, as the windows around each cell and the cells of each window.
+fn mining_windows(
+    cells: &[BlockIndex],
+    tonnage: &Array3<f32>,
+    include: &Array3<bool>,
+    width: (usize, usize),
+) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+    let (di, dj, dk) = tonnage.dim();
+    let (wi, wj) = width;
+    let mut node_of = Array3::from_elem((di, dj, dk), None);
+    for (n, ind) in cells.iter().enumerate() {
+        node_of[[ind.i, ind.j, ind.k]] = Some(n);
+    }
+    let rock = |i: usize, j: usize, k: usize| !include[[i, j, k]] && tonnage[[i, j, k]] > 0.0;
+
+    let mut windows = vec![Vec::new(); cells.len()];
+    let mut window_cells = Vec::new();
+    for k in 0..dk {
+        for i0 in 0..=di - wi {
+            for j0 in 0..=dj - wj {
+                let window = (i0..i0 + wi).flat_map(|i| (j0..j0 + wj).map(move |j| (i, j)));
+                if window.clone().any(|(i, j)| rock(i, j, k)) {
+                    continue;
+                }
+                let members = window
+                    .filter_map(|(i, j)| node_of[[i, j, k]])
+                    .collect::<Vec<_>>();
+                if members.is_empty() {
+                    continue;
+                }
+                for n in members.iter() {
+                    windows[*n].push(window_cells.len());
+                }
+                window_cells.push(members);
+            }
+        }
+    }
+    (windows, window_cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //10 t cells valued at 10·(grade - 1) with the defaults below
+    fn params(mining_rate: f32, proc_cap: f32) -> OptimizeParams {
+        OptimizeParams {
+            mining_rate,
+            proc_cap,
+            metal_price: 1.0,
+            mining_cost: 1.0,
+            discount_rate: 0.1,
+            ..Default::default()
+        }
+    }
+
+    /// Grid of 10 t cells with `grades` indexed `[i, j, k]`, all in the pit.
+    fn grid(grades: Array3<f32>) -> (Array3<f32>, Array3<f32>, Array3<bool>) {
+        let dim = grades.dim();
+        (
+            Array3::from_elem(dim, 10.0),
+            grades,
+            Array3::from_elem(dim, true),
+        )
+    }
+
+    fn bench(grades: &[f32]) -> (Array3<f32>, Array3<f32>, Array3<bool>) {
+        grid(Array3::from_shape_vec((grades.len(), 1, 1), grades.to_vec()).unwrap())
+    }
+
+    fn schedule(
+        (tonnage, grade, include): &(Array3<f32>, Array3<f32>, Array3<bool>),
+        params: &OptimizeParams,
+    ) -> Scheduler {
+        let control = JobControl::default();
+        Scheduler::new(tonnage, grade, include, Vec3::ONE, params, &control).unwrap()
+    }
+
+    fn assert_feasible(sched: &Scheduler, params: &OptimizeParams) {
+        for (n, above) in sched.precedence.above.iter().enumerate() {
+            for a in above.iter() {
+                assert!(sched.periods[*a] <= sched.periods[n]);
+            }
+            assert!(sched.has_width(n));
+        }
+        for (mined, milled) in sched.mined.iter().zip(sched.milled.iter()) {
+            assert!(*mined <= params.mining_rate as f64 + 1e-9);
+            assert!(*milled <= params.proc_cap as f64 + 1e-9);
+        }
+    }
+
+    #[test]
+    fn column_is_mined_from_the_top() {
+        let sched = schedule(
+            &grid(Array3::from_elem((1, 1, 3), 3.0)),
+            &params(10.0, 10.0),
+        );
+        let periods = sched.periods(&BlockIndex { i: 1, j: 1, k: 3 });
+        assert_eq!(periods[[0, 0, 2]], Some(1));
+        assert_eq!(periods[[0, 0, 1]], Some(2));
+        assert_eq!(periods[[0, 0, 0]], Some(3));
+    }
+
+    #[test]
+    fn bench_is_mined_by_value() {
+        //values 30, 20 and 10, one cell a period
+        let cells = bench(&[4.0, 3.0, 2.0]);
+        let sched = schedule(&cells, &params(10.0, 10.0));
+        assert_eq!(sched.periods, vec![1, 2, 3]);
+        let npv = 30.0 / 1.1 + 20.0 / 1.1f64.powi(2) + 10.0 / 1.1f64.powi(3);
+        assert!((sched.npv() - npv).abs() < 1e-6);
+
+        let sched = schedule(&cells, &params(20.0, 20.0));
+        assert_eq!(sched.periods, vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn processing_capacity_limits() {
+        //the mine could take two cells a period but the mill only one
+        let sched = schedule(&bench(&[4.0, 3.0, 2.0]), &params(20.0, 10.0));
+        assert_eq!(sched.periods, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn cells_outside_the_pit_are_not_scheduled() {
+        let (tonnage, grade, mut include) = bench(&[4.0, 3.0, 2.0]);
+        include[[1, 0, 0]] = false;
+        let sched = schedule(&(tonnage, grade, include), &params(10.0, 10.0));
+        let periods = sched.periods(&BlockIndex { i: 3, j: 1, k: 1 });
+        assert_eq!(periods[[0, 0, 0]], Some(1));
+        assert_eq!(periods[[1, 0, 0]], None);
+        assert_eq!(periods[[2, 0, 0]], Some(2));
+    }
+
+    #[test]
+    fn mining_width_keeps_neighbours_together() {
+        //values 40, 20, 30 and 10: the two best cells are not neighbours
+        let cells = bench(&[5.0, 3.0, 4.0, 2.0]);
+        let sched = schedule(&cells, &params(20.0, 20.0));
+        assert_eq!(sched.periods, vec![1, 2, 1, 2]);
+
+        let params = OptimizeParams {
+            mining_width: 2.0,
+            ..params(20.0, 20.0)
+        };
+        let mut sched = schedule(&cells, &params);
+        assert_eq!(sched.periods, vec![1, 1, 2, 2]);
+        sched.run(10, &JobControl::default(), |_, _| {}).unwrap();
+        assert_eq!(sched.periods, vec![1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn sweeps_keep_precedence_and_capacity() {
+        //a 7 by 4 section with ore at depth and air in a top corner
+        let mut grades = Array3::from_elem((7, 1, 4), 0.0);
+        grades[[3, 0, 0]] = 6.0;
+        grades[[2, 0, 1]] = 4.0;
+        grades[[3, 0, 1]] = 3.0;
+        grades[[4, 0, 1]] = 4.0;
+        let (mut tonnage, grade, include) = grid(grades);
+        tonnage[[0, 0, 3]] = 0.0;
+        let cells = (tonnage, grade, include);
+
+        for width in [0.0, 2.0] {
+            let params = OptimizeParams {
+                mining_width: width,
+                ..params(30.0, 20.0)
+            };
+            let mut sched = schedule(&cells, &params);
+            assert_feasible(&sched, &params);
+            let greedy = sched.npv();
+            let npv = sched.run(20, &JobControl::default(), |_, _| {}).unwrap();
+            assert_feasible(&sched, &params);
+            assert!(npv >= greedy);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use polars::prelude::{NamedFrom, Series};
+
+use crate::{
+    block_model::BlockModel,
+    economics::EconomicModel,
+    jobs::JobControl,
+    optimizer::OptimizeParams,
+    pit::{ultimate_pit, PitSummary},
+    spatial_index::BlockGrid,
+};
+
+/// One pit of a nested family, with totals at the base price.
+#[derive(Clone, Debug)]
+pub struct PitShell {
+    pub revenue_factor: f32,
+    pub pit: PitSummary,
+    /// Discounted value mining the shells one after another, each bench by bench.
+    pub best_case: f64,
+    /// Discounted value mining the whole pit bench by bench.
+    pub worst_case: f64,
+}
+
+/// Revenue factors from `min` to `max` in `steps` equal steps.
+pub fn revenue_factors(min: f32, max: f32, steps: usize) -> Vec<f32> {
+    if steps <= 1 {
+        return vec![max];
+    }
+    (0..steps)
+        .map(|i| min + (max - min) * i as f32 / (steps - 1) as f32)
+        .collect()
+}
+
+/// Value of mining `rows` in order at `params.mining_rate` tonnes a year, each year's
+/// cash flow discounted from the end of the year.
+fn discounted_value(
+    rows: impl Iterator<Item = usize>,
+    economics: &EconomicModel,
+    params: &OptimizeParams,
+) -> f64 {
+    let rate = params.mining_rate as f64;
+    let discount = 1.0 + params.discount_rate as f64;
+    let mut mined = 0.0;
+    let mut npv = 0.0;
+    for row in rows {
+        let year = (mined / rate).floor();
+        npv += economics.values[row].value / discount.powf(year + 1.0);
+        mined += economics.tonnes[row];
+    }
+    npv
+}
+
+/// Nested pits for each of `factors` (multiples of the metal price), smallest first.
+///
+/// Each block's shell is the number, from 1, of the first pit it falls in; blocks outside
+/// the largest pit are null. Pit `n` is then every block of shell `n` or less, which keeps
+/// the family nested even where ties let pits at neighbouring factors differ. Shell totals
+/// and the best and worst case values are at the base price of `params`, scheduled at its
+/// mining rate.
+pub fn nested_shells(
+    bm: &BlockModel,
+    grid: &BlockGrid,
+    params: &OptimizeParams,
+    factors: &[f32],
+    name: &str,
+    control: &JobControl,
+) -> Result<(Series, Vec<PitShell>), String> {
+    if factors.is_empty() {
+        return Err("No revenue factors".into());
+    }
+    if params.mining_rate <= 0.0 {
+        return Err("The mining rate must be positive".into());
+    }
+    let mut factors = factors.to_vec();
+    factors.sort_by(|a, b| a.total_cmp(b));
+
+    let mut shell: Vec<Option<u32>> = vec![None; bm.df.height()];
+    for (n, factor) in factors.iter().enumerate() {
+        control.set_part(n + 1, factors.len());
+        let scaled = OptimizeParams {
+            metal_price: params.metal_price * factor,
+            ..params.clone()
+        };
+        let (flags, _) = ultimate_pit(bm, grid, &scaled, name, control)?;
+        let flags = flags.bool().map_err(|e| e.to_string())?;
+        for (s, in_pit) in shell.iter_mut().zip(flags.into_iter()) {
+            if s.is_none() && in_pit == Some(true) {
+                *s = Some(n as u32 + 1);
+            }
+        }
+    }
+
+    let economics = EconomicModel::new(bm, params)?;
+    let z = bm
+        .column_f32(&bm.z)
+        .ok_or_else(|| "Block coordinates are not numeric".to_string())?
+        .into_iter()
+        .map(|z| z.unwrap_or(0.0))
+        .collect::<Vec<_>>();
+    let mut rows = (0..shell.len())
+        .filter(|row| shell[*row].is_some())
+        .collect::<Vec<_>>();
+
+    //benches top down, then shell by shell for the best case
+    rows.sort_by(|a, b| z[*b].total_cmp(&z[*a]));
+    let benches = rows.clone();
+    rows.sort_by_key(|row| shell[*row]);
+    let pushbacks = rows;
+
+    let mut shells = Vec::with_capacity(factors.len());
+    for (n, factor) in factors.iter().enumerate() {
+        let last = n as u32 + 1;
+        let in_pit = |row: usize| shell[row].map_or(false, |s| s <= last);
+        let [ore, waste] = economics.totals(params, in_pit);
+        shells.push(PitShell {
+            revenue_factor: *factor,
+            pit: PitSummary {
+                blocks: ore.blocks + waste.blocks,
+                ore_tonnes: ore.tonnes,
+                waste_tonnes: waste.tonnes,
+                metal: ore.metal,
+                value: ore.value + waste.value,
+            },
+            best_case: discounted_value(
+                pushbacks.iter().copied().take_while(|row| in_pit(*row)),
+                &economics,
+                params,
+            ),
+            worst_case: discounted_value(
+                benches.iter().copied().filter(|row| in_pit(*row)),
+                &economics,
+                params,
+            ),
+        });
+    }
+
+    Ok((Series::new(name, shell), shells))
+}
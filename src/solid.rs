@@ -0,0 +1,92 @@
+use polars::prelude::{NamedFrom, Series};
+
+use crate::{
+    block_model::{Axis, BlockModel},
+    jobs::JobControl,
+    surface::SurfaceIndex,
+};
+
+/// Crossings of a vertical line with a closed solid closer than this are treated as one.
+const CROSSING_TOLERANCE: f32 = 1e-4;
+/// Blocks tested between progress reports and cancellation checks.
+const REPORT_EVERY: usize = 10_000;
+
+/// Whether `z` lies inside a closed solid, given the sorted `crossings` of the vertical
+/// line through the point: inside points have an odd number of crossings above them.
+fn inside(crossings: &[f32], z: f32) -> bool {
+    let above = crossings.len() - crossings.partition_point(|c| *c <= z);
+    above % 2 == 1
+}
+
+/// Tests every block against a closed triangulated solid. Returns a boolean column,
+/// true where the block centre is inside, and the percentage of the block inside,
+/// estimated from `samples`³ sub-cell centres. Both are null for blocks without
+/// coordinates.
+pub fn flag_in_solid(
+    bm: &BlockModel,
+    solid: &SurfaceIndex,
+    samples: usize,
+    flag_name: &str,
+    percent_name: &str,
+    control: &JobControl,
+) -> Result<(Series, Series), String> {
+    let samples = samples.max(1);
+    let columns = Axis::ALL.map(|axis| {
+        let (coord, size) = bm.axis_columns(axis);
+        Some((bm.column_f32(coord)?, bm.column_f32(size)?))
+    });
+    let [Some((x, dx)), Some((y, dy)), Some((z, dz))] = columns else {
+        return Err("Block coordinates are not numeric".into());
+    };
+
+    //fractions of the block size at which sub-cells are sampled
+    let offsets = (0..samples)
+        .map(|i| (i as f32 + 0.5) / samples as f32)
+        .collect::<Vec<_>>();
+
+    let mut flags = Vec::with_capacity(bm.df.height());
+    let mut percents = Vec::with_capacity(bm.df.height());
+    for (row, (((((x, dx), y), dy), z), dz)) in x
+        .into_iter()
+        .zip(dx.into_iter())
+        .zip(y.into_iter())
+        .zip(dy.into_iter())
+        .zip(z.into_iter())
+        .zip(dz.into_iter())
+        .enumerate()
+    {
+        if row % REPORT_EVERY == 0 {
+            control.check()?;
+            control.report("Testing blocks", row, None);
+        }
+        let (Some(x), Some(dx), Some(y), Some(dy), Some(z), Some(dz)) = (x, dx, y, dy, z, dz)
+        else {
+            flags.push(None);
+            percents.push(None);
+            continue;
+        };
+
+        let centre = solid.crossings(x + dx / 2.0, y + dy / 2.0, CROSSING_TOLERANCE);
+        flags.push(Some(inside(&centre, z + dz / 2.0)));
+
+        let mut count = 0;
+        for u in offsets.iter() {
+            for v in offsets.iter() {
+                let crossings = solid.crossings(x + dx * u, y + dy * v, CROSSING_TOLERANCE);
+                if crossings.is_empty() {
+                    continue;
+                }
+                count += offsets
+                    .iter()
+                    .filter(|w| inside(&crossings, z + dz * *w))
+                    .count();
+            }
+        }
+        percents.push(Some(100.0 * count as f64 / samples.pow(3) as f64));
+    }
+
+    Ok((
+        Series::new(flag_name, flags),
+        Series::new(percent_name, percents),
+    ))
+}
@@ -0,0 +1,243 @@
+use bevy::{prelude::*, utils::HashMap};
+use itertools::izip;
+
+use crate::{block::BlockIndex, block_model::BlockModel, picking::ray_box_intersection};
+
+/// Regular grid lookup from cell index to DataFrame row.
+///
+/// The cell size is the smallest block size in the model; larger (parent) blocks are
+/// registered in every cell they cover. Queries walk only the cells a ray passes
+/// through, so cost scales with grid resolution rather than block count.
+#[derive(Clone)]
+pub struct BlockGrid {
+    pub origin: Vec3,
+    pub cell: Vec3,
+    pub dims: BlockIndex,
+    pub rows: usize,
+    cells: HashMap<BlockIndex, usize>,
+}
+
+impl BlockGrid {
+    pub fn from_block_model(bm: &BlockModel) -> Option<Self> {
+        let x = bm.column_f32(&bm.x)?;
+        let y = bm.column_f32(&bm.y)?;
+        let z = bm.column_f32(&bm.z)?;
+        let x_size = bm.column_f32(&bm.x_size)?;
+        let y_size = bm.column_f32(&bm.y_size)?;
+        let z_size = bm.column_f32(&bm.z_size)?;
+
+        let mut origin = Vec3::splat(f32::MAX);
+        let mut cell = Vec3::splat(f32::MAX);
+        for (x, y, z, xs, ys, zs) in izip!(&x, &y, &z, &x_size, &y_size, &z_size) {
+            if let (Some(x), Some(y), Some(z), Some(xs), Some(ys), Some(zs)) = (x, y, z, xs, ys, zs)
+            {
+                origin = origin.min(Vec3::new(x, y, z));
+                cell = cell.min(Vec3::new(xs, ys, zs));
+            }
+        }
+        if origin.x == f32::MAX || cell.min_element() <= 0.0 {
+            return None;
+        }
+
+        let mut grid = Self {
+            origin,
+            cell,
+            dims: BlockIndex::default(),
+            rows: bm.df.height(),
+            cells: HashMap::default(),
+        };
+
+        for (row, (x, y, z, xs, ys, zs)) in izip!(&x, &y, &z, &x_size, &y_size, &z_size).enumerate()
+        {
+            let (Some(x), Some(y), Some(z), Some(xs), Some(ys), Some(zs)) = (x, y, z, xs, ys, zs)
+            else {
+                continue;
+            };
+            let (start, span) = grid.block_cells(Vec3::new(x, y, z), Vec3::new(xs, ys, zs));
+
+            for i in 0..span.x as usize {
+                for j in 0..span.y as usize {
+                    for k in 0..span.z as usize {
+                        let ind = BlockIndex {
+                            i: start.x as usize + i,
+                            j: start.y as usize + j,
+                            k: start.z as usize + k,
+                        };
+                        grid.dims.i = grid.dims.i.max(ind.i + 1);
+                        grid.dims.j = grid.dims.j.max(ind.j + 1);
+                        grid.dims.k = grid.dims.k.max(ind.k + 1);
+                        grid.cells.insert(ind, row);
+                    }
+                }
+            }
+        }
+
+        Some(grid)
+    }
+
+    fn cell_coords(&self, p: Vec3) -> IVec3 {
+        ((p - self.origin) / self.cell + Vec3::splat(1e-3))
+            .floor()
+            .as_ivec3()
+    }
+
+    /// First cell and number of cells along each axis covered by a block.
+    pub fn block_cells(&self, minimum: Vec3, size: Vec3) -> (IVec3, UVec3) {
+        let span = (size / self.cell).round().max(Vec3::ONE).as_uvec3();
+        (self.cell_coords(minimum), span)
+    }
+
+    /// True if every cell sharing a face with the block is occupied by a block for
+    /// which `occludes` returns true, i.e. the block cannot be seen from outside.
+    pub fn is_enclosed(&self, minimum: Vec3, size: Vec3, occludes: impl Fn(usize) -> bool) -> bool {
+        let (start, span) = self.block_cells(minimum, size);
+        let end = start + span.as_ivec3();
+
+        let occupied = |c: IVec3| {
+            if c.min_element() < 0 {
+                return false;
+            }
+            self.get(&BlockIndex {
+                i: c.x as usize,
+                j: c.y as usize,
+                k: c.z as usize,
+            })
+            .map_or(false, &occludes)
+        };
+
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            for a in start[u]..end[u] {
+                for b in start[v]..end[v] {
+                    let mut below = IVec3::ZERO;
+                    below[axis] = start[axis] - 1;
+                    below[u] = a;
+                    below[v] = b;
+                    let mut above = below;
+                    above[axis] = end[axis];
+                    if !occupied(below) || !occupied(above) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    pub fn maximum(&self) -> Vec3 {
+        self.origin
+            + self.cell * Vec3::new(self.dims.i as f32, self.dims.j as f32, self.dims.k as f32)
+    }
+
+    /// All occupied cells and the row each maps to, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&BlockIndex, &usize)> {
+        self.cells.iter()
+    }
+
+    /// World position of the centre of cell `ind`.
+    pub fn cell_centre(&self, ind: &BlockIndex) -> Vec3 {
+        self.origin
+            + self.cell * (Vec3::new(ind.i as f32, ind.j as f32, ind.k as f32) + Vec3::splat(0.5))
+    }
+
+    pub fn get(&self, ind: &BlockIndex) -> Option<usize> {
+        self.cells.get(ind).copied()
+    }
+
+    /// Row of the block containing `p`, if any.
+    pub fn locate(&self, p: Vec3) -> Option<usize> {
+        let c = self.cell_coords(p);
+        if c.min_element() < 0 {
+            return None;
+        }
+        self.get(&BlockIndex {
+            i: c.x as usize,
+            j: c.y as usize,
+            k: c.z as usize,
+        })
+    }
+
+    /// Walks the cells crossed by `ray` front to back (Amanatides & Woo) and returns the
+    /// distance and row of the first block for which `accept` returns true.
+    pub fn raycast(
+        &self,
+        ray: &Ray,
+        mut accept: impl FnMut(usize) -> bool,
+    ) -> Option<(f32, usize)> {
+        let maximum = self.maximum();
+        let t_enter = ray_box_intersection(ray, self.origin, maximum)?;
+
+        let start = ray.origin + ray.direction * t_enter;
+        let dims = IVec3::new(self.dims.i as i32, self.dims.j as i32, self.dims.k as i32);
+        let mut c = ((start - self.origin) / self.cell)
+            .floor()
+            .as_ivec3()
+            .clamp(IVec3::ZERO, dims - IVec3::ONE);
+
+        let step = IVec3::new(
+            ray.direction.x.signum() as i32,
+            ray.direction.y.signum() as i32,
+            ray.direction.z.signum() as i32,
+        );
+        let t_delta = (self.cell / ray.direction).abs();
+        let next_boundary =
+            self.origin + (c.as_vec3() + step.max(IVec3::ZERO).as_vec3()) * self.cell;
+        let mut t_max = Vec3::select(
+            ray.direction.cmpeq(Vec3::ZERO),
+            Vec3::splat(f32::INFINITY),
+            (next_boundary - ray.origin) / ray.direction,
+        );
+        let mut t = t_enter;
+
+        loop {
+            if c.cmplt(IVec3::ZERO).any() || c.cmpge(dims).any() {
+                return None;
+            }
+            let ind = BlockIndex {
+                i: c.x as usize,
+                j: c.y as usize,
+                k: c.z as usize,
+            };
+            if let Some(row) = self.get(&ind) {
+                if accept(row) {
+                    return Some((t, row));
+                }
+            }
+
+            if t_max.x < t_max.y && t_max.x < t_max.z {
+                t = t_max.x;
+                t_max.x += t_delta.x;
+                c.x += step.x;
+            } else if t_max.y < t_max.z {
+                t = t_max.y;
+                t_max.y += t_delta.y;
+                c.y += step.y;
+            } else {
+                t = t_max.z;
+                t_max.z += t_delta.z;
+                c.z += step.z;
+            }
+        }
+    }
+}
+
+/// Lazily built spatial indices, keyed by block model name.
+#[derive(Resource, Default)]
+pub struct SpatialIndex {
+    pub grids: HashMap<String, BlockGrid>,
+}
+
+impl SpatialIndex {
+    /// Returns the grid for `bm`, (re)building it if missing or stale.
+    pub fn grid(&mut self, bm: &BlockModel) -> Option<&BlockGrid> {
+        let stale = self
+            .grids
+            .get(&bm.name)
+            .map_or(true, |grid| grid.rows != bm.df.height());
+        if stale {
+            let grid = BlockGrid::from_block_model(bm)?;
+            self.grids.insert(bm.name.clone(), grid);
+        }
+        self.grids.get(&bm.name)
+    }
+}
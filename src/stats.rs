@@ -0,0 +1,207 @@
+use bevy::{prelude::*, utils::HashMap};
+use itertools::izip;
+use polars::datatypes::DataType;
+
+use crate::block_model::BlockModel;
+
+pub const QUANTILES: [f64; 7] = [0.05, 0.1, 0.25, 0.5, 0.75, 0.9, 0.95];
+
+#[derive(Clone, Debug, Default)]
+pub struct ColumnStats {
+    pub count: usize,
+    pub nulls: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub variance: f64,
+    /// `(q, value)` pairs for each of [`QUANTILES`].
+    pub quantiles: Vec<(f64, f64)>,
+}
+
+impl ColumnStats {
+    pub fn from_values(mut values: Vec<f64>, nulls: usize) -> Self {
+        let count = values.len();
+        if count == 0 {
+            return Self {
+                nulls,
+                ..Default::default()
+            };
+        }
+        values.sort_by(|a, b| a.total_cmp(b));
+
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+        let quantiles = QUANTILES
+            .iter()
+            .map(|q| (*q, quantile_sorted(&values, *q)))
+            .collect();
+
+        Self {
+            count,
+            nulls,
+            min: values[0],
+            max: values[count - 1],
+            mean,
+            variance,
+            quantiles,
+        }
+    }
+
+    pub fn compute(bm: &BlockModel, column: &str) -> Option<Self> {
+        let ca = bm.column_f32(column)?;
+        let nulls = ca.null_count();
+        let values = ca.into_iter().flatten().map(|v| v as f64).collect();
+        Some(Self::from_values(values, nulls))
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+/// Linearly interpolated quantile of already sorted values.
+pub fn quantile_sorted(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let pos = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    /// `bins + 1` bin edges, in data units even when binned logarithmically.
+    pub edges: Vec<f64>,
+    pub counts: Vec<f64>,
+}
+
+impl Histogram {
+    /// Bins `column`, optionally weighting each block by `weight` (e.g. tonnage) and
+    /// spacing the bins logarithmically. Non-positive values are dropped in log mode.
+    pub fn compute(
+        bm: &BlockModel,
+        column: &str,
+        weight: Option<&str>,
+        bins: usize,
+        log: bool,
+    ) -> Option<Self> {
+        let values = bm.column_f32(column)?;
+        let weights = match weight {
+            Some(w) => Some(bm.column_f32(w)?),
+            None => None,
+        };
+
+        let pairs = match &weights {
+            Some(weights) => izip!(&values, weights)
+                .filter_map(|(v, w)| Some((v? as f64, w? as f64)))
+                .collect::<Vec<_>>(),
+            None => values
+                .into_iter()
+                .flatten()
+                .map(|v| (v as f64, 1.0))
+                .collect::<Vec<_>>(),
+        };
+
+        Some(Self::from_pairs(&pairs, bins, log))
+    }
+
+    pub fn from_pairs(pairs: &[(f64, f64)], bins: usize, log: bool) -> Self {
+        let transform = |v: f64| if log { v.log10() } else { v };
+        let bins = bins.max(1);
+
+        let (lo, hi) = pairs
+            .iter()
+            .filter(|(v, _)| !log || *v > 0.0)
+            .fold((f64::MAX, f64::MIN), |(lo, hi), (v, _)| {
+                (lo.min(transform(*v)), hi.max(transform(*v)))
+            });
+        if lo > hi {
+            return Self::default();
+        }
+        let width = if hi > lo {
+            (hi - lo) / bins as f64
+        } else {
+            1.0
+        };
+
+        let mut counts = vec![0.0; bins];
+        for (v, w) in pairs.iter() {
+            if log && *v <= 0.0 {
+                continue;
+            }
+            let bin = (((transform(*v) - lo) / width) as usize).min(bins - 1);
+            counts[bin] += w;
+        }
+
+        let edges = (0..=bins)
+            .map(|i| {
+                let edge = lo + i as f64 * width;
+                if log {
+                    10f64.powf(edge)
+                } else {
+                    edge
+                }
+            })
+            .collect();
+
+        Self { edges, counts }
+    }
+}
+
+/// Statistics of `column` for every distinct value of the categorical `category` column.
+pub fn breakdown(
+    bm: &BlockModel,
+    column: &str,
+    category: &str,
+) -> Option<Vec<(String, ColumnStats)>> {
+    let values = bm.column_f32(column)?;
+    let binding = bm.df.column(category).ok()?.cast(&DataType::Utf8).ok()?;
+    let categories = binding.utf8().ok()?;
+
+    let mut groups: HashMap<String, (Vec<f64>, usize)> = HashMap::default();
+    for (v, c) in izip!(&values, categories) {
+        let entry = groups
+            .entry(c.unwrap_or("<null>").to_string())
+            .or_insert_with(|| (Vec::new(), 0));
+        match v {
+            Some(v) => entry.0.push(v as f64),
+            None => entry.1 += 1,
+        }
+    }
+
+    let mut breakdown = groups
+        .into_iter()
+        .map(|(name, (values, nulls))| (name, ColumnStats::from_values(values, nulls)))
+        .collect::<Vec<_>>();
+    breakdown.sort_by(|a, b| a.0.cmp(&b.0));
+    Some(breakdown)
+}
+
+/// Column statistics keyed by `(block model, column)`, shared by the statistics panel,
+/// colour bars and colour mapping.
+#[derive(Resource, Default)]
+pub struct StatsCache {
+    stats: HashMap<(String, String), (usize, ColumnStats)>,
+}
+
+impl StatsCache {
+    pub fn get(&mut self, bm: &BlockModel, column: &str) -> Option<&ColumnStats> {
+        let key = (bm.name.clone(), column.to_string());
+        let stale = self
+            .stats
+            .get(&key)
+            .map_or(true, |(rows, _)| *rows != bm.df.height());
+        if stale {
+            let stats = ColumnStats::compute(bm, column)?;
+            self.stats.insert(key.clone(), (bm.df.height(), stats));
+        }
+        self.stats.get(&key).map(|(_, stats)| stats)
+    }
+
+    /// Drops cached statistics for `grid`, e.g. after one of its columns is rewritten.
+    pub fn invalidate(&mut self, grid: &str) {
+        self.stats.retain(|(name, _), _| name != grid);
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use polars::prelude::{NamedFrom, Series};
+
+use crate::{
+    block_model::{Axis, BlockModel},
+    isosurface::IsoMesh,
+    mesh_io::read_mesh,
+};
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Triangulates a regular grid of optional elevations, two triangles per cell with all
+/// four corners present, wound counter-clockwise seen from above.
+fn grid_mesh(nx: usize, ny: usize, point: impl Fn(usize, usize) -> Option<Vec3>) -> IsoMesh {
+    let mut mesh = IsoMesh::default();
+    let mut vertex: HashMap<(usize, usize), u32> = HashMap::default();
+    for j in 0..ny {
+        for i in 0..nx {
+            if let Some(p) = point(i, j) {
+                vertex.insert((i, j), mesh.positions.len() as u32);
+                mesh.positions.push(p.to_array());
+            }
+        }
+    }
+    for j in 0..ny.saturating_sub(1) {
+        for i in 0..nx.saturating_sub(1) {
+            let corners =
+                [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)].map(|c| vertex.get(&c).copied());
+            if let [Some(a), Some(b), Some(c), Some(d)] = corners {
+                mesh.indices.extend([a, b, c, a, c, d]);
+            }
+        }
+    }
+    mesh.compute_normals();
+    mesh
+}
+
+/// ESRI ASCII grid (`.asc`). Rows run from north to south.
+pub fn read_esri_ascii(path: &Path) -> io::Result<IsoMesh> {
+    let mut header: HashMap<String, f64> = HashMap::default();
+    let mut values = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let mut parts = line.split_whitespace().peekable();
+        let Some(first) = parts.peek() else {
+            continue;
+        };
+        if first.parse::<f64>().is_err() {
+            let key = first.to_ascii_lowercase();
+            parts.next();
+            let value = parts
+                .next()
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| invalid(format!("Bad header line '{}'", line)))?;
+            header.insert(key, value);
+            continue;
+        }
+        for v in parts {
+            values.push(
+                v.parse::<f32>()
+                    .map_err(|_| invalid(format!("Bad value '{}'", v)))?,
+            );
+        }
+    }
+
+    let get = |key: &str| {
+        header
+            .get(key)
+            .copied()
+            .ok_or_else(|| invalid(format!("Missing {}", key)))
+    };
+    let nx = get("ncols")? as usize;
+    let ny = get("nrows")? as usize;
+    let cell = get("cellsize")?;
+    let nodata = header.get("nodata_value").copied();
+    //cell centres, whichever reference the header uses
+    let x0 = match header.get("xllcenter") {
+        Some(x) => *x,
+        None => get("xllcorner")? + cell / 2.0,
+    };
+    let y0 = match header.get("yllcenter") {
+        Some(y) => *y,
+        None => get("yllcorner")? + cell / 2.0,
+    };
+    if values.len() != nx * ny {
+        return Err(invalid(format!(
+            "Expected {} values, found {}",
+            nx * ny,
+            values.len()
+        )));
+    }
+
+    Ok(grid_mesh(nx, ny, |i, j| {
+        let z = values[(ny - 1 - j) * nx + i];
+        if nodata.map_or(false, |n| z as f64 == n) {
+            return None;
+        }
+        Some(Vec3::new(
+            (x0 + i as f64 * cell) as f32,
+            (y0 + j as f64 * cell) as f32,
+            z,
+        ))
+    }))
+}
+
+/// Sorted distinct values, merging those closer than `tolerance`.
+fn distinct(mut values: Vec<f64>, tolerance: f64) -> Vec<f64> {
+    values.sort_by(|a, b| a.total_cmp(b));
+    values.dedup_by(|a, b| (*a - *b).abs() <= tolerance);
+    values
+}
+
+/// Nearest entry of sorted `values`.
+fn nearest(values: &[f64], v: f64) -> usize {
+    match values.binary_search_by(|x| x.total_cmp(&v)) {
+        Ok(i) => i,
+        Err(0) => 0,
+        Err(i) if i == values.len() => i - 1,
+        Err(i) => {
+            if v - values[i - 1] < values[i] - v {
+                i - 1
+            } else {
+                i
+            }
+        }
+    }
+}
+
+/// Gridded XYZ points, one `x y z` (or `x,y,z`) per line. Lines that do not parse, such
+/// as a header, are skipped.
+pub fn read_xyz_grid(path: &Path) -> io::Result<IsoMesh> {
+    let mut points = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let p = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .take(3)
+            .map(|s| s.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>();
+        if let Ok([x, y, z]) = p.as_deref() {
+            points.push((*x, *y, *z));
+        }
+    }
+    if points.len() < 4 {
+        return Err(invalid("Too few points for a grid"));
+    }
+
+    let tolerance = 1e-6
+        * points
+            .iter()
+            .fold(1.0f64, |m, (x, y, _)| m.max(x.abs()).max(y.abs()));
+    let xs = distinct(points.iter().map(|p| p.0).collect(), tolerance);
+    let ys = distinct(points.iter().map(|p| p.1).collect(), tolerance);
+    let mut z: HashMap<(usize, usize), f64> = HashMap::default();
+    for (x, y, elevation) in points.iter() {
+        z.insert((nearest(&xs, *x), nearest(&ys, *y)), *elevation);
+    }
+
+    Ok(grid_mesh(xs.len(), ys.len(), |i, j| {
+        z.get(&(i, j))
+            .map(|z| Vec3::new(xs[i] as f32, ys[j] as f32, *z as f32))
+    }))
+}
+
+/// Reads a surface from a gridded DTM (`.asc`, `.xyz`, `.txt`, `.csv`) or a
+/// triangulation (`.obj`, `.stl`, `.dxf`).
+pub fn read_surface(path: &Path) -> io::Result<IsoMesh> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let mesh = match extension.as_deref() {
+        Some("asc") => read_esri_ascii(path)?,
+        Some("xyz" | "txt" | "csv") => read_xyz_grid(path)?,
+        _ => read_mesh(path)?,
+    };
+    if mesh.indices.is_empty() {
+        return Err(invalid("Surface has no triangles"));
+    }
+    Ok(mesh)
+}
+
+/// Plan view bucket grid over the triangles of a surface, for elevation lookups.
+pub struct SurfaceIndex {
+    triangles: Vec<[Vec3; 3]>,
+    origin: Vec2,
+    cell: f32,
+    dims: (usize, usize),
+    buckets: Vec<Vec<u32>>,
+}
+
+impl SurfaceIndex {
+    pub fn new(mesh: &IsoMesh) -> Self {
+        let triangles = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]].map(|i| Vec3::from(mesh.positions[i as usize])))
+            .collect::<Vec<_>>();
+        let (min, max) = triangles.iter().flatten().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), p| (min.min(p.truncate()), max.max(p.truncate())),
+        );
+        let extent = (max - min).max(Vec2::ONE);
+        //roughly one triangle per bucket
+        let cell = (extent.x * extent.y / triangles.len().max(1) as f32)
+            .sqrt()
+            .max(f32::EPSILON);
+        let dims = (
+            (extent.x / cell).ceil() as usize + 1,
+            (extent.y / cell).ceil() as usize + 1,
+        );
+
+        let mut index = Self {
+            triangles: Vec::new(),
+            origin: min,
+            cell,
+            dims,
+            buckets: vec![Vec::new(); dims.0 * dims.1],
+        };
+        for (t, tri) in triangles.iter().enumerate() {
+            let (lo, hi) = tri.iter().fold(
+                (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+                |(lo, hi), p| (lo.min(p.truncate()), hi.max(p.truncate())),
+            );
+            let (i0, j0) = index.bucket(lo);
+            let (i1, j1) = index.bucket(hi);
+            for j in j0..=j1 {
+                for i in i0..=i1 {
+                    index.buckets[j * dims.0 + i].push(t as u32);
+                }
+            }
+        }
+        index.triangles = triangles;
+        index
+    }
+
+    fn bucket(&self, p: Vec2) -> (usize, usize) {
+        let b = ((p - self.origin) / self.cell).floor();
+        (
+            (b.x.max(0.0) as usize).min(self.dims.0 - 1),
+            (b.y.max(0.0) as usize).min(self.dims.1 - 1),
+        )
+    }
+
+    /// Elevations at `(x, y)` of every triangle covering that point, with whether the
+    /// triangle faces up (by its winding in plan).
+    fn hits(&self, x: f32, y: f32) -> impl Iterator<Item = (f32, bool)> + '_ {
+        let p = Vec2::new(x, y);
+        let (i, j) = self.bucket(p);
+        let bucket: &[u32] = if (p - self.origin).min_element() < 0.0 {
+            &[]
+        } else {
+            &self.buckets[j * self.dims.0 + i]
+        };
+        bucket.iter().filter_map(move |t| {
+            let [a, b, c] = self.triangles[*t as usize];
+            //barycentric coordinates in plan
+            let (v0, v1, v2) = (
+                b.truncate() - a.truncate(),
+                c.truncate() - a.truncate(),
+                p - a.truncate(),
+            );
+            let denom = v0.perp_dot(v1);
+            if denom.abs() <= f32::EPSILON {
+                return None;
+            }
+            let v = v2.perp_dot(v1) / denom;
+            let w = v0.perp_dot(v2) / denom;
+            let u = 1.0 - v - w;
+            (u >= -1e-5 && v >= -1e-5 && w >= -1e-5)
+                .then(|| (a.z * u + b.z * v + c.z * w, denom > 0.0))
+        })
+    }
+
+    /// Elevations at `(x, y)` of every triangle covering that point.
+    fn heights(&self, x: f32, y: f32) -> impl Iterator<Item = f32> + '_ {
+        self.hits(x, y).map(|(z, _)| z)
+    }
+
+    /// Highest elevation of the surface above `(x, y)`, if it covers that point.
+    pub fn elevation(&self, x: f32, y: f32) -> Option<f32> {
+        self.heights(x, y).reduce(f32::max)
+    }
+
+    /// Sorted elevations where a vertical line through `(x, y)` crosses the triangles.
+    /// Crossings closer than `tolerance` that face the same way are merged, so a line
+    /// through an edge shared by two triangles counts once. Where the triangles face
+    /// opposite ways, as at the rim of a closed solid, the line only grazes the surface
+    /// and both crossings are kept so their parity is unchanged.
+    pub fn crossings(&self, x: f32, y: f32, tolerance: f32) -> Vec<f32> {
+        let mut hits = self.hits(x, y).collect::<Vec<_>>();
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        hits.dedup_by(|b, a| b.0 - a.0 <= tolerance && b.1 == a.1);
+        hits.into_iter().map(|(z, _)| z).collect()
+    }
+}
+
+/// `1` for blocks whose centre lies below the surface, `0` above, null where the surface
+/// does not cover the block.
+pub fn flag_below_surface(bm: &BlockModel, surface: &SurfaceIndex, name: &str) -> Option<Series> {
+    let x = bm.centres(Axis::X)?;
+    let y = bm.centres(Axis::Y)?;
+    let z = bm.centres(Axis::Z)?;
+    let flags = x
+        .iter()
+        .zip(y.iter())
+        .zip(z.iter())
+        .map(|((x, y), z)| {
+            let elevation = surface.elevation((*x)? as f32, (*y)? as f32)?;
+            Some(((*z)? < elevation as f64) as i32)
+        })
+        .collect::<Vec<_>>();
+    Some(Series::new(name, flags))
+}
+
+/// How a surface is coloured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SurfaceShading {
+    Solid,
+    Elevation,
+}
+
+pub struct Surface {
+    pub name: String,
+    pub mesh: IsoMesh,
+    pub index: SurfaceIndex,
+    pub shading: SurfaceShading,
+    /// Unmultiplied RGBA; the alpha also applies when coloured by elevation.
+    pub color: [f32; 4],
+    pub visible: bool,
+    pub entity: Entity,
+    pub mesh_handle: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+impl Surface {
+    /// Bevy mesh, with vertex colours from the turbo gradient over the elevation range
+    /// when coloured by elevation.
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = self.mesh.to_mesh();
+        if self.shading == SurfaceShading::Elevation {
+            let (min, max) = self
+                .mesh
+                .positions
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), p| {
+                    (min.min(p[2]), max.max(p[2]))
+                });
+            let gradient = colorgrad::turbo();
+            let colors = self
+                .mesh
+                .positions
+                .iter()
+                .map(|p| {
+                    let t = if max > min {
+                        (p[2] - min) / (max - min)
+                    } else {
+                        0.5
+                    };
+                    let c = gradient.at(t as f64);
+                    [c.r as f32, c.g as f32, c.b as f32, 1.0]
+                })
+                .collect::<Vec<_>>();
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        }
+        mesh
+    }
+
+    pub fn material(&self) -> StandardMaterial {
+        let [r, g, b, a] = match self.shading {
+            SurfaceShading::Solid => self.color,
+            //vertex colours are multiplied by the base colour
+            SurfaceShading::Elevation => [1.0, 1.0, 1.0, self.color[3]],
+        };
+        StandardMaterial {
+            base_color: Color::rgba(r, g, b, a),
+            alpha_mode: if a < 1.0 {
+                AlphaMode::Blend
+            } else {
+                AlphaMode::Opaque
+            },
+            double_sided: true,
+            cull_mode: None,
+            perceptual_roughness: 0.9,
+            ..default()
+        }
+    }
+}
+
+/// Loaded topography surfaces.
+#[derive(Resource, Default)]
+pub struct Surfaces {
+    pub surfaces: Vec<Surface>,
+}
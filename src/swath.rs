@@ -0,0 +1,84 @@
+use itertools::izip;
+
+use crate::{
+    block_model::{Axis, BlockModel},
+    grade_tonnage::TonnageSource,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct Swath {
+    pub name: String,
+    /// Coordinate of the lower edge of slice 0.
+    pub origin: f64,
+    pub width: f64,
+    /// (Weighted) mean value per slice, `None` for empty slices.
+    pub mean: Vec<Option<f64>>,
+    /// Tonnes per slice.
+    pub tonnes: Vec<f64>,
+}
+
+impl Swath {
+    pub fn slice_centre(&self, slice: usize) -> f64 {
+        self.origin + (slice as f64 + 0.5) * self.width
+    }
+}
+
+/// Averages `column` in slices of `width` along `axis`, starting at `origin`.
+///
+/// Blocks are assigned to slices by their centre and their tonnes from `tonnage` summed
+/// per slice. The mean is weighted by tonnes when `weighted`, otherwise every block
+/// counts equally.
+pub fn swath(
+    bm: &BlockModel,
+    column: &str,
+    axis: Axis,
+    origin: f64,
+    width: f64,
+    tonnage: &TonnageSource,
+    weighted: bool,
+) -> Option<Swath> {
+    if width <= 0.0 {
+        return None;
+    }
+    let centres = bm.centres(axis)?;
+    let values = bm.column_f32(column)?;
+    let tonnages = bm.tonnages(tonnage)?;
+
+    let mut sums: Vec<f64> = Vec::new();
+    let mut total: Vec<f64> = Vec::new();
+    let mut tonnes: Vec<f64> = Vec::new();
+    for (c, v, t) in izip!(centres, &values, tonnages) {
+        let Some(c) = c else {
+            continue;
+        };
+        if c < origin {
+            continue;
+        }
+        let slice = ((c - origin) / width) as usize;
+        if slice >= sums.len() {
+            sums.resize(slice + 1, 0.0);
+            total.resize(slice + 1, 0.0);
+            tonnes.resize(slice + 1, 0.0);
+        }
+        tonnes[slice] += t.unwrap_or(0.0);
+
+        let w = if weighted { t } else { Some(1.0) };
+        let (Some(v), Some(w)) = (v, w) else {
+            continue;
+        };
+        sums[slice] += v as f64 * w;
+        total[slice] += w;
+    }
+
+    Some(Swath {
+        name: format!("{}: {}", bm.name, column),
+        origin,
+        width,
+        mean: sums
+            .iter()
+            .zip(total.iter())
+            .map(|(s, t)| (*t > 0.0).then(|| s / t))
+            .collect(),
+        tonnes,
+    })
+}
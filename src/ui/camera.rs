@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use smooth_bevy_cameras::{controllers::orbit::OrbitCameraController, LookTransform};
+
+use crate::{
+    axes::SceneAxes,
+    camera::{CameraBookmark, CameraBookmarks, CameraView, StandardView},
+};
+
+use super::OpenPanels;
+
+#[derive(Resource, Default)]
+pub struct CameraPanel {
+    pub name: String,
+}
+
+pub fn camera_panel(
+    mut contexts: EguiContexts,
+    cameras: Query<(&LookTransform, &Projection), With<OrbitCameraController>>,
+    axes: Res<SceneAxes>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut view_events: EventWriter<CameraView>,
+    mut panel: ResMut<CameraPanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.camera {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+
+    egui::Window::new("Camera Views")
+        .open(&mut open_panels.camera)
+        .show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for view in StandardView::ALL {
+                    if ui.button(view.name()).clicked() {
+                        view_events.send(CameraView::Standard(view));
+                    }
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut panel.name);
+                let add = ui.add_enabled(!panel.name.is_empty(), egui::Button::new("Add Bookmark"));
+                if add.clicked() {
+                    if let Ok((look, projection)) = cameras.get_single() {
+                        let name = std::mem::take(&mut panel.name);
+                        bookmarks.bookmarks.retain(|b| b.name != name);
+                        bookmarks
+                            .bookmarks
+                            .push(CameraBookmark::capture(name, look, projection, &axes));
+                    }
+                }
+            });
+
+            let mut remove = None;
+            for (i, bookmark) in bookmarks.bookmarks.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.button(bookmark.name.as_str()).clicked() {
+                        view_events.send(CameraView::Bookmark(bookmark.clone()));
+                    }
+                    if ui.small_button("✖").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                bookmarks.bookmarks.remove(i);
+            }
+
+            ui.separator();
+            ui.label("Bookmarks are saved with the project (File > Save Project…)");
+        });
+}
@@ -0,0 +1,257 @@
+use std::path::PathBuf;
+
+use bevy::{prelude::*, render::view::RenderLayers};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    axes::ModelSpace,
+    drillholes::{DrillholeFiles, DrillholeSelection, DrillholeSet, Drillholes, TraceStyle},
+};
+
+use super::OpenPanels;
+
+#[derive(Resource, Default)]
+pub struct DrillholePanel {
+    pub files: DrillholeFiles,
+    pub error: Option<String>,
+}
+
+fn pick_csv(ui: &mut egui::Ui, label: &str, path: &mut PathBuf) -> bool {
+    let mut picked = false;
+    ui.horizontal(|ui| {
+        if ui.button(label).clicked() {
+            if let Some(p) = rfd::FileDialog::new()
+                .add_filter("csv", &["csv"])
+                .pick_file()
+            {
+                *path = p;
+                picked = true;
+            }
+        }
+        ui.label(
+            path.file_name()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "-".into()),
+        );
+    });
+    picked
+}
+
+fn column_fields<const N: usize>(ui: &mut egui::Ui, id: &str, fields: [(&str, &mut String); N]) {
+    egui::Grid::new(id).show(ui, |ui| {
+        for (label, value) in fields {
+            ui.label(label);
+            ui.add(egui::TextEdit::singleline(value).desired_width(100.0));
+            ui.end_row();
+        }
+    });
+}
+
+pub fn drillhole_panel(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut drillholes: ResMut<Drillholes>,
+    mut selection: ResMut<DrillholeSelection>,
+    mut panel: ResMut<DrillholePanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.drillholes {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+
+    egui::Window::new("Drillholes")
+        .open(&mut open_panels.drillholes)
+        .show(ctx, |ui| {
+            let files = &mut panel.files;
+            column_fields(ui, "hole_id_column", [("Hole id", &mut files.hole_id)]);
+
+            pick_csv(ui, "Collars…", &mut files.collar);
+            column_fields(
+                ui,
+                "collar_columns",
+                [
+                    ("X", &mut files.x),
+                    ("Y", &mut files.y),
+                    ("Z", &mut files.z),
+                ],
+            );
+
+            let mut survey = files.survey.clone().unwrap_or_default();
+            if pick_csv(ui, "Surveys…", &mut survey) {
+                files.survey = Some(survey);
+            }
+            if files.survey.is_some() && ui.small_button("No surveys (vertical)").clicked() {
+                files.survey = None;
+            }
+            ui.label("Dip is negative downwards");
+            column_fields(
+                ui,
+                "survey_columns",
+                [
+                    ("Depth", &mut files.depth),
+                    ("Azimuth", &mut files.azimuth),
+                    ("Dip", &mut files.dip),
+                ],
+            );
+
+            pick_csv(ui, "Intervals…", &mut files.intervals);
+            column_fields(
+                ui,
+                "interval_columns",
+                [("From", &mut files.from), ("To", &mut files.to)],
+            );
+
+            if ui.button("Load Drillholes").clicked() {
+                let name = files
+                    .intervals
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "drillholes".into());
+                match DrillholeSet::load(name, files) {
+                    Ok(mut set) => {
+                        set.attribute = set
+                            .numeric_columns()
+                            .into_iter()
+                            .find(|c| *c != files.from && *c != files.to)
+                            .unwrap_or_default();
+                        set.mesh = meshes.add(set.to_mesh());
+                        set.material = materials.add(set.material());
+                        set.entity = commands
+                            .spawn((
+                                PbrBundle {
+                                    mesh: set.mesh.clone(),
+                                    material: set.material.clone(),
+                                    ..default()
+                                },
+                                RenderLayers::layer(0),
+                                ModelSpace,
+                            ))
+                            .id();
+                        drillholes.sets.push(set);
+                        panel.error = None;
+                    }
+                    Err(e) => panel.error = Some(e),
+                }
+            }
+
+            if let Some(error) = &panel.error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+
+            let mut remove = None;
+            for (i, set) in drillholes.sets.iter_mut().enumerate() {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .checkbox(
+                            &mut set.visible,
+                            format!("{} ({} holes)", set.name, set.holes.len()),
+                        )
+                        .changed()
+                    {
+                        commands.entity(set.entity).insert(if set.visible {
+                            Visibility::Inherited
+                        } else {
+                            Visibility::Hidden
+                        });
+                    }
+                    if ui.small_button("✖").clicked() {
+                        remove = Some(i);
+                    }
+                });
+
+                let (attribute, style, radius) = (set.attribute.clone(), set.style, set.radius);
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source(("drillhole_attribute", i))
+                        .selected_text(set.attribute.as_str())
+                        .show_ui(ui, |ui| {
+                            for column in set.numeric_columns() {
+                                let label = column.clone();
+                                ui.selectable_value(&mut set.attribute, column, label);
+                            }
+                        });
+                    ui.radio_value(&mut set.style, TraceStyle::Tubes, "Tubes");
+                    ui.radio_value(&mut set.style, TraceStyle::Lines, "Lines");
+                    ui.add(
+                        egui::DragValue::new(&mut set.radius)
+                            .speed(0.1)
+                            .clamp_range(0.01..=100.0)
+                            .prefix("r "),
+                    );
+                });
+
+                if set.attribute != attribute
+                    || set.style != style
+                    || (set.style == TraceStyle::Tubes && set.radius != radius)
+                {
+                    if let Some(mesh) = meshes.get_mut(&set.mesh) {
+                        *mesh = set.to_mesh();
+                    }
+                    if let Some(material) = materials.get_mut(&set.material) {
+                        *material = set.material();
+                    }
+                }
+            }
+            if let Some(i) = remove {
+                let set = drillholes.sets.remove(i);
+                commands.entity(set.entity).despawn_recursive();
+                selection.pick = None;
+            }
+        });
+}
+
+pub fn interval_inspector(
+    mut contexts: EguiContexts,
+    drillholes: Res<Drillholes>,
+    mut selection: ResMut<DrillholeSelection>,
+) {
+    let Some((set, row)) = selection.pick else {
+        return;
+    };
+    let Some(set) = drillholes.sets.get(set) else {
+        selection.pick = None;
+        return;
+    };
+    let Some(Some((hole, from, to))) = set.spans.get(row).copied() else {
+        return;
+    };
+    let hole = &set.holes[hole];
+    let centre = hole.position((from + to) / 2.0);
+
+    let mut open = true;
+    egui::Window::new("Interval Inspector")
+        .open(&mut open)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("interval_grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Hole");
+                    ui.label(hole.id.as_str());
+                    ui.end_row();
+
+                    ui.label("Interval");
+                    ui.label(format!("{:.2} – {:.2}", from, to));
+                    ui.end_row();
+
+                    ui.label("Centre");
+                    ui.label(format!("{:.2}, {:.2}, {:.2}", centre.x, centre.y, centre.z));
+                    ui.end_row();
+
+                    for series in set.intervals.get_columns() {
+                        ui.label(series.name());
+                        match series.get(row) {
+                            Ok(value) => ui.label(value.to_string()),
+                            Err(_) => ui.label("-"),
+                        };
+                        ui.end_row();
+                    }
+                });
+        });
+    if !open {
+        selection.pick = None;
+    }
+}
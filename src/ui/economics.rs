@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    block_model::{BlockModel, BlockModelDB},
+    economics::{
+        breakeven_grade, marginal_grade, BlockValue, Destination, DestinationTotals, EconomicModel,
+    },
+    optimizer::OptimizeParams,
+    picking::Selection,
+    stats::StatsCache,
+};
+
+use super::{block_model_combo, OpenPanels};
+
+#[derive(Resource)]
+pub struct EconomicsPanel {
+    pub value_column: String,
+    pub destination_column: String,
+    pub totals: Option<[DestinationTotals; 2]>,
+    pub error: Option<String>,
+}
+
+impl Default for EconomicsPanel {
+    fn default() -> Self {
+        Self {
+            value_column: "value".into(),
+            destination_column: "destination".into(),
+            totals: None,
+            error: None,
+        }
+    }
+}
+
+impl EconomicsPanel {
+    /// Writes the value and destination columns to `bm` and keeps their totals.
+    pub fn write_columns(
+        &mut self,
+        bm: &mut BlockModel,
+        params: &OptimizeParams,
+    ) -> Result<(), String> {
+        let economics = EconomicModel::new(bm, params)?;
+        self.write_model(bm, &economics, params)
+    }
+
+    /// As `write_columns`, for blocks already valued, e.g. with a cutoff per block.
+    pub fn write_model(
+        &mut self,
+        bm: &mut BlockModel,
+        economics: &EconomicModel,
+        params: &OptimizeParams,
+    ) -> Result<(), String> {
+        let (value, destination) = economics.columns(&self.value_column, &self.destination_column);
+        bm.set_column(value).map_err(|e| e.to_string())?;
+        bm.set_column(destination).map_err(|e| e.to_string())?;
+        self.totals = Some(economics.totals(params, |_| true));
+        Ok(())
+    }
+}
+
+fn money(value: f64) -> String {
+    format!("{:.0}", value)
+}
+
+pub fn economics_panel(
+    mut contexts: EguiContexts,
+    mut block_models: ResMut<BlockModelDB>,
+    mut stats_cache: ResMut<StatsCache>,
+    mut params: ResMut<OptimizeParams>,
+    selection: Res<Selection>,
+    mut panel: ResMut<EconomicsPanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.economics {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+    let params = &mut *params;
+
+    egui::Window::new("Block Economics")
+        .open(&mut open_panels.economics)
+        .show(ctx, |ui| {
+            block_model_combo(ui, "economics_bm", &block_models, &mut params.grid);
+
+            ui.heading("Inputs");
+            egui::Grid::new("economics_inputs").show(ui, |ui| {
+                ui.label("Grade column");
+                ui.text_edit_singleline(&mut params.grade_col);
+                ui.end_row();
+                ui.label("Tonnage column");
+                ui.text_edit_singleline(&mut params.tonnage_col);
+                ui.end_row();
+                for (label, value) in [
+                    ("Metal price (per unit metal)", &mut params.metal_price),
+                    ("Mining cost (per t)", &mut params.mining_cost),
+                    ("Processing cost (per t)", &mut params.processing_cost),
+                    ("Cutoff grade", &mut params.cutoff),
+                ] {
+                    ui.label(label);
+                    ui.add(egui::DragValue::new(value).speed(0.1));
+                    ui.end_row();
+                }
+                ui.label("Breakeven grade");
+                ui.label(breakeven_grade(params).map_or("-".into(), |g| format!("{:.4}", g)));
+                ui.end_row();
+                ui.label("Marginal grade");
+                ui.label(marginal_grade(params).map_or("-".into(), |g| format!("{:.4}", g)));
+                ui.end_row();
+                ui.label("Value column");
+                ui.text_edit_singleline(&mut panel.value_column);
+                ui.end_row();
+                ui.label("Destination column");
+                ui.text_edit_singleline(&mut panel.destination_column);
+                ui.end_row();
+            });
+
+            if ui.button("Compute Values").clicked() {
+                let result = block_models
+                    .block_models
+                    .get_mut(&params.grid)
+                    .ok_or_else(|| "Select a block model".to_string())
+                    .and_then(|bm| panel.write_columns(bm, params));
+                match result {
+                    Ok(()) => {
+                        stats_cache.invalidate(&params.grid);
+                        panel.error = None;
+                    }
+                    Err(e) => panel.error = Some(e),
+                }
+            }
+            if let Some(error) = &panel.error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+
+            if let Some(totals) = &panel.totals {
+                ui.separator();
+                ui.heading("By destination");
+                egui::Grid::new("economics_totals")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for header in [
+                            "",
+                            "Blocks",
+                            "Tonnes",
+                            "Grade",
+                            "Metal",
+                            "Revenue",
+                            "Mining",
+                            "Processing",
+                            "Value",
+                        ] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+                        for (destination, total) in Destination::ALL.iter().zip(totals.iter()) {
+                            ui.label(destination.name());
+                            ui.label(total.blocks.to_string());
+                            ui.label(money(total.tonnes));
+                            ui.label(format!("{:.4}", total.grade()));
+                            ui.label(format!("{:.1}", total.metal));
+                            ui.label(money(total.revenue));
+                            ui.label(money(-total.mining_cost));
+                            ui.label(money(-total.processing_cost));
+                            ui.label(money(total.value));
+                            ui.end_row();
+                        }
+                    });
+            }
+
+            //worked calculation for the selected block
+            let Some(pick) = selection.picks.first() else {
+                return;
+            };
+            let Some(bm) = block_models.block_models.get(&pick.layer.grid) else {
+                return;
+            };
+            let value = |column: &str| {
+                bm.df
+                    .column(column)
+                    .ok()
+                    .and_then(|c| c.get(pick.row).ok())
+                    .and_then(|v| v.extract::<f64>())
+                    .unwrap_or(0.0)
+            };
+            let (tonnes, grade) = (value(&params.tonnage_col), value(&params.grade_col));
+            let block = BlockValue::new(tonnes, grade, params);
+            ui.separator();
+            ui.heading(format!("Selected block [row {}]", pick.row));
+            egui::Grid::new("economics_block")
+                .striped(true)
+                .show(ui, |ui| {
+                    for (label, text) in [
+                        ("Tonnes", money(tonnes)),
+                        ("Grade", format!("{:.4}", grade)),
+                        ("Waste value", money(block.waste)),
+                        (
+                            "Process value",
+                            block.process.map_or("below cutoff".into(), money),
+                        ),
+                        ("Destination", block.destination.name().into()),
+                        ("Value", money(block.value)),
+                    ] {
+                        ui.label(label);
+                        ui.label(text);
+                        ui.end_row();
+                    }
+                });
+        });
+}
@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::export::{ExportRequest, ImageExport};
+
+use super::OpenPanels;
+
+#[derive(Resource)]
+pub struct ExportPanel {
+    pub width: u32,
+    pub height: u32,
+    pub color_bars: bool,
+    pub panels: bool,
+}
+
+impl Default for ExportPanel {
+    fn default() -> Self {
+        Self {
+            width: 3840,
+            height: 2160,
+            color_bars: true,
+            panels: false,
+        }
+    }
+}
+
+pub fn export_panel(
+    mut contexts: EguiContexts,
+    mut export: ResMut<ImageExport>,
+    mut panel: ResMut<ExportPanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.export {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+
+    egui::Window::new("Export Image")
+        .open(&mut open_panels.export)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut panel.panels, "Include panels");
+            ui.add_enabled_ui(!panel.panels, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut panel.width)
+                            .clamp_range(16..=8192)
+                            .prefix("Width: "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut panel.height)
+                            .clamp_range(16..=8192)
+                            .prefix("Height: "),
+                    );
+                });
+            });
+            if panel.panels {
+                ui.label("Panels are captured at the window resolution.");
+            }
+            ui.checkbox(&mut panel.color_bars, "Include colour bars");
+
+            ui.add_enabled_ui(!export.is_busy(), |ui| {
+                if ui.button("Export…").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("png", &["png"])
+                        .save_file()
+                    {
+                        export.request(ExportRequest {
+                            path: path.with_extension("png"),
+                            width: panel.width,
+                            height: panel.height,
+                            color_bars: panel.color_bars,
+                            panels: panel.panels,
+                        });
+                    }
+                }
+            });
+            if export.is_busy() {
+                ui.label("Exporting…");
+            }
+            if let Some(error) = &export.last_error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+        });
+}
@@ -0,0 +1,263 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{
+        self,
+        plot::{Legend, Line, Plot, PlotPoints},
+    },
+    EguiContexts,
+};
+
+use crate::{
+    block_model::{BlockModel, BlockModelDB},
+    grade_tonnage::{
+        cutoff_range, export_csv, grade_tonnage_curve, GradeTonnagePoint, TonnageSource,
+    },
+    picking::Selection,
+    stats::StatsCache,
+};
+
+use super::{block_model_combo, column_combo, OpenPanels};
+
+#[derive(Resource)]
+pub struct GradeTonnagePanel {
+    pub grid: String,
+    pub grade: String,
+    pub tonnage: TonnageSource,
+    pub min_cutoff: f64,
+    pub max_cutoff: f64,
+    pub steps: usize,
+    pub selection_only: bool,
+    pub curves: Vec<(String, Vec<GradeTonnagePoint>)>,
+    pub error: Option<String>,
+}
+
+impl Default for GradeTonnagePanel {
+    fn default() -> Self {
+        Self {
+            grid: String::new(),
+            grade: String::new(),
+            tonnage: TonnageSource::default(),
+            min_cutoff: 0.0,
+            max_cutoff: 1.0,
+            steps: 50,
+            selection_only: false,
+            curves: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+/// Radio selection between the ways of deriving block tonnes.
+/// Returns true if the source changed.
+pub fn tonnage_source_ui(ui: &mut egui::Ui, bm: &BlockModel, source: &mut TonnageSource) -> bool {
+    let before = source.clone();
+    ui.horizontal(|ui| {
+        ui.label("Tonnage");
+        if ui
+            .radio(matches!(source, TonnageSource::Column(_)), "Column")
+            .clicked()
+        {
+            *source = TonnageSource::Column(String::new());
+        }
+        if ui
+            .radio(
+                matches!(source, TonnageSource::DensityColumn(_)),
+                "Volume × density column",
+            )
+            .clicked()
+        {
+            *source = TonnageSource::DensityColumn(String::new());
+        }
+        if ui
+            .radio(
+                matches!(source, TonnageSource::Density(_)),
+                "Volume × density",
+            )
+            .clicked()
+        {
+            *source = TonnageSource::default();
+        }
+    });
+
+    match source {
+        TonnageSource::Column(column) => {
+            column_combo(ui, "Tonnage column", bm, column);
+        }
+        TonnageSource::DensityColumn(column) => {
+            column_combo(ui, "Density column", bm, column);
+        }
+        TonnageSource::Density(density) => {
+            ui.add(
+                egui::DragValue::new(density)
+                    .speed(0.01)
+                    .prefix("Density: "),
+            );
+        }
+    }
+    *source != before
+}
+
+pub fn grade_tonnage_panel(
+    mut contexts: EguiContexts,
+    block_models: Res<BlockModelDB>,
+    selection: Res<Selection>,
+    mut stats_cache: ResMut<StatsCache>,
+    mut panel: ResMut<GradeTonnagePanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.grade_tonnage {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+
+    egui::Window::new("Grade–Tonnage")
+        .open(&mut open_panels.grade_tonnage)
+        .show(ctx, |ui| {
+            block_model_combo(ui, "gt_bm", &block_models, &mut panel.grid);
+            if let Some(bm) = block_models.block_models.get(&panel.grid) {
+                if column_combo(ui, "Grade column", bm, &mut panel.grade) {
+                    if let Some(stats) = stats_cache.get(bm, &panel.grade) {
+                        panel.min_cutoff = stats.min;
+                        panel.max_cutoff = stats.max;
+                    }
+                }
+                tonnage_source_ui(ui, bm, &mut panel.tonnage);
+
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut panel.min_cutoff).prefix("From: "));
+                    ui.add(egui::DragValue::new(&mut panel.max_cutoff).prefix("To: "));
+                    ui.add(
+                        egui::DragValue::new(&mut panel.steps)
+                            .clamp_range(1..=1000)
+                            .prefix("Steps: "),
+                    );
+                });
+                ui.checkbox(&mut panel.selection_only, "Selected blocks only");
+
+                if ui.button("Add curve").clicked() {
+                    let rows = panel.selection_only.then(|| {
+                        selection
+                            .picks
+                            .iter()
+                            .filter(|p| p.layer.grid == panel.grid)
+                            .map(|p| p.row)
+                            .collect::<Vec<_>>()
+                    });
+                    let cutoffs = cutoff_range(panel.min_cutoff, panel.max_cutoff, panel.steps);
+                    match grade_tonnage_curve(
+                        bm,
+                        &panel.tonnage,
+                        &panel.grade,
+                        rows.as_deref(),
+                        &cutoffs,
+                    ) {
+                        Some(points) => {
+                            let mut name = format!("{}: {}", panel.grid, panel.grade);
+                            if panel.selection_only {
+                                name.push_str(" (selection)");
+                            }
+                            panel.curves.push((name, points));
+                            panel.error = None;
+                        }
+                        None => {
+                            panel.error =
+                                Some("Grade or tonnage columns missing or not numeric".into())
+                        }
+                    }
+                }
+            }
+
+            if let Some(error) = &panel.error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Clear curves").clicked() {
+                    panel.curves.clear();
+                }
+                if ui.button("Export CSV").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("csv", &["csv"])
+                        .save_file()
+                    {
+                        if let Err(e) = export_csv(&path, &panel.curves) {
+                            panel.error = Some(format!("Export failed: {}", e));
+                        }
+                    }
+                }
+            });
+
+            if panel.curves.is_empty() {
+                return;
+            }
+
+            //grade is drawn against a secondary axis by scaling it onto the tonnage range
+            let max_tonnes = panel
+                .curves
+                .iter()
+                .flat_map(|(_, points)| points.iter().map(|p| p.tonnes))
+                .fold(0.0, f64::max);
+            let max_grade = panel
+                .curves
+                .iter()
+                .flat_map(|(_, points)| points.iter().map(|p| p.grade))
+                .fold(0.0, f64::max);
+            let scale = if max_grade > 0.0 {
+                max_tonnes / max_grade
+            } else {
+                1.0
+            };
+
+            Plot::new("grade_tonnage_plot")
+                .height(300.0)
+                .legend(Legend::default())
+                .y_axis_formatter(move |y, _| format!("{:.3e} t | {:.3}", y, y / scale))
+                .label_formatter(move |name, value| {
+                    format!(
+                        "{}\ncutoff: {:.3}\n{:.3e} t | grade {:.3}",
+                        name,
+                        value.x,
+                        value.y,
+                        value.y / scale
+                    )
+                })
+                .show(ui, |plot_ui| {
+                    for (name, points) in panel.curves.iter() {
+                        let tonnes: PlotPoints =
+                            points.iter().map(|p| [p.cutoff, p.tonnes]).collect();
+                        let grade: PlotPoints =
+                            points.iter().map(|p| [p.cutoff, p.grade * scale]).collect();
+                        plot_ui.line(Line::new(tonnes).name(format!("{} tonnes", name)));
+                        plot_ui.line(
+                            Line::new(grade)
+                                .style(egui::plot::LineStyle::dashed_loose())
+                                .name(format!("{} grade", name)),
+                        );
+                    }
+                });
+
+            egui::CollapsingHeader::new("Table").show(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(250.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("gt_table").striped(true).show(ui, |ui| {
+                            for header in ["Curve", "Cutoff", "Tonnes", "Grade", "Metal"] {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+                            for (name, points) in panel.curves.iter() {
+                                for p in points.iter() {
+                                    ui.label(name.as_str());
+                                    ui.label(format!("{:.3}", p.cutoff));
+                                    ui.label(format!("{:.0}", p.tonnes));
+                                    ui.label(format!("{:.3}", p.grade));
+                                    ui.label(format!("{:.1}", p.metal));
+                                    ui.end_row();
+                                }
+                            }
+                        });
+                    });
+            });
+        });
+}
@@ -0,0 +1,118 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use polars::prelude::AnyValue;
+use smooth_bevy_cameras::controllers::orbit::OrbitCameraController;
+
+use crate::{
+    axes::SceneAxes,
+    block_model::{BlockLayer, BlockModelDB},
+    patching::CoarseLod,
+    spatial_index::SpatialIndex,
+};
+
+/// Seconds the cursor must rest before the tooltip is shown.
+const HOVER_DELAY: f32 = 0.25;
+
+#[derive(Default)]
+pub struct HoverState {
+    cursor: Vec2,
+    resting_since: f32,
+    hit: Option<(BlockLayer, usize, String, String)>,
+    queried: bool,
+}
+
+pub fn hover_tooltip(
+    mut contexts: EguiContexts,
+    time: Res<Time>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<OrbitCameraController>>,
+    layers: Query<&BlockLayer, Without<CoarseLod>>,
+    block_models: Res<BlockModelDB>,
+    axes: Res<SceneAxes>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    mut state: Local<HoverState>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position() else {
+        state.hit = None;
+        return;
+    };
+
+    let now = time.elapsed_seconds();
+    if cursor.distance(state.cursor) > 1.0 {
+        state.cursor = cursor;
+        state.resting_since = now;
+        state.hit = None;
+        state.queried = false;
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    if ctx.wants_pointer_input() || now - state.resting_since < HOVER_DELAY {
+        return;
+    }
+
+    if !state.queried {
+        state.queried = true;
+
+        let Some((camera, camera_transform)) = cameras.iter().next() else {
+            return;
+        };
+        let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+            return;
+        };
+        let ray = axes.ray_to_model(&ray);
+
+        //blocks are looked up in the spatial index, so coarse patches only repeat layers
+        let mut drawn_layers = layers.iter().cloned().collect::<Vec<_>>();
+        drawn_layers.sort_by(|a, b| (&a.grid, &a.column).cmp(&(&b.grid, &b.column)));
+        drawn_layers.dedup();
+
+        let mut closest: Option<(f32, BlockLayer, usize, String, String)> = None;
+        for layer in drawn_layers {
+            let Some(bm) = block_models.block_models.get(&layer.grid) else {
+                continue;
+            };
+            let Ok(values) = bm.df.column(&layer.column) else {
+                continue;
+            };
+            let Some(grid) = spatial_index.grid(bm) else {
+                continue;
+            };
+
+            //blocks with a null value in the coloured column are not drawn
+            let hit = grid.raycast(&ray, |row| {
+                !matches!(values.get(row), Ok(AnyValue::Null) | Err(_))
+            });
+            if let Some((t, row)) = hit {
+                if closest.as_ref().map_or(true, |(best, ..)| t < *best) {
+                    let value = values.get(row).map(|v| v.to_string()).unwrap_or_default();
+                    let coords = [&bm.x, &bm.y, &bm.z]
+                        .iter()
+                        .map(|c| {
+                            bm.df
+                                .column(c.as_str())
+                                .and_then(|s| s.get(row))
+                                .map(|v| v.to_string())
+                                .unwrap_or_default()
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    closest = Some((t, layer.clone(), row, coords, value));
+                }
+            }
+        }
+
+        state.hit = closest.map(|(_, layer, row, coords, value)| (layer, row, coords, value));
+    }
+
+    if let Some((layer, row, coords, value)) = &state.hit {
+        egui::show_tooltip_at_pointer(ctx, egui::Id::new("block_hover"), |ui| {
+            ui.label(format!("{} [row {}]", layer.grid, row));
+            ui.label(coords.as_str());
+            ui.label(format!("{}: {}", layer.column, value));
+        });
+    }
+}
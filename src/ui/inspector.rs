@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{block_model::BlockModelDB, picking::Selection};
+
+pub fn inspector_panel(
+    mut contexts: EguiContexts,
+    block_models: Res<BlockModelDB>,
+    mut selection: ResMut<Selection>,
+) {
+    if selection.picks.is_empty() {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    let mut clear = false;
+
+    egui::Window::new("Block Inspector").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.label(format!("{} block(s) selected", selection.picks.len()));
+            if ui.button("Clear").clicked() {
+                clear = true;
+            }
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (i, pick) in selection.picks.iter().enumerate() {
+                let Some(bm) = block_models.block_models.get(&pick.layer.grid) else {
+                    continue;
+                };
+                let center = (pick.minimum + pick.maximum) / 2.0;
+
+                egui::CollapsingHeader::new(format!(
+                    "{}: {} [row {}]",
+                    pick.layer.grid, pick.layer.column, pick.row
+                ))
+                .id_source(("inspector", i))
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::Grid::new(("inspector_grid", i))
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("Layer");
+                            ui.label(format!("{} / {}", pick.layer.grid, pick.layer.column));
+                            ui.end_row();
+
+                            ui.label("Row");
+                            ui.label(pick.row.to_string());
+                            ui.end_row();
+
+                            ui.label("Centre");
+                            ui.label(format!("{:.2}, {:.2}, {:.2}", center.x, center.y, center.z));
+                            ui.end_row();
+
+                            ui.label("Min");
+                            ui.label(format!(
+                                "{:.2}, {:.2}, {:.2}",
+                                pick.minimum.x, pick.minimum.y, pick.minimum.z
+                            ));
+                            ui.end_row();
+
+                            ui.label("Max");
+                            ui.label(format!(
+                                "{:.2}, {:.2}, {:.2}",
+                                pick.maximum.x, pick.maximum.y, pick.maximum.z
+                            ));
+                            ui.end_row();
+
+                            for series in bm.df.get_columns() {
+                                ui.label(series.name());
+                                match series.get(pick.row) {
+                                    Ok(value) => ui.label(value.to_string()),
+                                    Err(_) => ui.label("-"),
+                                };
+                                ui.end_row();
+                            }
+                        });
+                });
+            }
+        });
+    });
+
+    if clear {
+        selection.picks.clear();
+    }
+}
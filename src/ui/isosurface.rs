@@ -0,0 +1,161 @@
+use bevy::{prelude::*, render::view::RenderLayers};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    axes::ModelSpace,
+    block_model::BlockModelDB,
+    isosurface::{extract, shell_material, IsoShell, IsoShells},
+    spatial_index::SpatialIndex,
+};
+
+use super::{block_model_combo, column_combo, OpenPanels};
+
+#[derive(Resource)]
+pub struct IsoSurfacePanel {
+    pub grid: String,
+    pub column: String,
+    pub threshold: f32,
+    pub color: [f32; 4],
+    pub error: Option<String>,
+}
+
+impl Default for IsoSurfacePanel {
+    fn default() -> Self {
+        Self {
+            grid: String::new(),
+            column: String::new(),
+            threshold: 0.5,
+            color: [0.9, 0.6, 0.1, 0.6],
+            error: None,
+        }
+    }
+}
+
+pub fn isosurface_panel(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    block_models: Res<BlockModelDB>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    mut shells: ResMut<IsoShells>,
+    mut panel: ResMut<IsoSurfacePanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.isosurface {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+
+    egui::Window::new("Grade Shells")
+        .open(&mut open_panels.isosurface)
+        .show(ctx, |ui| {
+            block_model_combo(ui, "iso_bm", &block_models, &mut panel.grid);
+            if let Some(bm) = block_models.block_models.get(&panel.grid) {
+                column_combo(ui, "Column", bm, &mut panel.column);
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut panel.threshold)
+                            .speed(0.01)
+                            .prefix("Threshold: "),
+                    );
+                    ui.color_edit_button_rgba_unmultiplied(&mut panel.color);
+                });
+
+                if ui.button("Extract").clicked() {
+                    let values = bm.column_f32(&panel.column);
+                    match (values, spatial_index.grid(bm)) {
+                        (Some(values), Some(grid)) => {
+                            let mesh = extract(grid, |row| values.get(row), panel.threshold);
+                            if mesh.indices.is_empty() {
+                                panel.error = Some("No blocks above the threshold".into());
+                            } else {
+                                let material = materials.add(shell_material(panel.color));
+                                let entity = commands
+                                    .spawn((
+                                        PbrBundle {
+                                            mesh: meshes.add(mesh.to_mesh()),
+                                            material: material.clone(),
+                                            ..default()
+                                        },
+                                        RenderLayers::layer(0),
+                                        ModelSpace,
+                                    ))
+                                    .id();
+                                shells.shells.push(IsoShell {
+                                    name: format!(
+                                        "{}: {} ≥ {}",
+                                        panel.grid, panel.column, panel.threshold
+                                    ),
+                                    mesh,
+                                    color: panel.color,
+                                    visible: true,
+                                    entity,
+                                    material,
+                                });
+                                panel.error = None;
+                            }
+                        }
+                        _ => {
+                            panel.error = Some("Column is missing or not numeric".into());
+                        }
+                    }
+                }
+            }
+
+            if let Some(error) = &panel.error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+
+            ui.separator();
+            let mut remove = None;
+            for (i, shell) in shells.shells.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.checkbox(&mut shell.visible, &shell.name).changed() {
+                        commands.entity(shell.entity).insert(if shell.visible {
+                            Visibility::Inherited
+                        } else {
+                            Visibility::Hidden
+                        });
+                    }
+                    if ui
+                        .color_edit_button_rgba_unmultiplied(&mut shell.color)
+                        .changed()
+                    {
+                        if let Some(material) = materials.get_mut(&shell.material) {
+                            *material = shell_material(shell.color);
+                        }
+                    }
+                    ui.label(format!("{} triangles", shell.mesh.triangle_count()));
+                    if ui.button("OBJ").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("obj", &["obj"])
+                            .save_file()
+                        {
+                            if let Err(e) = shell.mesh.write_obj(&path) {
+                                panel.error = Some(format!("Export failed: {}", e));
+                            }
+                        }
+                    }
+                    if ui.button("STL").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("stl", &["stl"])
+                            .save_file()
+                        {
+                            if let Err(e) = shell.mesh.write_stl(&path) {
+                                panel.error = Some(format!("Export failed: {}", e));
+                            }
+                        }
+                    }
+                    if ui.small_button("✖").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                let shell = shells.shells.remove(i);
+                commands.entity(shell.entity).despawn_recursive();
+            }
+        });
+}
@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::jobs::Jobs;
+
+use super::OpenPanels;
+
+/// Lists running jobs with their progress and a cancel button, then the finished ones.
+/// Opens by itself when a job is started.
+pub fn jobs_panel(
+    mut contexts: EguiContexts,
+    mut jobs: ResMut<Jobs>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if jobs.is_changed() && !jobs.running.is_empty() {
+        open_panels.jobs = true;
+    }
+    if !open_panels.jobs {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+
+    egui::Window::new("Jobs")
+        .open(&mut open_panels.jobs)
+        .show(ctx, |ui| {
+            if jobs.running.is_empty() && jobs.finished.is_empty() {
+                ui.label("No jobs have been run");
+                return;
+            }
+
+            egui::Grid::new("jobs_running")
+                .striped(true)
+                .show(ui, |ui| {
+                    for job in jobs.running.iter() {
+                        let progress = job.control.progress();
+                        ui.spinner();
+                        ui.strong(job.name.as_str());
+                        ui.label(match progress.part {
+                            Some((part, parts)) => {
+                                format!("{} ({}/{})", progress.stage, part, parts)
+                            }
+                            None => progress.stage.clone(),
+                        });
+                        ui.label(format!("iteration {}", progress.iteration));
+                        ui.label(
+                            progress
+                                .objective
+                                .map_or("-".into(), |o| format!("objective {:.0}", o)),
+                        );
+                        ui.label(format!("{:.1} s", job.started.elapsed().as_secs_f32()));
+                        if job.control.is_cancelled() {
+                            ui.label("cancelling…");
+                        } else if ui.button("Cancel").clicked() {
+                            job.control.cancel();
+                        }
+                        ui.end_row();
+                    }
+                });
+            //progress is polled, so keep repainting while anything runs
+            if !jobs.running.is_empty() {
+                ui.ctx().request_repaint();
+            }
+
+            if jobs.finished.is_empty() {
+                return;
+            }
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("jobs_finished")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for job in jobs.finished.iter().rev() {
+                                ui.strong(job.name.as_str());
+                                ui.label(format!("{:.1} s", job.elapsed.as_secs_f32()));
+                                match &job.outcome {
+                                    Ok(message) => ui.label(message.as_str()),
+                                    Err(e) => ui.colored_label(egui::Color32::RED, e.as_str()),
+                                };
+                                ui.end_row();
+                            }
+                        });
+                });
+            if ui.button("Clear Finished").clicked() {
+                jobs.finished.clear();
+            }
+        });
+}
@@ -0,0 +1,320 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    block_model::{BlockModel, BlockModelDB},
+    economics::EconomicModel,
+    lane::{lane_schedule, LaneYear},
+    optimizer::OptimizeParams,
+    stats::StatsCache,
+};
+
+use super::{block_model_combo, economics::EconomicsPanel, OpenPanels};
+
+#[derive(Resource, Default)]
+pub struct LanePanel {
+    /// Only blocks flagged by the pit column count towards the reserve.
+    pub in_pit_only: bool,
+    pub schedule: Vec<LaneYear>,
+    /// Year whose cutoff is applied to every block when there is no period column, from 0.
+    pub year: usize,
+    pub error: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Tonnes and grades of the reserve, optionally restricted to the blocks in the pit.
+fn reserve(
+    bm: &BlockModel,
+    params: &OptimizeParams,
+    in_pit_only: bool,
+) -> Result<(Vec<f64>, Vec<f64>), String> {
+    let economics = EconomicModel::new(bm, params)?;
+    if !in_pit_only {
+        return Ok((economics.tonnes, economics.grades));
+    }
+    let in_pit = bm
+        .df
+        .column(&params.pit_col)
+        .and_then(|c| c.bool().cloned())
+        .map_err(|_| format!("No pit flag column {}", params.pit_col))?;
+    let tonnes = economics
+        .tonnes
+        .iter()
+        .zip(in_pit.into_iter())
+        .map(|(t, mined)| if mined == Some(true) { *t } else { 0.0 })
+        .collect();
+    Ok((tonnes, economics.grades))
+}
+
+/// Cutoff of the year each block is mined in, from the periods in `period_col`. Blocks
+/// that are not scheduled, or are mined after the last year, get the last year's cutoff.
+fn cutoffs_by_period(
+    bm: &BlockModel,
+    schedule: &[LaneYear],
+    period_col: &str,
+) -> Result<Vec<f64>, String> {
+    let periods = bm
+        .column_f32(period_col)
+        .ok_or_else(|| format!("Period column {} is not numeric", period_col))?;
+    let last = schedule.len() - 1;
+    Ok(periods
+        .into_iter()
+        .map(|period| {
+            let year = period.map_or(last, |p| (p.max(1.0) as usize - 1).min(last));
+            schedule[year].cutoff
+        })
+        .collect())
+}
+
+pub fn lane_panel(
+    mut contexts: EguiContexts,
+    mut block_models: ResMut<BlockModelDB>,
+    mut stats_cache: ResMut<StatsCache>,
+    mut params: ResMut<OptimizeParams>,
+    mut economics_panel: ResMut<EconomicsPanel>,
+    mut panel: ResMut<LanePanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.lane {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+    let params = &mut *params;
+
+    egui::Window::new("Cutoff Optimisation")
+        .open(&mut open_panels.lane)
+        .show(ctx, |ui| {
+            block_model_combo(ui, "lane_bm", &block_models, &mut params.grid);
+            ui.checkbox(
+                &mut panel.in_pit_only,
+                format!("Blocks in pit only ({})", params.pit_col),
+            );
+            egui::Grid::new("lane_inputs").show(ui, |ui| {
+                ui.label("Grade column");
+                ui.text_edit_singleline(&mut params.grade_col);
+                ui.end_row();
+                ui.label("Tonnage column");
+                ui.text_edit_singleline(&mut params.tonnage_col);
+                ui.end_row();
+                for (label, value) in [
+                    ("Mining rate (t/yr)", &mut params.mining_rate),
+                    ("Processing capacity (t/yr)", &mut params.proc_cap),
+                    (
+                        "Market limit (metal/yr, 0 for none)",
+                        &mut params.market_cap,
+                    ),
+                    ("Minimum life (yr)", &mut params.min_life),
+                ] {
+                    ui.label(label);
+                    ui.add(egui::DragValue::new(value).clamp_range(0.0..=f32::MAX));
+                    ui.end_row();
+                }
+                ui.label("Discount rate");
+                ui.add(
+                    egui::DragValue::new(&mut params.discount_rate)
+                        .speed(0.005)
+                        .clamp_range(0.0..=1.0),
+                );
+                ui.end_row();
+                ui.label("Iterations");
+                ui.add(egui::DragValue::new(&mut params.num_iters).clamp_range(1..=1000));
+                ui.end_row();
+            });
+            ui.label("Price and costs are set in Block Economics");
+
+            if ui.button("Optimise Cutoffs").clicked() {
+                let result = block_models
+                    .block_models
+                    .get(&params.grid)
+                    .ok_or_else(|| "Select a block model".to_string())
+                    .and_then(|bm| reserve(bm, params, panel.in_pit_only))
+                    .and_then(|(tonnes, grades)| lane_schedule(&tonnes, &grades, params));
+                match result {
+                    Ok(schedule) => {
+                        panel.schedule = schedule;
+                        panel.year = 0;
+                        panel.error = None;
+                    }
+                    Err(e) => panel.error = Some(e),
+                }
+            }
+            if let Some(error) = &panel.error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+            if panel.schedule.is_empty() {
+                return;
+            }
+
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("lane_schedule")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for header in [
+                                "Year",
+                                "Cutoff",
+                                "Limit",
+                                "Mined",
+                                "Processed",
+                                "Grade",
+                                "Metal",
+                                "Cash flow",
+                                "NPV",
+                            ] {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+                            for (i, year) in panel.schedule.iter().enumerate() {
+                                ui.label((i + 1).to_string());
+                                ui.label(format!("{:.4}", year.cutoff));
+                                ui.label(year.limit.name());
+                                ui.label(format!("{:.0}", year.mined));
+                                ui.label(format!("{:.0}", year.processed));
+                                ui.label(format!("{:.4}", year.grade));
+                                ui.label(format!("{:.1}", year.metal));
+                                ui.label(format!("{:.0}", year.cash_flow));
+                                ui.label(format!("{:.0}", year.npv));
+                                ui.end_row();
+                            }
+                        });
+                });
+
+            ui.horizontal(|ui| {
+                ui.label("Period column");
+                ui.text_edit_singleline(&mut params.period_col);
+            });
+            let has_periods = block_models
+                .block_models
+                .get(&params.grid)
+                .map_or(false, |bm| bm.df.column(&params.period_col).is_ok());
+
+            //reclassify destinations as Block Economics does, leaving its cutoff as it is
+            let mut apply = None;
+            if has_periods {
+                ui.label("Each block is classified with the cutoff of the year it is mined in");
+                if ui.button("Apply Cutoffs to Destinations").clicked() {
+                    apply = Some(None);
+                }
+            } else {
+                ui.label(format!(
+                    "No period column {}: one year's cutoff is applied to every block. \
+                     Schedule the pit to classify blocks by the year they are mined in.",
+                    params.period_col
+                ));
+                ui.horizontal(|ui| {
+                    ui.label("Year");
+                    let years = panel.schedule.len();
+                    let mut year = panel.year + 1;
+                    ui.add(egui::DragValue::new(&mut year).clamp_range(1..=years));
+                    panel.year = year.clamp(1, years) - 1;
+                    if ui.button("Apply Cutoff to Destinations").clicked() {
+                        apply = Some(Some(panel.year));
+                    }
+                });
+            }
+            if let Some(year) = apply {
+                let result = block_models
+                    .block_models
+                    .get_mut(&params.grid)
+                    .ok_or_else(|| "Select a block model".to_string())
+                    .and_then(|bm| {
+                        let economics = match year {
+                            Some(year) => {
+                                let cutoff = panel.schedule[year].cutoff;
+                                EconomicModel::with_cutoffs(bm, params, |_| cutoff)?
+                            }
+                            None => {
+                                let cutoffs =
+                                    cutoffs_by_period(bm, &panel.schedule, &params.period_col)?;
+                                EconomicModel::with_cutoffs(bm, params, |row| cutoffs[row])?
+                            }
+                        };
+                        economics_panel.write_model(bm, &economics, params)
+                    });
+                match result {
+                    Ok(()) => {
+                        stats_cache.invalidate(&params.grid);
+                        panel.message = Some(match year {
+                            Some(year) => format!(
+                                "Destinations use the year {} cutoff {:.4}",
+                                year + 1,
+                                panel.schedule[year].cutoff
+                            ),
+                            None => format!(
+                                "Destinations use the cutoff of the year in {}",
+                                params.period_col
+                            ),
+                        });
+                        panel.error = None;
+                    }
+                    Err(e) => panel.error = Some(e),
+                }
+            }
+            if let Some(message) = &panel.message {
+                ui.label(message.as_str());
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    use super::*;
+    use crate::lane::Limit;
+
+    fn year(cutoff: f64) -> LaneYear {
+        LaneYear {
+            cutoff,
+            limit: Limit::Mine,
+            mined: 0.0,
+            processed: 0.0,
+            grade: 0.0,
+            metal: 0.0,
+            cash_flow: 0.0,
+            npv: 0.0,
+        }
+    }
+
+    #[test]
+    fn blocks_get_the_cutoff_of_their_period() {
+        let df = DataFrame::new(vec![Series::new(
+            "period",
+            &[Some(0u32), Some(1), Some(2), Some(3), None],
+        )])
+        .unwrap();
+        let bm = BlockModel::new(
+            "bm".into(),
+            df,
+            "x".into(),
+            "y".into(),
+            "z".into(),
+            "dx".into(),
+            "dy".into(),
+            "dz".into(),
+        );
+        let schedule = [year(0.5), year(0.3)];
+        //period 0 is clamped to the first year; later and unscheduled blocks get the last
+        let cutoffs = cutoffs_by_period(&bm, &schedule, "period").unwrap();
+        assert_eq!(cutoffs, vec![0.5, 0.5, 0.3, 0.3, 0.3]);
+    }
+
+    #[test]
+    fn missing_period_column_is_an_error() {
+        let df = DataFrame::new(vec![Series::new("grade", &[1.0f32])]).unwrap();
+        let bm = BlockModel::new(
+            "bm".into(),
+            df,
+            "x".into(),
+            "y".into(),
+            "z".into(),
+            "dx".into(),
+            "dy".into(),
+            "dz".into(),
+        );
+        assert!(cutoffs_by_period(&bm, &[year(0.5)], "period").is_err());
+    }
+}
@@ -1,5 +1,4 @@
-use bevy::{prelude::*, render::view::RenderLayers, utils::HashMap};
-use bevy_aabb_instancing::{CuboidMaterial, CuboidMaterialMap, COLOR_MODE_RGB};
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
 use bevy_egui::{
     egui::{self, Widget},
     EguiContexts,
@@ -7,15 +6,175 @@ use bevy_egui::{
 use itertools::izip;
 use polars::prelude::{CsvReader, SerReader};
 
+pub mod camera;
+pub mod drillholes;
+pub mod economics;
+pub mod export;
+pub mod grade_tonnage;
+pub mod hover;
+pub mod inspector;
+pub mod isosurface;
+pub mod jobs;
+pub mod lane;
+pub mod playback;
+pub mod shells;
+pub mod solid;
+pub mod stats;
+pub mod surface;
+pub mod swath;
+pub mod wireframe;
+
 use crate::{
-    block_model::{BlockModel, BlockModelDB, BlockModelResource},
-    optimizer::OptimizeParams,
+    axes::{AxisConvention, SceneAxes},
+    block_model::{BlockLayer, BlockModel, BlockModelDB, BlockModelResource},
+    camera::{CameraBookmarks, CameraView, ProjectionSettings, StandardView},
+    jobs::{JobOutput, Jobs, PIT_JOB, SCHEDULE_JOB},
+    layers::{LayerEvent, LayerStyle, LayerStyles},
+    optimizer::{OptimizeParams, Optimizer},
+    pit::ultimate_pit,
+    project::{Project, ProjectFile},
+    reference::ReferenceFrame,
+    spatial_index::SpatialIndex,
+    stats::StatsCache,
     AppState, ColorBarSelectionEvent,
 };
 
 #[derive(Event)]
 pub struct ViewAll;
 
+/// Columns ticked for drawing in the side panel, per block model in column order.
+#[derive(Default, Resource)]
+pub struct CheckedColumns {
+    pub columns: HashMap<String, Vec<bool>>,
+}
+
+impl CheckedColumns {
+    /// Ticks `column` of `bm`, for tools that draw a column they have just written.
+    pub fn check(&mut self, bm: &BlockModel, column: &str) {
+        let checked = self.columns.entry(bm.name.clone()).or_default();
+        checked.resize(bm.columns.len(), false);
+        if let Some(i) = bm.columns.iter().position(|c| c == column) {
+            checked[i] = true;
+        }
+    }
+}
+
+/// Open/closed state of the tool windows reachable from the top menu.
+#[derive(Default, Resource)]
+pub struct OpenPanels {
+    pub stats: bool,
+    pub grade_tonnage: bool,
+    pub swath: bool,
+    pub isosurface: bool,
+    pub export: bool,
+    pub camera: bool,
+    pub surface: bool,
+    pub drillholes: bool,
+    pub solid: bool,
+    pub economics: bool,
+    pub lane: bool,
+    pub jobs: bool,
+    pub shells: bool,
+    pub playback: bool,
+}
+
+/// Combo box over the loaded block models. Returns true if the selection changed.
+pub fn block_model_combo(
+    ui: &mut egui::Ui,
+    id: &str,
+    block_models: &BlockModelDB,
+    selected: &mut String,
+) -> bool {
+    let before = selected.clone();
+    egui::ComboBox::new(id, "Blockmodel")
+        .selected_text(selected.clone())
+        .show_ui(ui, |ui| {
+            for name in block_models.block_models.keys() {
+                ui.selectable_value(selected, name.clone(), name.clone());
+            }
+        });
+    *selected != before
+}
+
+/// Combo box over the columns of `bm`. Returns true if the selection changed.
+pub fn column_combo(
+    ui: &mut egui::Ui,
+    label: &str,
+    bm: &BlockModel,
+    selected: &mut String,
+) -> bool {
+    let before = selected.clone();
+    egui::ComboBox::from_label(label)
+        .selected_text(selected.clone())
+        .show_ui(ui, |ui| {
+            for col in bm.columns.iter() {
+                ui.selectable_value(selected, col.clone(), col.clone());
+            }
+        });
+    *selected != before
+}
+
+/// Like [`column_combo`], with an extra "None" entry.
+pub fn optional_column_combo(
+    ui: &mut egui::Ui,
+    label: &str,
+    bm: &BlockModel,
+    selected: &mut Option<String>,
+) -> bool {
+    let before = selected.clone();
+    egui::ComboBox::from_label(label)
+        .selected_text(selected.clone().unwrap_or_else(|| "None".to_string()))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(selected, None, "None");
+            for col in bm.columns.iter() {
+                ui.selectable_value(selected, Some(col.clone()), col.clone());
+            }
+        });
+    *selected != before
+}
+
+/// Display controls for a displayed layer. Returns true once an edit is complete and
+/// the layer needs rebuilding.
+pub fn layer_style_ui(
+    ui: &mut egui::Ui,
+    layer: &BlockLayer,
+    style: &mut LayerStyle,
+    range: Option<(f64, f64)>,
+) -> bool {
+    let mut rebuild = false;
+    let committed = |response: egui::Response| {
+        response.drag_released() || (response.changed() && !response.dragged())
+    };
+
+    egui::CollapsingHeader::new("Display")
+        .id_source(("layer_style", &layer.grid, &layer.column))
+        .show(ui, |ui| {
+            rebuild |= ui
+                .checkbox(&mut style.cull_hidden, "Cull hidden blocks")
+                .changed();
+            rebuild |=
+                committed(ui.add(egui::Slider::new(&mut style.opacity, 0.0..=1.0).text("Opacity")));
+
+            let mut ramp = style.ramp.is_some();
+            if ui.checkbox(&mut ramp, "Ramp by value").changed() {
+                style.ramp = ramp.then(|| {
+                    let (min, max) = range.unwrap_or((0.0, 1.0));
+                    (min, (min + max) / 2.0)
+                });
+                rebuild = true;
+            }
+            if let Some((low, high)) = style.ramp.as_mut() {
+                ui.horizontal(|ui| {
+                    rebuild |=
+                        committed(ui.add(egui::DragValue::new(low).prefix("Transparent ≤ ")));
+                    rebuild |= committed(ui.add(egui::DragValue::new(high).prefix("Opaque ≥ ")));
+                });
+            }
+        });
+
+    rebuild
+}
+
 #[derive(Default, Resource)]
 pub struct OccupiedScreenSpace {
     pub left: f32,
@@ -24,18 +183,77 @@ pub struct OccupiedScreenSpace {
     pub bottom: f32,
 }
 
+/// Scene-wide display settings edited from the View menu.
+#[derive(SystemParam)]
+pub struct ViewSettings<'w, 's> {
+    pub projection: ResMut<'w, ProjectionSettings>,
+    pub axes: ResMut<'w, SceneAxes>,
+    pub reference: ResMut<'w, ReferenceFrame>,
+    pub bookmarks: ResMut<'w, CameraBookmarks>,
+    pub project: ResMut<'w, ProjectFile>,
+    /// Vertical exaggeration being dragged, applied to `axes` once released.
+    dragged_exaggeration: Local<'s, Option<f32>>,
+}
+
+impl ViewSettings<'_, '_> {
+    fn project_dialog(&self) -> rfd::FileDialog {
+        let dialog = rfd::FileDialog::new().add_filter("project", &["json"]);
+        match self.project.path.as_ref().and_then(|p| p.parent()) {
+            Some(dir) => dialog.set_directory(dir),
+            None => dialog,
+        }
+    }
+
+    pub fn save_project(&mut self) {
+        let Some(path) = self.project_dialog().save_file() else {
+            return;
+        };
+        let project = Project {
+            axes: *self.axes,
+            projection: *self.projection,
+            bookmarks: self.bookmarks.bookmarks.clone(),
+        };
+        self.project.error = project
+            .save(&path)
+            .err()
+            .map(|e| format!("Cannot save project: {}", e));
+        self.project.path = Some(path);
+    }
+
+    pub fn open_project(&mut self) {
+        let Some(path) = self.project_dialog().pick_file() else {
+            return;
+        };
+        match Project::load(&path) {
+            Ok(project) => {
+                *self.axes = project.axes;
+                *self.projection = project.projection;
+                self.bookmarks.bookmarks = project.bookmarks;
+                self.project.error = None;
+                self.project.path = Some(path);
+            }
+            Err(e) => self.project.error = Some(format!("Cannot open project: {}", e)),
+        }
+    }
+}
+
 pub fn ui_system(
     mut contexts: EguiContexts,
     block_models: Res<BlockModelDB>,
     mut selected: Local<String>,
-    mut checked: Local<HashMap<String, (Vec<bool>, Vec<Vec<Entity>>)>>,
-    mut commands: Commands,
-    mut material_map: ResMut<CuboidMaterialMap>,
+    mut checked: ResMut<CheckedColumns>,
+    mut layer_events: EventWriter<LayerEvent>,
+    mut layer_styles: ResMut<LayerStyles>,
     mut next_state: ResMut<NextState<AppState>>,
     mut file_dnd: ResMut<FileInputResource>,
     mut event_writer: EventWriter<ViewAll>,
     mut occupied_screen_space: ResMut<OccupiedScreenSpace>,
     mut colorbar_event_writer: EventWriter<ColorBarSelectionEvent>,
+    mut open_panels: ResMut<OpenPanels>,
+    mut stats_cache: ResMut<StatsCache>,
+    mut camera_events: EventWriter<CameraView>,
+    mut view_settings: ViewSettings,
+    mut wireframes: wireframe::WireframeLayers,
 ) {
     let ctx = contexts.ctx_mut();
 
@@ -50,6 +268,126 @@ pub fn ui_system(
                             next_state.set(AppState::FileInput);
                         }
                     }
+                    ui.separator();
+                    if ui.button("Open Project…").clicked() {
+                        view_settings.open_project();
+                        ui.close_menu();
+                    }
+                    if ui.button("Save Project…").clicked() {
+                        view_settings.save_project();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Import Wireframe…").clicked() {
+                        wireframes.pick_and_import();
+                        ui.close_menu();
+                    }
+                    if ui.button("Export Image…").clicked() {
+                        open_panels.export = true;
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("View", |ui| {
+                    for view in StandardView::ALL {
+                        if ui.button(view.name()).clicked() {
+                            camera_events.send(CameraView::Standard(view));
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    ui.checkbox(&mut view_settings.projection.orthographic, "Orthographic");
+                    //SceneAxes is only written on an actual change, every change rebuilds the layers
+                    let mut convention = view_settings.axes.convention;
+                    ui.horizontal(|ui| {
+                        for option in [AxisConvention::ZUp, AxisConvention::YUp] {
+                            ui.radio_value(&mut convention, option, option.name());
+                        }
+                    });
+                    if convention != view_settings.axes.convention {
+                        view_settings.axes.convention = convention;
+                    }
+                    let mut exaggeration = view_settings
+                        .dragged_exaggeration
+                        .unwrap_or(view_settings.axes.exaggeration);
+                    let response = ui.add(
+                        egui::DragValue::new(&mut exaggeration)
+                            .speed(0.05)
+                            .clamp_range(0.1..=20.0)
+                            .prefix("Vertical exaggeration: "),
+                    );
+                    if response.dragged() {
+                        *view_settings.dragged_exaggeration = Some(exaggeration);
+                    } else {
+                        *view_settings.dragged_exaggeration = None;
+                        if exaggeration != view_settings.axes.exaggeration {
+                            view_settings.axes.exaggeration = exaggeration;
+                        }
+                    }
+                    ui.separator();
+                    let reference = &mut *view_settings.reference;
+                    ui.checkbox(&mut reference.triad, "Axis triad");
+                    ui.checkbox(&mut reference.grid, "Bounding box grid");
+                    ui.checkbox(&mut reference.north_arrow, "North arrow");
+                    ui.checkbox(&mut reference.scale_bar, "Scale bar");
+                    ui.separator();
+                    if ui.button("Bookmarks…").clicked() {
+                        open_panels.camera = true;
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("Tools", |ui| {
+                    if ui.button("Statistics").clicked() {
+                        open_panels.stats = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Grade–Tonnage").clicked() {
+                        open_panels.grade_tonnage = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Swath Plot").clicked() {
+                        open_panels.swath = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Grade Shells").clicked() {
+                        open_panels.isosurface = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Topography").clicked() {
+                        open_panels.surface = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Drillholes").clicked() {
+                        open_panels.drillholes = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Inside Solid").clicked() {
+                        open_panels.solid = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Block Economics").clicked() {
+                        open_panels.economics = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Cutoff Optimisation").clicked() {
+                        open_panels.lane = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Pit Optimisation").clicked() {
+                        next_state.set(AppState::OptimizeInit);
+                        ui.close_menu();
+                    }
+                    if ui.button("Pit Shells").clicked() {
+                        open_panels.shells = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Schedule Playback").clicked() {
+                        open_panels.playback = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Jobs").clicked() {
+                        open_panels.jobs = true;
+                        ui.close_menu();
+                    }
                 });
             });
         })
@@ -63,9 +401,14 @@ pub fn ui_system(
 
     let bottom = egui::TopBottomPanel::bottom("Bottom panel")
         .show(ctx, |ui| {
-            if ui.button("View All").clicked() {
-                event_writer.send(ViewAll);
-            }
+            ui.horizontal(|ui| {
+                if ui.button("View All").clicked() {
+                    event_writer.send(ViewAll);
+                }
+                if let Some(error) = &view_settings.project.error {
+                    ui.colored_label(egui::Color32::RED, error.as_str());
+                }
+            });
         })
         .response
         .rect
@@ -88,76 +431,80 @@ pub fn ui_system(
             ui.heading("Columns");
             ui.separator();
             if *selected != "" {
-                let (ref mut checked, ref mut entities) =
-                    checked.entry(selected.clone()).or_insert_with(|| {
-                        (
-                            block_models
-                                .block_models
-                                .get(&*selected)
-                                .unwrap()
-                                .columns
-                                .iter()
-                                .map(|_| false)
-                                .collect::<Vec<_>>(),
-                            vec![
-                                Vec::new();
-                                block_models
-                                    .block_models
-                                    .get(&*selected)
-                                    .unwrap()
-                                    .columns
-                                    .len()
-                            ],
-                        )
-                    });
+                let checked = checked.columns.entry(selected.clone()).or_insert_with(|| {
+                    block_models
+                        .block_models
+                        .get(&*selected)
+                        .unwrap()
+                        .columns
+                        .iter()
+                        .map(|_| false)
+                        .collect::<Vec<_>>()
+                });
+                //columns may have been added since, e.g. by flagging against a surface
+                checked.resize(
+                    block_models
+                        .block_models
+                        .get(&*selected)
+                        .unwrap()
+                        .columns
+                        .len(),
+                    false,
+                );
 
-                for (col, mut check, ents) in izip!(
+                for (col, mut check) in izip!(
                     block_models
                         .block_models
                         .get(&*selected)
                         .unwrap()
                         .columns
                         .iter(),
-                    checked.iter_mut(),
-                    entities.iter_mut()
+                    checked.iter_mut()
                 ) {
+                    let layer = BlockLayer {
+                        grid: selected.clone(),
+                        column: col.clone(),
+                    };
                     //ui.label(col);
                     if ui.checkbox(&mut check, col).changed() {
+                        let bm = block_models.block_models.get(&*selected).unwrap();
+                        if *check && stats_cache.get(bm, col).is_none() {
+                            //non-numeric columns cannot be coloured
+                            *check = false;
+                            continue;
+                        }
                         if *check == true {
                             //draw bm
-                            let cuboids_abbb = block_models
-                                .block_models
-                                .get(&*selected)
-                                .unwrap()
-                                .aabb_instances(col.clone(), colorgrad::turbo(), 22500);
-
-                            let material_id = material_map.push(CuboidMaterial {
-                                color_mode: COLOR_MODE_RGB,
-                                ..default()
-                            });
-                            for (cuboids, aabb) in cuboids_abbb.into_iter() {
-                                let mut ent = commands.spawn(SpatialBundle::default());
-                                ent.insert((cuboids, aabb, material_id, RenderLayers::layer(0)));
-
-                                ents.push(ent.id());
-                            }
-                            colorbar_event_writer.send(ColorBarSelectionEvent {
-                                grid: selected.clone(),
-                                column: col.clone(),
-                            });
+                            layer_events.send(LayerEvent::Spawn(layer.clone()));
                         } else {
                             //erase bm
-                            ents.drain(..).for_each(|ent| {
-                                commands.entity(ent).despawn_recursive();
-                            });
-                            colorbar_event_writer.send(ColorBarSelectionEvent {
-                                grid: selected.clone(),
-                                column: col.clone(),
-                            });
+                            layer_events.send(LayerEvent::Despawn(layer.clone()));
+                        }
+                        colorbar_event_writer.send(ColorBarSelectionEvent {
+                            grid: selected.clone(),
+                            column: col.clone(),
+                        });
+                    }
+
+                    if *check {
+                        let bm = block_models.block_models.get(&*selected).unwrap();
+                        let range = stats_cache.get(bm, col).map(|s| (s.min, s.max));
+                        //only stored once edited, so LayerStyles is not changed every frame
+                        let before = layer_styles.styles.get(&layer).copied().unwrap_or_default();
+                        let mut style = before;
+                        let rebuild = layer_style_ui(ui, &layer, &mut style, range);
+                        if style != before {
+                            layer_styles.styles.insert(layer.clone(), style);
+                        }
+                        if rebuild {
+                            layer_events.send(LayerEvent::Spawn(layer));
                         }
                     }
                 }
             }
+
+            ui.add_space(8.0);
+            wireframes.ui(ui);
         })
         .response
         .rect
@@ -280,13 +627,15 @@ pub fn init_optimizer(
     mut optimizer_init_data: ResMut<OptimizeParams>,
     mut contexts: EguiContexts,
     mut next_state: ResMut<NextState<AppState>>,
-    mut bm_res: ResMut<BlockModelResource>,
-    mut bm_db: ResMut<BlockModelDB>,
+    bm_db: Res<BlockModelDB>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    mut jobs: ResMut<Jobs>,
 ) {
     let ctx = contexts.ctx_mut();
     let window = egui::Window::new("Optimization Parameters");
     window.show(ctx, |ui| {
         ui.label("Optimization Parameters");
+        block_model_combo(ui, "optimizer_bm", &bm_db, &mut optimizer_init_data.grid);
 
         egui::Grid::new("some_unique_id").show(ui, |ui| {
             ui.label("Grade Column");
@@ -305,25 +654,130 @@ pub fn init_optimizer(
             ui.add(egui::DragValue::new(&mut optimizer_init_data.mining_rate));
             ui.end_row();
 
+            ui.label("Metal Price");
+            ui.label("Mining Cost");
+            ui.label("Processing Cost");
+            ui.label("Slope Angle");
+            ui.label("Pit Column");
+            ui.end_row();
+
+            ui.add(egui::DragValue::new(&mut optimizer_init_data.metal_price));
+            ui.add(egui::DragValue::new(&mut optimizer_init_data.mining_cost));
+            ui.add(egui::DragValue::new(
+                &mut optimizer_init_data.processing_cost,
+            ));
+            ui.add(
+                egui::DragValue::new(&mut optimizer_init_data.slope_angle)
+                    .clamp_range(1.0..=89.0)
+                    .suffix("°"),
+            );
+            ui.text_edit_singleline(&mut optimizer_init_data.pit_col);
+            ui.end_row();
+
+            ui.label("Discount Rate");
+            ui.label("Iterations");
+            ui.label("Period Column");
+            ui.label("Refresh Every");
+            ui.label("Mining Width");
+            ui.end_row();
+
+            ui.add(
+                egui::DragValue::new(&mut optimizer_init_data.discount_rate)
+                    .speed(0.005)
+                    .clamp_range(0.0..=1.0),
+            );
+            ui.add(egui::DragValue::new(&mut optimizer_init_data.num_iters).clamp_range(1..=1000));
+            ui.text_edit_singleline(&mut optimizer_init_data.period_col);
+            ui.add(
+                egui::DragValue::new(&mut optimizer_init_data.refresh_every)
+                    .clamp_range(0..=1000)
+                    .suffix(" sweeps"),
+            )
+            .on_hover_text("Redraw the period column while scheduling, 0 for only at the end");
+            ui.add(
+                egui::DragValue::new(&mut optimizer_init_data.mining_width)
+                    .clamp_range(0.0..=f32::MAX)
+                    .suffix(" m"),
+            )
+            .on_hover_text("Narrowest opening scheduled, 0 for a single block");
+            ui.end_row();
+
             ui.label(""); // spacing
-            if ui.button("Optimize").clicked() {
-                // let bm = bm_res.block_model.take().unwrap();
-                // let bm = bm.optimize(
-                //     optimizer_init_data.grade_col.clone(),
-                //     optimizer_init_data.tonnage_col.clone(),
-                //     optimizer_init_data.cutoff,
-                //     optimizer_init_data.min_life,
-                //     optimizer_init_data.proc_cap,
-                //     optimizer_init_data.mining_rate,
-                // );
-
-                // *bm_res = BlockModelResource {
-                //     block_model: Some(bm.clone()),
-                // };
-
-                // bm_db.block_models.insert(bm.name.clone(), bm);
+            let running = jobs.is_running(PIT_JOB);
+            if ui
+                .add_enabled(!running, egui::Button::new("Optimize"))
+                .on_disabled_hover_text("Already running, see Tools > Jobs")
+                .clicked()
+            {
+                let params = optimizer_init_data.clone();
+                match bm_db.block_models.get(&params.grid) {
+                    //the job works on copies so the viewer stays usable meanwhile
+                    Some(bm) => match spatial_index.grid(bm) {
+                        Some(grid) => {
+                            let (bm, grid) = (bm.clone(), grid.clone());
+                            jobs.last_pit = None;
+                            jobs.spawn(PIT_JOB, move |control| {
+                                let (flags, summary) =
+                                    ultimate_pit(&bm, &grid, &params, &params.pit_col, control)?;
+                                Ok(JobOutput::Pit {
+                                    grid: params.grid.clone(),
+                                    flags,
+                                    summary,
+                                })
+                            });
+                        }
+                        None => {
+                            jobs.last_pit = Some(Err("Block coordinates are not numeric".into()))
+                        }
+                    },
+                    None => jobs.last_pit = Some(Err("Select a block model".into())),
+                }
+            }
 
-                next_state.set(AppState::Running);
+            //schedules the blocks of the pit written by Optimize into periods
+            let running = jobs.is_running(SCHEDULE_JOB);
+            if ui
+                .add_enabled(!running, egui::Button::new("Schedule"))
+                .on_disabled_hover_text("Already running, see Tools > Jobs")
+                .clicked()
+            {
+                let params = optimizer_init_data.clone();
+                match bm_db.block_models.get(&params.grid) {
+                    Some(bm) => match spatial_index.grid(bm) {
+                        Some(grid) => {
+                            let (bm, grid) = (bm.clone(), grid.clone());
+                            jobs.spawn(SCHEDULE_JOB, move |control| {
+                                let mut optimizer = Optimizer::new(&bm, &grid, &params, control)?;
+                                let npv = optimizer.run(control, |sched| {
+                                    let periods = sched.periods(&grid.dims);
+                                    control.publish(JobOutput::Schedule {
+                                        grid: params.grid.clone(),
+                                        periods: Optimizer::period_column(
+                                            &grid,
+                                            &periods,
+                                            &params.period_col,
+                                        ),
+                                        npv: sched.npv(),
+                                    });
+                                })?;
+                                let periods = optimizer.sched.periods(&grid.dims);
+                                Ok(JobOutput::Schedule {
+                                    grid: params.grid.clone(),
+                                    periods: Optimizer::period_column(
+                                        &grid,
+                                        &periods,
+                                        &params.period_col,
+                                    ),
+                                    npv,
+                                })
+                            });
+                        }
+                        None => {
+                            jobs.last_pit = Some(Err("Block coordinates are not numeric".into()))
+                        }
+                    },
+                    None => jobs.last_pit = Some(Err("Select a block model".into())),
+                }
             }
 
             if ui.button("Close").clicked() {
@@ -331,6 +785,42 @@ pub fn init_optimizer(
             }
             ui.end_row();
         });
+
+        if jobs.is_running(PIT_JOB) {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Optimising in the background");
+            });
+        }
+        if jobs.is_running(SCHEDULE_JOB) {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Scheduling in the background, results are listed in Tools > Jobs");
+            });
+        }
+        match &jobs.last_pit {
+            Some(Ok(summary)) => {
+                ui.separator();
+                egui::Grid::new("pit_summary").striped(true).show(ui, |ui| {
+                    for (label, value) in [
+                        ("Blocks", summary.blocks.to_string()),
+                        ("Ore tonnes", format!("{:.0}", summary.ore_tonnes)),
+                        ("Waste tonnes", format!("{:.0}", summary.waste_tonnes)),
+                        ("Metal", format!("{:.1}", summary.metal)),
+                        ("Strip ratio", format!("{:.2}", summary.strip_ratio())),
+                        ("Undiscounted value", format!("{:.0}", summary.value)),
+                    ] {
+                        ui.label(label);
+                        ui.label(value);
+                        ui.end_row();
+                    }
+                });
+            }
+            Some(Err(e)) => {
+                ui.colored_label(egui::Color32::RED, e.as_str());
+            }
+            None => {}
+        }
     });
 }
 
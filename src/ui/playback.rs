@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    block_model::BlockModelDB,
+    export::{ExportRequest, ImageExport},
+    optimizer::OptimizeParams,
+    playback::{period_summaries, Playback},
+    ColorBarSelectionEvent,
+};
+
+use super::{block_model_combo, column_combo, export::ExportPanel, CheckedColumns, OpenPanels};
+
+fn money(value: f64) -> String {
+    format!("{:.0}", value)
+}
+
+pub fn playback_panel(
+    mut contexts: EguiContexts,
+    block_models: Res<BlockModelDB>,
+    params: Res<OptimizeParams>,
+    export: Res<ImageExport>,
+    export_panel: Res<ExportPanel>,
+    mut checked: ResMut<CheckedColumns>,
+    mut colorbar_events: EventWriter<ColorBarSelectionEvent>,
+    mut playback: ResMut<Playback>,
+    mut open_panels: ResMut<OpenPanels>,
+    mut error: Local<Option<String>>,
+) {
+    if !open_panels.playback {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let playback = &mut *playback;
+
+    egui::Window::new("Schedule Playback")
+        .open(&mut open_panels.playback)
+        .show(ctx, |ui| {
+            let layer = playback.layer();
+            block_model_combo(ui, "playback_bm", &block_models, &mut playback.grid);
+            if let Some(bm) = block_models.block_models.get(&playback.grid) {
+                column_combo(ui, "Period column", bm, &mut playback.column);
+            }
+            //a schedule is only played on the column it was loaded from
+            if playback.layer() != layer {
+                playback.summaries.clear();
+            }
+            if let Some(bm) = block_models.block_models.get(&playback.grid) {
+                if ui.button("Load Schedule").clicked() {
+                    match period_summaries(bm, &params, &playback.column) {
+                        Ok(summaries) => {
+                            playback.summaries = summaries;
+                            playback.playing = false;
+                            playback.show(0);
+                            checked.check(bm, &playback.column);
+                            colorbar_events.send(ColorBarSelectionEvent {
+                                grid: playback.grid.clone(),
+                                column: playback.column.clone(),
+                            });
+                            *error = None;
+                        }
+                        Err(e) => *error = Some(e),
+                    }
+                }
+            }
+            ui.label("Tonnage, grade and economics are set in Block Economics");
+            if let Some(error) = &*error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+            if playback.summaries.is_empty() {
+                return;
+            }
+
+            ui.separator();
+            let last = playback.summaries.len() - 1;
+            ui.add_enabled_ui(!playback.is_recording(), |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("⏮").clicked() {
+                        playback.show(0);
+                    }
+                    let label = if playback.playing { "Pause" } else { "Play" };
+                    if ui.button(label).clicked() {
+                        if !playback.playing && playback.current == last {
+                            playback.show(0);
+                        }
+                        playback.playing = !playback.playing;
+                    }
+                    if ui.button("⏭").clicked() {
+                        playback.show(last);
+                    }
+                    let mut current = playback.current;
+                    let slider = egui::Slider::new(&mut current, 0..=last).show_value(false);
+                    if ui.add(slider).changed() {
+                        playback.show(current);
+                    }
+                    ui.label(format!(
+                        "Period {}",
+                        playback.summaries[playback.current].period
+                    ));
+                });
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut playback.speed)
+                            .speed(0.1)
+                            .clamp_range(0.1..=30.0)
+                            .suffix(" periods/s"),
+                    );
+                    let ghost = ui.add(
+                        egui::Slider::new(&mut playback.ghost, 0.0..=1.0).text("Mined opacity"),
+                    );
+                    if ghost.drag_released() || (ghost.changed() && !ghost.dragged()) {
+                        playback.dirty = true;
+                    }
+                    ui.checkbox(&mut playback.highlight, "Outline current period");
+                });
+            });
+
+            let current = playback.current;
+            egui::ScrollArea::vertical()
+                .max_height(250.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("playback_periods")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for header in [
+                                "Period",
+                                "Tonnes",
+                                "Ore tonnes",
+                                "Grade",
+                                "Value",
+                                "Cumulative NPV",
+                            ] {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+                            let mut clicked = None;
+                            for (i, summary) in playback.summaries.iter().enumerate() {
+                                if ui
+                                    .selectable_label(i == current, summary.period.to_string())
+                                    .clicked()
+                                {
+                                    clicked = Some(i);
+                                }
+                                ui.label(money(summary.tonnes));
+                                ui.label(money(summary.ore_tonnes));
+                                ui.label(format!("{:.4}", summary.grade));
+                                ui.label(money(summary.value));
+                                ui.label(money(summary.cumulative_npv));
+                                ui.end_row();
+                            }
+                            if let Some(i) = clicked {
+                                if !playback.is_recording() {
+                                    playback.show(i);
+                                }
+                            }
+                        });
+                });
+
+            ui.separator();
+            if playback.is_recording() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!(
+                        "Exporting period {} of {}",
+                        playback.current + 1,
+                        last + 1
+                    ));
+                    if ui.button("Stop").clicked() {
+                        playback.stop_recording();
+                    }
+                });
+            } else {
+                ui.add_enabled_ui(!export.is_busy(), |ui| {
+                    if ui.button("Export PNG Sequence…").clicked() {
+                        if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                            let template = ExportRequest {
+                                path: dir.clone(),
+                                width: export_panel.width,
+                                height: export_panel.height,
+                                color_bars: export_panel.color_bars,
+                                panels: export_panel.panels,
+                            };
+                            playback.record(dir, template);
+                        }
+                    }
+                });
+                ui.label("Image size and overlays are set in Export Image");
+            }
+            if let Some(message) = &playback.message {
+                ui.label(message.as_str());
+            }
+        });
+}
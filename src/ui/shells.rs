@@ -0,0 +1,271 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{
+        self,
+        plot::{Bar, BarChart, Legend, Line, Plot, PlotPoints},
+    },
+    EguiContexts,
+};
+
+use crate::{
+    block_model::{BlockLayer, BlockModelDB},
+    jobs::{JobOutput, Jobs, SHELLS_JOB},
+    layers::LayerEvent,
+    optimizer::OptimizeParams,
+    shells::{nested_shells, revenue_factors, PitShell},
+    spatial_index::SpatialIndex,
+    ColorBarSelectionEvent,
+};
+
+use super::{block_model_combo, CheckedColumns, OpenPanels};
+
+#[derive(Resource)]
+pub struct ShellsPanel {
+    pub min_factor: f32,
+    pub max_factor: f32,
+    pub steps: usize,
+    pub column: String,
+    pub error: Option<String>,
+}
+
+impl Default for ShellsPanel {
+    fn default() -> Self {
+        Self {
+            min_factor: 0.3,
+            max_factor: 1.5,
+            steps: 13,
+            column: "pit_shell".into(),
+            error: None,
+        }
+    }
+}
+
+fn shell_plots(ui: &mut egui::Ui, shells: &[PitShell]) {
+    let x = |n: usize| (n + 1) as f64;
+    let ore = BarChart::new(
+        shells
+            .iter()
+            .enumerate()
+            .map(|(n, s)| Bar::new(x(n), s.pit.ore_tonnes).width(0.8))
+            .collect(),
+    )
+    .name("Ore tonnes");
+    let waste = BarChart::new(
+        shells
+            .iter()
+            .enumerate()
+            .map(|(n, s)| Bar::new(x(n), s.pit.waste_tonnes).width(0.8))
+            .collect(),
+    )
+    .name("Waste tonnes")
+    .stack_on(&[&ore]);
+    Plot::new("shells_tonnes")
+        .height(180.0)
+        .legend(Legend::default())
+        .link_axis(egui::Id::new("shells"), true, false)
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(ore);
+            plot_ui.bar_chart(waste);
+        });
+
+    let line = |value: fn(&PitShell) -> f64| -> PlotPoints {
+        shells
+            .iter()
+            .enumerate()
+            .map(|(n, s)| [x(n), value(s)])
+            .collect()
+    };
+    Plot::new("shells_value")
+        .height(180.0)
+        .legend(Legend::default())
+        .link_axis(egui::Id::new("shells"), true, false)
+        .show(ui, |plot_ui| {
+            plot_ui.line(Line::new(line(|s| s.pit.value)).name("Undiscounted"));
+            plot_ui.line(Line::new(line(|s| s.best_case)).name("Best case"));
+            plot_ui.line(Line::new(line(|s| s.worst_case)).name("Worst case"));
+        });
+    Plot::new("shells_strip")
+        .height(100.0)
+        .legend(Legend::default())
+        .link_axis(egui::Id::new("shells"), true, false)
+        .show(ui, |plot_ui| {
+            plot_ui.line(Line::new(line(|s| s.pit.strip_ratio())).name("Strip ratio"));
+        });
+}
+
+pub fn shells_panel(
+    mut contexts: EguiContexts,
+    block_models: Res<BlockModelDB>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    mut params: ResMut<OptimizeParams>,
+    mut jobs: ResMut<Jobs>,
+    mut checked: ResMut<CheckedColumns>,
+    mut layer_events: EventWriter<LayerEvent>,
+    mut colorbar_events: EventWriter<ColorBarSelectionEvent>,
+    mut panel: ResMut<ShellsPanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.shells {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+
+    egui::Window::new("Pit Shells")
+        .open(&mut open_panels.shells)
+        .show(ctx, |ui| {
+            block_model_combo(ui, "shells_bm", &block_models, &mut params.grid);
+            egui::Grid::new("shells_inputs").show(ui, |ui| {
+                ui.label("Revenue factors");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut panel.min_factor)
+                            .speed(0.01)
+                            .clamp_range(0.01..=10.0),
+                    );
+                    ui.label("to");
+                    ui.add(
+                        egui::DragValue::new(&mut panel.max_factor)
+                            .speed(0.01)
+                            .clamp_range(0.01..=10.0),
+                    );
+                    ui.label("in");
+                    ui.add(egui::DragValue::new(&mut panel.steps).clamp_range(1..=100));
+                    ui.label("pits");
+                });
+                ui.end_row();
+                ui.label("Mining rate (t/yr)");
+                ui.add(egui::DragValue::new(&mut params.mining_rate).clamp_range(0.0..=f32::MAX));
+                ui.end_row();
+                ui.label("Discount rate");
+                ui.add(
+                    egui::DragValue::new(&mut params.discount_rate)
+                        .speed(0.005)
+                        .clamp_range(0.0..=1.0),
+                );
+                ui.end_row();
+                ui.label("Shell column");
+                ui.text_edit_singleline(&mut panel.column);
+                ui.end_row();
+            });
+            ui.label("Price, costs and slope are set in Pit Optimisation");
+
+            let running = jobs.is_running(SHELLS_JOB);
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!running, egui::Button::new("Compute Shells"))
+                    .on_disabled_hover_text("Already running, see Tools > Jobs")
+                    .clicked()
+                {
+                    //the job works on copies so the viewer stays usable meanwhile
+                    let copies = block_models
+                        .block_models
+                        .get(&params.grid)
+                        .ok_or_else(|| "Select a block model".to_string())
+                        .and_then(|bm| {
+                            let grid = spatial_index
+                                .grid(bm)
+                                .ok_or_else(|| "Block coordinates are not numeric".to_string())?;
+                            Ok((bm.clone(), grid.clone()))
+                        });
+                    match copies {
+                        Ok((bm, grid)) => {
+                            let params = params.clone();
+                            let factors =
+                                revenue_factors(panel.min_factor, panel.max_factor, panel.steps);
+                            let column = panel.column.clone();
+                            jobs.last_shells = None;
+                            jobs.spawn(SHELLS_JOB, move |control| {
+                                let (shells, pits) =
+                                    nested_shells(&bm, &grid, &params, &factors, &column, control)?;
+                                Ok(JobOutput::Shells {
+                                    grid: params.grid.clone(),
+                                    shells,
+                                    pits,
+                                })
+                            });
+                            panel.error = None;
+                        }
+                        Err(e) => panel.error = Some(e),
+                    }
+                }
+                if running {
+                    ui.spinner();
+                }
+
+                let written = block_models
+                    .block_models
+                    .get(&params.grid)
+                    .filter(|bm| bm.columns.contains(&panel.column));
+                if ui
+                    .add_enabled(
+                        written.is_some(),
+                        egui::Button::new("Colour Blocks by Shell"),
+                    )
+                    .clicked()
+                {
+                    if let Some(bm) = written {
+                        checked.check(bm, &panel.column);
+                        layer_events.send(LayerEvent::Spawn(BlockLayer {
+                            grid: params.grid.clone(),
+                            column: panel.column.clone(),
+                        }));
+                        colorbar_events.send(ColorBarSelectionEvent {
+                            grid: params.grid.clone(),
+                            column: panel.column.clone(),
+                        });
+                    }
+                }
+            });
+            if let Some(error) = &panel.error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+
+            let shells = match &jobs.last_shells {
+                Some(Ok(shells)) => shells,
+                Some(Err(e)) => {
+                    ui.colored_label(egui::Color32::RED, e.as_str());
+                    return;
+                }
+                None => return,
+            };
+            ui.separator();
+            shell_plots(ui, shells);
+            egui::ScrollArea::vertical()
+                .max_height(250.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("shells_table")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for header in [
+                                "Pit",
+                                "Revenue factor",
+                                "Blocks",
+                                "Ore tonnes",
+                                "Waste tonnes",
+                                "Strip ratio",
+                                "Metal",
+                                "Undiscounted",
+                                "Best case",
+                                "Worst case",
+                            ] {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+                            for (n, shell) in shells.iter().enumerate() {
+                                ui.label((n + 1).to_string());
+                                ui.label(format!("{:.2}", shell.revenue_factor));
+                                ui.label(shell.pit.blocks.to_string());
+                                ui.label(format!("{:.0}", shell.pit.ore_tonnes));
+                                ui.label(format!("{:.0}", shell.pit.waste_tonnes));
+                                ui.label(format!("{:.2}", shell.pit.strip_ratio()));
+                                ui.label(format!("{:.1}", shell.pit.metal));
+                                ui.label(format!("{:.0}", shell.pit.value));
+                                ui.label(format!("{:.0}", shell.best_case));
+                                ui.label(format!("{:.0}", shell.worst_case));
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+}
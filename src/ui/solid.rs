@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    block_model::BlockModelDB,
+    jobs::{JobOutput, Jobs, SOLID_JOB},
+    solid::flag_in_solid,
+    surface::SurfaceIndex,
+    wireframe::Wireframes,
+};
+
+use super::{block_model_combo, OpenPanels};
+
+#[derive(Resource)]
+pub struct SolidPanel {
+    pub grid: String,
+    pub wireframe: usize,
+    pub flag_column: String,
+    pub percent_column: String,
+    /// Sub-cells per block along each axis for the percent-in-solid estimate.
+    pub samples: usize,
+    pub error: Option<String>,
+}
+
+impl Default for SolidPanel {
+    fn default() -> Self {
+        Self {
+            grid: String::new(),
+            wireframe: 0,
+            flag_column: "in_solid".into(),
+            percent_column: "pct_in_solid".into(),
+            samples: 4,
+            error: None,
+        }
+    }
+}
+
+pub fn solid_panel(
+    mut contexts: EguiContexts,
+    block_models: Res<BlockModelDB>,
+    mut jobs: ResMut<Jobs>,
+    wireframes: Res<Wireframes>,
+    mut panel: ResMut<SolidPanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.solid {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+
+    egui::Window::new("Inside Solid")
+        .open(&mut open_panels.solid)
+        .show(ctx, |ui| {
+            if wireframes.wireframes.is_empty() {
+                ui.label("Load a closed wireframe solid first");
+                return;
+            }
+            panel.wireframe = panel.wireframe.min(wireframes.wireframes.len() - 1);
+            egui::ComboBox::from_label("Solid")
+                .selected_text(wireframes.wireframes[panel.wireframe].name.as_str())
+                .show_ui(ui, |ui| {
+                    for (i, wireframe) in wireframes.wireframes.iter().enumerate() {
+                        ui.selectable_value(&mut panel.wireframe, i, wireframe.name.as_str());
+                    }
+                });
+            block_model_combo(ui, "solid_bm", &block_models, &mut panel.grid);
+            egui::Grid::new("solid_columns").show(ui, |ui| {
+                ui.label("Flag column");
+                ui.text_edit_singleline(&mut panel.flag_column);
+                ui.end_row();
+                ui.label("Percent column");
+                ui.text_edit_singleline(&mut panel.percent_column);
+                ui.end_row();
+                ui.label("Sub-cells per axis");
+                ui.add(egui::DragValue::new(&mut panel.samples).clamp_range(1..=16));
+                ui.end_row();
+            });
+
+            let running = jobs.is_running(SOLID_JOB);
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!running, egui::Button::new("Flag Blocks"))
+                    .on_disabled_hover_text("Already running, see Tools > Jobs")
+                    .clicked()
+                {
+                    match block_models.block_models.get(&panel.grid) {
+                        Some(bm) => {
+                            //the job works on copies so the viewer stays usable meanwhile
+                            let bm = bm.clone();
+                            let wireframe = &wireframes.wireframes[panel.wireframe];
+                            let (solid, mesh) = (wireframe.name.clone(), wireframe.mesh.clone());
+                            let grid = panel.grid.clone();
+                            let samples = panel.samples;
+                            let flag_column = panel.flag_column.clone();
+                            let percent_column = panel.percent_column.clone();
+                            jobs.spawn(SOLID_JOB, move |control| {
+                                let index = SurfaceIndex::new(&mesh);
+                                let (flags, percents) = flag_in_solid(
+                                    &bm,
+                                    &index,
+                                    samples,
+                                    &flag_column,
+                                    &percent_column,
+                                    control,
+                                )?;
+                                Ok(JobOutput::Solid {
+                                    grid,
+                                    solid,
+                                    flags,
+                                    percents,
+                                })
+                            });
+                            panel.error = None;
+                        }
+                        None => panel.error = Some("Select a block model".into()),
+                    }
+                }
+                if running {
+                    ui.spinner();
+                }
+            });
+            ui.label("Results are listed in Tools > Jobs");
+
+            if let Some(error) = &panel.error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+        });
+}
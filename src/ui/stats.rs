@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{
+        self,
+        plot::{Bar, BarChart, Plot},
+    },
+    EguiContexts,
+};
+
+use crate::{
+    block_model::BlockModelDB,
+    stats::{breakdown, ColumnStats, Histogram, StatsCache},
+};
+
+use super::{block_model_combo, column_combo, optional_column_combo, OpenPanels};
+
+#[derive(Resource)]
+pub struct StatsPanel {
+    pub grid: String,
+    pub column: String,
+    pub weight: Option<String>,
+    pub category: Option<String>,
+    pub bins: usize,
+    pub log: bool,
+    histogram: Option<Histogram>,
+    breakdown: Option<Vec<(String, ColumnStats)>>,
+}
+
+impl Default for StatsPanel {
+    fn default() -> Self {
+        Self {
+            grid: String::new(),
+            column: String::new(),
+            weight: None,
+            category: None,
+            bins: 30,
+            log: false,
+            histogram: None,
+            breakdown: None,
+        }
+    }
+}
+
+fn stats_grid(ui: &mut egui::Ui, id: &str, stats: &ColumnStats) {
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        for (label, value) in [
+            ("Count", stats.count as f64),
+            ("Nulls", stats.nulls as f64),
+            ("Min", stats.min),
+            ("Max", stats.max),
+            ("Mean", stats.mean),
+            ("Variance", stats.variance),
+            ("Std. dev.", stats.std_dev()),
+        ] {
+            ui.label(label);
+            ui.label(format!("{:.4}", value));
+            ui.end_row();
+        }
+        for (q, value) in stats.quantiles.iter() {
+            ui.label(format!("P{:.0}", q * 100.0));
+            ui.label(format!("{:.4}", value));
+            ui.end_row();
+        }
+    });
+}
+
+pub fn stats_panel(
+    mut contexts: EguiContexts,
+    block_models: Res<BlockModelDB>,
+    mut stats_cache: ResMut<StatsCache>,
+    mut panel: ResMut<StatsPanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.stats {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+
+    egui::Window::new("Column Statistics")
+        .open(&mut open_panels.stats)
+        .show(ctx, |ui| {
+            let mut changed = block_model_combo(ui, "stats_bm", &block_models, &mut panel.grid);
+            let Some(bm) = block_models.block_models.get(&panel.grid) else {
+                return;
+            };
+
+            changed |= column_combo(ui, "Column", bm, &mut panel.column);
+            changed |= optional_column_combo(ui, "Weight", bm, &mut panel.weight);
+            changed |= optional_column_combo(ui, "Breakdown by", bm, &mut panel.category);
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .add(egui::Slider::new(&mut panel.bins, 5..=200).text("Bins"))
+                    .changed();
+                changed |= ui.checkbox(&mut panel.log, "Log scale").changed();
+            });
+
+            if panel.column.is_empty() {
+                return;
+            }
+            if changed || panel.histogram.is_none() {
+                panel.histogram = Histogram::compute(
+                    bm,
+                    &panel.column,
+                    panel.weight.as_deref(),
+                    panel.bins,
+                    panel.log,
+                );
+                panel.breakdown = panel
+                    .category
+                    .as_deref()
+                    .and_then(|category| breakdown(bm, &panel.column, category));
+            }
+
+            ui.separator();
+            if let Some(stats) = stats_cache.get(bm, &panel.column) {
+                stats_grid(ui, "stats_summary", stats);
+            } else {
+                ui.label("Column is not numeric");
+                return;
+            }
+
+            if let Some(histogram) = &panel.histogram {
+                let log = panel.log;
+                let bars = histogram
+                    .counts
+                    .iter()
+                    .zip(histogram.edges.windows(2))
+                    .map(|(count, edge)| {
+                        let (lo, hi) = if log {
+                            (edge[0].log10(), edge[1].log10())
+                        } else {
+                            (edge[0], edge[1])
+                        };
+                        Bar::new((lo + hi) / 2.0, *count).width(hi - lo)
+                    })
+                    .collect();
+
+                Plot::new("stats_histogram")
+                    .height(200.0)
+                    .x_axis_formatter(move |x, _| {
+                        if log {
+                            format!("{:.3}", 10f64.powf(x))
+                        } else {
+                            format!("{:.3}", x)
+                        }
+                    })
+                    .show(ui, |plot_ui| {
+                        plot_ui.bar_chart(BarChart::new(bars).name(&panel.column));
+                    });
+            }
+
+            if let Some(breakdown) = &panel.breakdown {
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (category, stats) in breakdown.iter() {
+                            egui::CollapsingHeader::new(format!("{} ({})", category, stats.count))
+                                .id_source(("stats_breakdown", category))
+                                .show(ui, |ui| {
+                                    stats_grid(ui, &format!("stats_breakdown_{}", category), stats);
+                                });
+                        }
+                    });
+            }
+        });
+}
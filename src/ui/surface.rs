@@ -0,0 +1,217 @@
+use bevy::{prelude::*, render::view::RenderLayers};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    axes::ModelSpace,
+    block_model::BlockModelDB,
+    stats::StatsCache,
+    surface::{flag_below_surface, read_surface, Surface, SurfaceIndex, SurfaceShading, Surfaces},
+};
+
+use super::{block_model_combo, OpenPanels};
+
+#[derive(Resource)]
+pub struct SurfacePanel {
+    pub grid: String,
+    pub surface: usize,
+    pub column: String,
+    pub error: Option<String>,
+    pub message: Option<String>,
+}
+
+impl Default for SurfacePanel {
+    fn default() -> Self {
+        Self {
+            grid: String::new(),
+            surface: 0,
+            column: "below_topo".into(),
+            error: None,
+            message: None,
+        }
+    }
+}
+
+pub fn surface_panel(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut block_models: ResMut<BlockModelDB>,
+    mut stats_cache: ResMut<StatsCache>,
+    mut surfaces: ResMut<Surfaces>,
+    mut panel: ResMut<SurfacePanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.surface {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+
+    egui::Window::new("Topography")
+        .open(&mut open_panels.surface)
+        .show(ctx, |ui| {
+            if ui.button("Load Surface…").clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter(
+                        "surface",
+                        &["asc", "xyz", "txt", "csv", "obj", "stl", "dxf"],
+                    )
+                    .pick_file()
+                {
+                    match read_surface(&path) {
+                        Ok(mesh) => {
+                            let name = path
+                                .file_stem()
+                                .map(|s| s.to_string_lossy().to_string())
+                                .unwrap_or_else(|| "surface".into());
+                            let index = SurfaceIndex::new(&mesh);
+                            let mut surface = Surface {
+                                name,
+                                mesh,
+                                index,
+                                shading: SurfaceShading::Elevation,
+                                color: [0.55, 0.5, 0.4, 0.8],
+                                visible: true,
+                                entity: Entity::PLACEHOLDER,
+                                mesh_handle: Handle::default(),
+                                material: Handle::default(),
+                            };
+                            surface.mesh_handle = meshes.add(surface.to_mesh());
+                            surface.material = materials.add(surface.material());
+                            surface.entity = commands
+                                .spawn((
+                                    PbrBundle {
+                                        mesh: surface.mesh_handle.clone(),
+                                        material: surface.material.clone(),
+                                        ..default()
+                                    },
+                                    RenderLayers::layer(0),
+                                    ModelSpace,
+                                ))
+                                .id();
+                            surfaces.surfaces.push(surface);
+                            panel.error = None;
+                        }
+                        Err(e) => panel.error = Some(format!("Cannot load surface: {}", e)),
+                    }
+                }
+            }
+
+            if let Some(error) = &panel.error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+
+            let mut remove = None;
+            for (i, surface) in surfaces.surfaces.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.checkbox(&mut surface.visible, &surface.name).changed() {
+                        commands.entity(surface.entity).insert(if surface.visible {
+                            Visibility::Inherited
+                        } else {
+                            Visibility::Hidden
+                        });
+                    }
+
+                    let shading = surface.shading;
+                    egui::ComboBox::from_id_source(("surface_shading", i))
+                        .selected_text(match surface.shading {
+                            SurfaceShading::Solid => "Solid",
+                            SurfaceShading::Elevation => "Elevation",
+                        })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut surface.shading,
+                                SurfaceShading::Solid,
+                                "Solid",
+                            );
+                            ui.selectable_value(
+                                &mut surface.shading,
+                                SurfaceShading::Elevation,
+                                "Elevation",
+                            );
+                        });
+                    if surface.shading != shading {
+                        if let Some(mesh) = meshes.get_mut(&surface.mesh_handle) {
+                            *mesh = surface.to_mesh();
+                        }
+                        if let Some(material) = materials.get_mut(&surface.material) {
+                            *material = surface.material();
+                        }
+                    }
+
+                    let color_changed = if surface.shading == SurfaceShading::Solid {
+                        ui.color_edit_button_rgba_unmultiplied(&mut surface.color)
+                            .changed()
+                    } else {
+                        ui.add(egui::Slider::new(&mut surface.color[3], 0.0..=1.0).text("Opacity"))
+                            .changed()
+                    };
+                    if color_changed {
+                        if let Some(material) = materials.get_mut(&surface.material) {
+                            *material = surface.material();
+                        }
+                    }
+
+                    if ui.small_button("✖").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                let surface = surfaces.surfaces.remove(i);
+                commands.entity(surface.entity).despawn_recursive();
+                panel.surface = 0;
+            }
+
+            if surfaces.surfaces.is_empty() {
+                return;
+            }
+            ui.separator();
+            ui.label("Flag blocks against a surface");
+            panel.surface = panel.surface.min(surfaces.surfaces.len() - 1);
+            egui::ComboBox::from_label("Surface")
+                .selected_text(surfaces.surfaces[panel.surface].name.as_str())
+                .show_ui(ui, |ui| {
+                    for (i, surface) in surfaces.surfaces.iter().enumerate() {
+                        ui.selectable_value(&mut panel.surface, i, surface.name.as_str());
+                    }
+                });
+            block_model_combo(ui, "surface_bm", &block_models, &mut panel.grid);
+            ui.horizontal(|ui| {
+                ui.label("Column");
+                ui.text_edit_singleline(&mut panel.column);
+            });
+
+            if ui.button("Flag Blocks").clicked() {
+                let surface = &surfaces.surfaces[panel.surface];
+                let Some(bm) = block_models.block_models.get_mut(&panel.grid) else {
+                    panel.error = Some("Select a block model".into());
+                    return;
+                };
+                let result = flag_below_surface(bm, &surface.index, &panel.column)
+                    .ok_or_else(|| "Block coordinates are not numeric".to_string())
+                    .and_then(|flags| {
+                        let below = flags.sum::<i64>().unwrap_or(0);
+                        let covered = flags.len() - flags.null_count();
+                        bm.set_column(flags).map_err(|e| e.to_string())?;
+                        Ok((below, covered))
+                    });
+                match result {
+                    Ok((below, covered)) => {
+                        stats_cache.invalidate(&panel.grid);
+                        panel.message = Some(format!(
+                            "{} of {} covered blocks below {}",
+                            below, covered, surface.name
+                        ));
+                        panel.error = None;
+                    }
+                    Err(e) => panel.error = Some(e),
+                }
+            }
+
+            if let Some(message) = &panel.message {
+                ui.label(message.as_str());
+            }
+        });
+}
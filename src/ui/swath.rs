@@ -0,0 +1,221 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{
+        self,
+        plot::{Bar, BarChart, Legend, Line, Plot, PlotPoints, VLine},
+    },
+    EguiContexts,
+};
+
+use crate::{
+    axes::SceneAxes,
+    block_model::{Axis, BlockModelDB},
+    grade_tonnage::TonnageSource,
+    swath::{swath, Swath},
+};
+
+use super::{block_model_combo, column_combo, grade_tonnage::tonnage_source_ui, OpenPanels};
+
+#[derive(Resource)]
+pub struct SwathPanel {
+    pub grid: String,
+    pub column: String,
+    pub axis: Axis,
+    pub width: f64,
+    pub weighted: bool,
+    pub tonnage: TonnageSource,
+    /// `(block model, column)` pairs to overlay.
+    pub series: Vec<(String, String)>,
+    pub swaths: Vec<Swath>,
+    /// Extent of the models the swaths were computed over.
+    pub bounds: Option<(Vec3, Vec3)>,
+    pub slice: usize,
+    pub highlight: bool,
+}
+
+impl Default for SwathPanel {
+    fn default() -> Self {
+        Self {
+            grid: String::new(),
+            column: String::new(),
+            axis: Axis::X,
+            width: 50.0,
+            weighted: false,
+            tonnage: TonnageSource::default(),
+            series: Vec::new(),
+            swaths: Vec::new(),
+            bounds: None,
+            slice: 0,
+            highlight: true,
+        }
+    }
+}
+
+impl SwathPanel {
+    fn compute(&mut self, block_models: &BlockModelDB) {
+        let axis = self.axis.index();
+        self.bounds = self
+            .series
+            .iter()
+            .filter_map(|(grid, _)| block_models.block_models.get(grid)?.bounds())
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)));
+
+        let Some((min, _)) = self.bounds else {
+            self.swaths.clear();
+            return;
+        };
+        let origin = (min[axis] as f64 / self.width).floor() * self.width;
+
+        self.swaths = self
+            .series
+            .iter()
+            .filter_map(|(grid, column)| {
+                let bm = block_models.block_models.get(grid)?;
+                swath(
+                    bm,
+                    column,
+                    self.axis,
+                    origin,
+                    self.width,
+                    &self.tonnage,
+                    self.weighted,
+                )
+            })
+            .collect();
+    }
+}
+
+pub fn swath_panel(
+    mut contexts: EguiContexts,
+    block_models: Res<BlockModelDB>,
+    mut panel: ResMut<SwathPanel>,
+    mut open_panels: ResMut<OpenPanels>,
+) {
+    if !open_panels.swath {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let panel = &mut *panel;
+
+    egui::Window::new("Swath Plot")
+        .open(&mut open_panels.swath)
+        .show(ctx, |ui| {
+            let mut changed = false;
+
+            block_model_combo(ui, "swath_bm", &block_models, &mut panel.grid);
+            if let Some(bm) = block_models.block_models.get(&panel.grid) {
+                ui.horizontal(|ui| {
+                    column_combo(ui, "Column", bm, &mut panel.column);
+                    if ui.button("Add").clicked() && !panel.column.is_empty() {
+                        panel
+                            .series
+                            .push((panel.grid.clone(), panel.column.clone()));
+                        changed = true;
+                    }
+                });
+                changed |= tonnage_source_ui(ui, bm, &mut panel.tonnage);
+                changed |= ui
+                    .checkbox(&mut panel.weighted, "Weight mean by tonnes")
+                    .changed();
+            }
+
+            let mut remove = None;
+            for (i, (grid, column)) in panel.series.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}: {}", grid, column));
+                    if ui.small_button("✖").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                panel.series.remove(i);
+                changed = true;
+            }
+
+            ui.horizontal(|ui| {
+                for axis in Axis::ALL {
+                    changed |= ui
+                        .selectable_value(&mut panel.axis, axis, axis.name())
+                        .changed();
+                }
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut panel.width)
+                            .clamp_range(0.01..=f64::MAX)
+                            .prefix("Slice width: "),
+                    )
+                    .changed();
+                if ui.button("Recompute").clicked() {
+                    changed = true;
+                }
+            });
+
+            if changed {
+                panel.compute(&block_models);
+            }
+
+            let Some(first) = panel.swaths.first() else {
+                return;
+            };
+            let slices = panel.swaths.iter().map(|s| s.mean.len()).max().unwrap_or(0);
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::Slider::new(&mut panel.slice, 0..=slices.saturating_sub(1)).text("Slice"),
+                );
+                ui.checkbox(&mut panel.highlight, "Highlight in view");
+            });
+            let current = first.slice_centre(panel.slice);
+
+            Plot::new("swath_means")
+                .height(220.0)
+                .legend(Legend::default())
+                .link_axis(egui::Id::new("swath"), true, false)
+                .show(ui, |plot_ui| {
+                    for s in panel.swaths.iter() {
+                        let points: PlotPoints = s
+                            .mean
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(i, m)| Some([s.slice_centre(i), (*m)?]))
+                            .collect();
+                        plot_ui.line(Line::new(points).name(&s.name));
+                    }
+                    plot_ui.vline(VLine::new(current));
+                });
+
+            let bars = first
+                .tonnes
+                .iter()
+                .enumerate()
+                .map(|(i, t)| Bar::new(first.slice_centre(i), *t).width(first.width * 0.9))
+                .collect();
+            Plot::new("swath_weights")
+                .height(120.0)
+                .link_axis(egui::Id::new("swath"), true, false)
+                .show(ui, |plot_ui| {
+                    plot_ui.bar_chart(BarChart::new(bars).name("Tonnes"));
+                    plot_ui.vline(VLine::new(current));
+                });
+        });
+}
+
+/// Outlines the current swath slice across the extent of the swathed models.
+pub fn highlight_swath_slice(
+    panel: Res<SwathPanel>,
+    open_panels: Res<OpenPanels>,
+    axes: Res<SceneAxes>,
+    mut gizmos: Gizmos,
+) {
+    if !open_panels.swath || !panel.highlight {
+        return;
+    }
+    let (Some((mut min, mut max)), Some(first)) = (panel.bounds, panel.swaths.first()) else {
+        return;
+    };
+    let axis = panel.axis.index();
+    min[axis] = (first.origin + panel.slice as f64 * first.width) as f32;
+    max[axis] = min[axis] + first.width as f32;
+
+    gizmos.cuboid(axes.box_transform(min, max), Color::YELLOW);
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+
+use bevy::{ecs::system::SystemParam, prelude::*, render::view::RenderLayers};
+use bevy_egui::egui;
+
+use crate::{
+    axes::ModelSpace,
+    isosurface::shell_material,
+    mesh_io::read_mesh,
+    wireframe::{Wireframe, Wireframes},
+};
+
+/// Wireframe layers and the assets needed to (re)build them, listed in the side panel.
+#[derive(SystemParam)]
+pub struct WireframeLayers<'w, 's> {
+    commands: Commands<'w, 's>,
+    wireframes: ResMut<'w, Wireframes>,
+    materials: ResMut<'w, Assets<StandardMaterial>>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    error: Local<'s, Option<String>>,
+}
+
+impl WireframeLayers<'_, '_> {
+    pub fn pick_and_import(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("wireframe", &["obj", "stl", "dxf"])
+            .pick_file()
+        {
+            self.import(&path);
+        }
+    }
+
+    pub fn import(&mut self, path: &Path) {
+        let mesh = match read_mesh(path) {
+            Ok(mesh) => mesh,
+            Err(e) => {
+                *self.error = Some(format!("Cannot load wireframe: {}", e));
+                return;
+            }
+        };
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "wireframe".into());
+        let mut wireframe = Wireframe::new(name, mesh);
+
+        wireframe.face_material = self.materials.add(shell_material(wireframe.color));
+        wireframe.edge_material = self.materials.add(wireframe.edge_material());
+        wireframe.face_entity = self
+            .commands
+            .spawn((
+                PbrBundle {
+                    mesh: self.meshes.add(wireframe.mesh.to_mesh()),
+                    material: wireframe.face_material.clone(),
+                    ..default()
+                },
+                RenderLayers::layer(0),
+                ModelSpace,
+            ))
+            .id();
+        wireframe.edge_entity = self
+            .commands
+            .spawn((
+                PbrBundle {
+                    mesh: self.meshes.add(wireframe.edge_mesh()),
+                    material: wireframe.edge_material.clone(),
+                    ..default()
+                },
+                RenderLayers::layer(0),
+                ModelSpace,
+            ))
+            .id();
+        self.wireframes.wireframes.push(wireframe);
+        *self.error = None;
+    }
+
+    /// Side panel section listing the wireframes with their display settings.
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.heading("Wireframes");
+            if ui.small_button("Load…").clicked() {
+                self.pick_and_import();
+            }
+        });
+        ui.separator();
+        if let Some(error) = &*self.error {
+            ui.colored_label(egui::Color32::RED, error.as_str());
+        }
+
+        let mut remove = None;
+        for (i, wireframe) in self.wireframes.wireframes.iter_mut().enumerate() {
+            let mut changed = false;
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .checkbox(&mut wireframe.visible, &wireframe.name)
+                    .changed();
+                if ui.small_button("✖").clicked() {
+                    remove = Some(i);
+                }
+            });
+            ui.add_enabled_ui(wireframe.visible, |ui| {
+                ui.horizontal(|ui| {
+                    changed |= ui.checkbox(&mut wireframe.faces, "Faces").changed();
+                    if ui
+                        .color_edit_button_rgba_unmultiplied(&mut wireframe.color)
+                        .changed()
+                    {
+                        if let Some(material) = self.materials.get_mut(&wireframe.face_material) {
+                            *material = shell_material(wireframe.color);
+                        }
+                    }
+                    changed |= ui.checkbox(&mut wireframe.edges, "Edges").changed();
+                    if ui
+                        .color_edit_button_rgba_unmultiplied(&mut wireframe.edge_color)
+                        .changed()
+                    {
+                        if let Some(material) = self.materials.get_mut(&wireframe.edge_material) {
+                            *material = wireframe.edge_material();
+                        }
+                    }
+                })
+            });
+            if changed {
+                self.commands
+                    .entity(wireframe.face_entity)
+                    .insert(wireframe.face_visibility());
+                self.commands
+                    .entity(wireframe.edge_entity)
+                    .insert(wireframe.edge_visibility());
+            }
+        }
+        if let Some(i) = remove {
+            let wireframe = self.wireframes.wireframes.remove(i);
+            self.commands
+                .entity(wireframe.face_entity)
+                .despawn_recursive();
+            self.commands
+                .entity(wireframe.edge_entity)
+                .despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use bevy::{prelude::*, render::render_resource::PrimitiveTopology, utils::HashSet};
+
+use crate::{axes::SceneAxes, isosurface::IsoMesh};
+
+/// A triangulated solid or surface, e.g. a pit design, domain or stope, drawn as its
+/// faces and/or triangle edges.
+pub struct Wireframe {
+    pub name: String,
+    pub mesh: IsoMesh,
+    /// Bounds in model coordinates.
+    pub minimum: Vec3,
+    pub maximum: Vec3,
+    /// Unmultiplied RGBA of the faces.
+    pub color: [f32; 4],
+    pub edge_color: [f32; 4],
+    pub faces: bool,
+    pub edges: bool,
+    pub visible: bool,
+    pub face_entity: Entity,
+    pub edge_entity: Entity,
+    pub face_material: Handle<StandardMaterial>,
+    pub edge_material: Handle<StandardMaterial>,
+}
+
+impl Wireframe {
+    pub fn new(name: String, mesh: IsoMesh) -> Self {
+        let (minimum, maximum) = mesh.positions.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), p| (min.min(Vec3::from(*p)), max.max(Vec3::from(*p))),
+        );
+        Self {
+            name,
+            mesh,
+            minimum,
+            maximum,
+            color: [0.8, 0.6, 0.2, 0.6],
+            edge_color: [0.1, 0.1, 0.1, 1.0],
+            faces: true,
+            edges: true,
+            visible: true,
+            face_entity: Entity::PLACEHOLDER,
+            edge_entity: Entity::PLACEHOLDER,
+            face_material: Handle::default(),
+            edge_material: Handle::default(),
+        }
+    }
+
+    /// Each triangle edge once, as a line list.
+    pub fn edge_mesh(&self) -> Mesh {
+        let mut edges = HashSet::new();
+        for tri in self.mesh.indices.chunks_exact(3) {
+            for (a, b) in [(tri[0], tri[1]), (tri[1], tri[2]), (tri[2], tri[0])] {
+                edges.insert((a.min(b), a.max(b)));
+            }
+        }
+        let positions = edges
+            .into_iter()
+            .flat_map(|(a, b)| {
+                [
+                    self.mesh.positions[a as usize],
+                    self.mesh.positions[b as usize],
+                ]
+            })
+            .collect::<Vec<_>>();
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh
+    }
+
+    pub fn edge_material(&self) -> StandardMaterial {
+        let [r, g, b, a] = self.edge_color;
+        StandardMaterial {
+            base_color: Color::rgba(r, g, b, a),
+            alpha_mode: if a < 1.0 {
+                AlphaMode::Blend
+            } else {
+                AlphaMode::Opaque
+            },
+            unlit: true,
+            ..default()
+        }
+    }
+
+    pub fn face_visibility(&self) -> Visibility {
+        if self.visible && self.faces {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        }
+    }
+
+    pub fn edge_visibility(&self) -> Visibility {
+        if self.visible && self.edges {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        }
+    }
+}
+
+/// Loaded wireframe layers.
+#[derive(Resource, Default)]
+pub struct Wireframes {
+    pub wireframes: Vec<Wireframe>,
+}
+
+impl Wireframes {
+    /// World space bounds of the visible wireframes.
+    pub fn visible_bounds(&self, axes: &SceneAxes) -> Option<(Vec3, Vec3)> {
+        self.wireframes
+            .iter()
+            .filter(|w| w.visible)
+            .map(|w| axes.box_to_world(w.minimum, w.maximum))
+            .reduce(|(amin, amax), (bmin, bmax)| (amin.min(bmin), amax.max(bmax)))
+    }
+}